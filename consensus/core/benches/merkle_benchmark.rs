@@ -0,0 +1,41 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use consensus_core::{hashing, Hash, MerkleNode, MerkleTree, MAX_TRANSACTIONS_PER_BLOCK};
+
+/// The recursive midpoint-split build `MerkleTree::from_tx_hashes` replaced, kept as a baseline.
+fn build_tree_recursive(tx_hashes: &[Hash], start: usize, end: usize) -> MerkleNode {
+    if start == end {
+        return MerkleNode::Leaf(tx_hashes[start]);
+    }
+
+    let mid = start + (end - start) / 2;
+    let left = build_tree_recursive(tx_hashes, start, mid);
+    let right = build_tree_recursive(tx_hashes, mid + 1, end);
+
+    let combined = left.hash().as_bytes().iter().chain(right.hash().as_bytes().iter()).cloned().collect::<Vec<u8>>();
+    MerkleNode::Internal(hashing::double_sha256(&combined), Box::new(left), Box::new(right))
+}
+
+fn bench_merkle_tree(c: &mut Criterion) {
+    let mut group = c.benchmark_group("merkle_tree");
+
+    for len in [1_000, MAX_TRANSACTIONS_PER_BLOCK] {
+        let tx_hashes: Vec<Hash> = (0..len as u64).map(|i| Hash::from_le_u64([i, 1, 2, 3])).collect();
+        group.bench_function(format!("from_tx_hashes_{}", len), |b| {
+            b.iter(|| {
+                let tree = MerkleTree::from_tx_hashes(black_box(&tx_hashes)).unwrap();
+                black_box(tree.root());
+            });
+        });
+        group.bench_function(format!("recursive_{}", len), |b| {
+            b.iter(|| {
+                let root = build_tree_recursive(black_box(&tx_hashes), 0, tx_hashes.len() - 1);
+                black_box(root.hash());
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_merkle_tree);
+criterion_main!(benches);
@@ -1,6 +1,13 @@
 //! Acceptance data for block validation.
 
-use crate::{errors::ConsensusResult, Hash};
+use crate::{
+    block::Block,
+    errors::{ConsensusError, ConsensusResult},
+    ghostdag::GhostDag,
+    tx::Transaction,
+    utxo::{UtxoDiff, UtxoView},
+    Hash,
+};
 
 /// Acceptance data structure for block acceptance.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -29,6 +36,49 @@ impl AcceptanceData {
     }
 }
 
+/// Computes the transactions accepted by `block`: those of its mergeset blues, in the topological
+/// order of `GhostDag::ordered_mergeset`, followed by its own. Transactions the view already knows,
+/// i.e. accepted by an ancestor, are skipped and, on conflicting spends, the first in mergeset order wins.
+/// `view` is the UTXO state the block builds on and `block_transactions` resolves a block's transactions.
+pub fn compute_acceptance(
+    block: &Block,
+    ghostdag: &GhostDag,
+    view: &UtxoView,
+    block_transactions: &dyn Fn(&Hash) -> Option<Vec<Transaction>>,
+) -> ConsensusResult<AcceptanceData> {
+    let block_hash = block.hash();
+    let relations = ghostdag.get_relations(&block_hash).ok_or(ConsensusError::MissingGhostDagData { block: block_hash })?;
+
+    let mergeset_blues = ghostdag
+        .ordered_mergeset(block_hash)?
+        .into_iter()
+        .filter(|hash| relations.merge_set_blues.contains(hash));
+
+    let mut view = view.clone();
+    let mut accepted_tx_ids = Vec::new();
+    let mut accepted_block_hashes = Vec::new();
+    for merged in mergeset_blues.chain(std::iter::once(block_hash)) {
+        let transactions = block_transactions(&merged)
+            .ok_or_else(|| ConsensusError::Generic { msg: format!("Missing transactions for block {}", merged) })?;
+        for tx in transactions {
+            let tx_id = tx.id();
+            if view.is_known_tx(&tx_id) {
+                continue;
+            }
+            // Spends of outputs which are missing or already spent lose the conflict
+            if !tx.is_coinbase() && view.validate_tx(&tx, block.header.daa_score).is_err() {
+                continue;
+            }
+            view.apply_diff(&UtxoDiff::from_transaction(&tx, &view)?);
+            view.insert_known_tx_ids([tx_id]);
+            accepted_tx_ids.push(tx_id);
+        }
+        accepted_block_hashes.push(merged);
+    }
+
+    Ok(AcceptanceData::new(accepted_tx_ids, accepted_block_hashes))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -50,4 +100,89 @@ mod tests {
         let data = AcceptanceData::new(vec![], vec![Hash::default()]);
         assert!(data.validate().is_err());
     }
+
+    #[tokio::test]
+    async fn test_compute_acceptance_double_spend() {
+        use crate::header::Header;
+        use crate::tx::{TxInput, TxOutput};
+        use crate::utxo::{OutPoint, UtxoCollection};
+        use std::collections::HashMap;
+
+        let outpoint = OutPoint { tx_hash: Hash::from_le_u64([7, 0, 0, 0]), index: 0 };
+        let collection = UtxoCollection::new();
+        collection.insert(outpoint.clone(), TxOutput { value: 100, script_pubkey: vec![] }).unwrap();
+        let view = UtxoView::new_from_collection(&collection);
+
+        let spend = |value: u64| {
+            let input = TxInput { prev_tx_hash: outpoint.tx_hash, index: outpoint.index, script_sig: vec![], sequence: 0 };
+            Transaction::new(1, vec![input], vec![TxOutput { value, script_pubkey: vec![] }], 0)
+        };
+        let create_block = |parents: Vec<Hash>, nonce: u64, txs: Vec<Transaction>| {
+            let mut header = Header::new();
+            header.parents_by_level = vec![parents];
+            header.nonce = nonce;
+            Block::new_with_txs(header, txs)
+        };
+
+        let tx_a = spend(90);
+        let tx_b = spend(80);
+        let genesis = create_block(vec![], 0, vec![]);
+        let a = create_block(vec![genesis.hash()], 1, vec![tx_a.clone()]);
+        let b = create_block(vec![genesis.hash()], 2, vec![tx_b.clone()]);
+        let merge = create_block(vec![a.hash(), b.hash()], 3, vec![]);
+
+        let ghostdag = GhostDag::new(10);
+        for block in [&genesis, &a, &b, &merge] {
+            ghostdag.add_block(block).await.unwrap();
+        }
+        let bodies: HashMap<Hash, Vec<Transaction>> = HashMap::from([
+            (genesis.hash(), vec![]),
+            (a.hash(), vec![tx_a.clone()]),
+            (b.hash(), vec![tx_b.clone()]),
+            (merge.hash(), vec![]),
+        ]);
+
+        let acceptance = compute_acceptance(&merge, &ghostdag, &view, &|hash| bodies.get(hash).cloned()).unwrap();
+        // The siblings are ordered by hash and the first spend wins the conflict
+        let first = ghostdag.ordered_mergeset(merge.hash()).unwrap()[0];
+        let (winner, loser) = if first == a.hash() { (&tx_a, &b) } else { (&tx_b, &a) };
+        assert_eq!(acceptance.accepted_tx_ids, vec![winner.id()]);
+        assert_eq!(acceptance.accepted_block_hashes.last(), Some(&merge.hash()));
+        assert!(acceptance.accepted_block_hashes.contains(&loser.hash()));
+    }
+    #[tokio::test]
+    async fn test_compute_acceptance_skips_ancestor_coinbases() {
+        use crate::header::Header;
+        use std::collections::HashMap;
+
+        let create_block = |parents: Vec<Hash>, reward: u64| {
+            let mut header = Header::new();
+            header.parents_by_level = vec![parents];
+            header.nonce = reward;
+            Block::new_with_txs(header, vec![crate::create_coinbase_transaction(reward, vec![])])
+        };
+        let g = create_block(vec![], 50);
+        let a = create_block(vec![g.hash()], 51);
+        let b = create_block(vec![a.hash()], 52);
+        let ghostdag = GhostDag::new(10);
+        for block in [&g, &a, &b] {
+            ghostdag.add_block(block).await.unwrap();
+        }
+        let bodies: HashMap<Hash, Vec<Transaction>> =
+            [&g, &a, &b].into_iter().map(|block| (block.hash(), block.transactions.clone())).collect();
+        let block_transactions = |hash: &Hash| bodies.get(hash).cloned();
+        let coinbase_id = |block: &Block| block.transactions[0].id();
+
+        // Each block builds on the view left by the acceptance of its selected parent
+        let mut view = UtxoView::new_from_collection(&crate::utxo::UtxoCollection::new());
+        let mut accepted = Vec::new();
+        for block in [&g, &a, &b] {
+            let acceptance = compute_acceptance(block, &ghostdag, &view, &block_transactions).unwrap();
+            for tx in bodies.values().flatten().filter(|tx| acceptance.accepted_tx_ids.contains(&tx.id())) {
+                view.apply_diff(&UtxoDiff::from_transaction(tx, &view).unwrap());
+            }
+            accepted.push(acceptance.accepted_tx_ids);
+        }
+        assert_eq!(accepted, vec![vec![coinbase_id(&g)], vec![coinbase_id(&a)], vec![coinbase_id(&b)]]);
+    }
 }
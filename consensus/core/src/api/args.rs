@@ -1,7 +1,6 @@
 use clap::Parser;
-use std::str::FromStr;
 use crate::config::{Config, ConfigBuilder};
-use crate::network::{ContextualNetAddress, NetAddress};
+use crate::network::ContextualNetAddress;
 
 fn validate_ram_scale(s: &str) -> Result<f64, String> {
     let value: f64 = s.parse().map_err(|_| "invalid ram_scale: not a number")?;
@@ -57,11 +56,11 @@ pub struct Args {
 
     /// P2P listen address (default: 0.0.0.0)
     #[arg(long)]
-    pub p2p_listen_address: Option<String>,
+    pub p2p_listen_address: Option<ContextualNetAddress>,
 
-    /// External IP address
+    /// External IP address, optionally with a port (default: the network's P2P port)
     #[arg(long)]
-    pub externalip: Option<String>,
+    pub externalip: Option<ContextualNetAddress>,
 
     /// Block template cache lifetime in seconds
     #[arg(long)]
@@ -83,6 +82,7 @@ pub struct Args {
 impl Args {
     /// Build a Config from the parsed arguments.
     pub fn build_config(self, params: crate::config::params::Params) -> Config {
+        let default_port = params.network_id.default_p2p_port();
         let mut builder = ConfigBuilder::new(params);
 
         if self.archival {
@@ -104,14 +104,10 @@ impl Args {
                 config.retention_period_days = self.retention_period_days;
                 config.block_template_cache_lifetime = self.block_template_cache_lifetime;
 
-                if let Some(ref addr) = self.p2p_listen_address {
-                    // Parse address, for simplicity assume it's an IP:port
-                    // In real impl, handle properly
-                    config.p2p_listen_address = ContextualNetAddress::from_str(addr).unwrap_or_default();
-                }
-                if let Some(ref ip) = self.externalip {
-                    config.externalip = Some(NetAddress::from_str(ip).unwrap_or_default());
+                if let Some(addr) = self.p2p_listen_address {
+                    config.p2p_listen_address = addr;
                 }
+                config.externalip = self.externalip.map(|ip| ip.normalize(default_port));
             })
             .build()
     }
@@ -141,6 +137,7 @@ mod tests {
     use super::*;
     use clap::CommandFactory;
     use crate::config::params::Params; // Assuming params is available
+    use crate::network::NetAddress;
 
     #[test]
     fn test_args_parse() {
@@ -173,6 +170,17 @@ mod tests {
         assert!(cmd.get_arguments().any(|arg| arg.get_id() == "ram_scale"));
     }
 
+    #[test]
+    fn test_address_args() {
+        assert!(Args::try_parse_from(["consensus", "--externalip", "notanip"]).is_err());
+        assert!(Args::try_parse_from(["consensus", "--p2p-listen-address", "0.0.0.0:port"]).is_err());
+
+        let args = Args::parse_from(["consensus", "--externalip", "203.0.113.5", "--p2p-listen-address", "[::]:16112"]);
+        let config = args.build_config(Params::default());
+        assert_eq!(config.externalip, Some("203.0.113.5:16111".parse::<NetAddress>().unwrap()));
+        assert_eq!(config.p2p_listen_address.to_string(), "[::]:16112");
+    }
+
     #[test]
     fn test_invalid_ram_scale() {
         let result = Args::try_parse_from(["consensus", "--ram-scale", "-1.0"]);
@@ -14,6 +14,8 @@ pub struct Counters {
     pub blocks_rejected: AtomicU64,
     /// Number of pruning operations
     pub pruning_operations: AtomicU64,
+    /// Number of blocks whose processing exceeded the timeout
+    pub blocks_timed_out: AtomicU64,
 }
 
 impl Counters {
@@ -42,6 +44,11 @@ impl Counters {
         self.pruning_operations.fetch_add(1, Ordering::Relaxed);
     }
 
+    /// Increment the blocks timed out counter
+    pub fn increment_blocks_timed_out(&self) {
+        self.blocks_timed_out.fetch_add(1, Ordering::Relaxed);
+    }
+
     /// Get a snapshot of current counter values
     pub fn get_snapshot(&self) -> HashMap<&'static str, u64> {
         HashMap::from([
@@ -50,6 +57,7 @@ impl Counters {
             ("validation_errors", self.validation_errors.load(Ordering::Relaxed)),
             ("blocks_rejected", self.blocks_rejected.load(Ordering::Relaxed)),
             ("pruning_operations", self.pruning_operations.load(Ordering::Relaxed)),
+            ("blocks_timed_out", self.blocks_timed_out.load(Ordering::Relaxed)),
         ])
     }
 
@@ -60,6 +68,7 @@ impl Counters {
         self.validation_errors.store(0, Ordering::Relaxed);
         self.blocks_rejected.store(0, Ordering::Relaxed);
         self.pruning_operations.store(0, Ordering::Relaxed);
+        self.blocks_timed_out.store(0, Ordering::Relaxed);
     }
 }
 
@@ -117,13 +126,15 @@ mod tests {
         counters.increment_validation_errors();
         counters.increment_blocks_rejected();
         counters.increment_pruning_operations();
+        counters.increment_blocks_timed_out();
 
         let snapshot = counters.get_snapshot();
-        assert_eq!(snapshot.len(), 5);
+        assert_eq!(snapshot.len(), 6);
         assert_eq!(snapshot["blocks_processed"], 1);
         assert_eq!(snapshot["transactions_validated"], 1);
         assert_eq!(snapshot["validation_errors"], 1);
         assert_eq!(snapshot["blocks_rejected"], 1);
         assert_eq!(snapshot["pruning_operations"], 1);
+        assert_eq!(snapshot["blocks_timed_out"], 1);
     }
 }
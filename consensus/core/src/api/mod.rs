@@ -1,6 +1,7 @@
 use futures_util::future::BoxFuture;
 use crate::muhash::MuHash;
 use std::sync::Arc;
+use std::time::Duration;
 use async_trait::async_trait;
 
 use crate::{
@@ -9,11 +10,14 @@ use crate::{
     block::{Block, BlockTemplate, TemplateBuildMode, TemplateTransactionSelector, VirtualStateApproxId},
     blockstatus::BlockStatus,
     coinbase::MinerData,
+    config::constants::perf::{PerfParams, PERF_PARAMS},
     daa_score_timestamp::DaaScoreTimestamp,
+    ghostdag::GhostDag,
     errors::{
         block::{BlockProcessResult, RuleError},
         coinbase::CoinbaseResult,
         consensus::ConsensusResult,
+        ConsensusError,
         pruning::PruningImportResult,
         tx::TxResult,
     },
@@ -23,10 +27,11 @@ use crate::{
     trusted::{ExternalGhostdagData, TrustedBlock},
     tx::{MutableTransaction, SignableTransaction, Transaction, TransactionOutpoint, UtxoEntry},
     utxo::utxo_inquirer::UtxoInquirerError,
-    BlockHashSet, BlueWorkType, ChainPath,
+    BlockHashSet, BlueWorkType, ChainPath, KType,
 };
 use jio_hashes::Hash;
 
+pub use self::counters::Counters;
 pub use self::stats::{BlockCount, ConsensusStats};
 
 pub mod args;
@@ -388,6 +393,174 @@ pub trait ConsensusApi: Send + Sync {
 
 pub type DynConsensus = Arc<dyn ConsensusApi>;
 
+/// Validation step run on each block before it is inserted into the DAG.
+pub type BlockValidator = Arc<dyn Fn(&Block) -> BoxFuture<'static, ConsensusResult<()>> + Send + Sync>;
+
 /// Default implementation of ConsensusApi (stub).
-pub struct DefaultConsensusApi;
+pub struct DefaultConsensusApi {
+    ghostdag: Arc<GhostDag>,
+    counters: Arc<Counters>,
+    block_validator: BlockValidator,
+    block_processing_timeout: Duration,
+}
+
+impl DefaultConsensusApi {
+    /// Creates a new consensus API with the given GHOSTDAG k parameter.
+    pub fn new(k: KType) -> Self {
+        Self {
+            ghostdag: Arc::new(GhostDag::new(k)),
+            counters: Arc::new(Counters::default()),
+            block_validator: Arc::new(|block: &Block| {
+                let result = block.validate();
+                Box::pin(async move { result })
+            }),
+            block_processing_timeout: Duration::from_millis(PERF_PARAMS.block_processing_timeout_ms),
+        }
+    }
+
+    /// Applies the performance parameters, e.g. the block processing timeout.
+    pub fn with_perf_params(mut self, perf: &PerfParams) -> Self {
+        self.block_processing_timeout = Duration::from_millis(perf.block_processing_timeout_ms);
+        self
+    }
+
+    /// Replaces the validation step run before inserting each block.
+    pub fn with_block_validator(mut self, validator: BlockValidator) -> Self {
+        self.block_validator = validator;
+        self
+    }
+
+    /// Gets the GHOSTDAG manager.
+    pub fn ghostdag(&self) -> &Arc<GhostDag> {
+        &self.ghostdag
+    }
+
+    /// Gets the consensus counters.
+    pub fn counters(&self) -> &Arc<Counters> {
+        &self.counters
+    }
+
+    /// Validates a single block and inserts it into the DAG, failing with `ConsensusError::Timeout`
+    /// if this takes longer than the configured block processing timeout.
+    pub async fn process_block(&self, block: &Block) -> ConsensusResult<BlockStatus> {
+        let work = async {
+            (self.block_validator)(block).await?;
+            self.ghostdag.add_block(block).await.map(|_| BlockStatus::Valid)
+        };
+        let result = match tokio::time::timeout(self.block_processing_timeout, work).await {
+            Ok(result) => result,
+            Err(_) => {
+                self.counters.increment_blocks_timed_out();
+                Err(ConsensusError::Timeout { timeout_ms: self.block_processing_timeout.as_millis() as u64 })
+            }
+        };
+        match result {
+            Ok(_) => self.counters.increment_blocks_processed(),
+            Err(_) => self.counters.increment_blocks_rejected(),
+        }
+        result
+    }
+
+    /// Processes a batch of blocks in the order of [`crate::block::topological_sort`], returning
+    /// the results in that order, which keeps the input order for a batch that is already sorted.
+    /// If `abort_on_error` is set, processing stops at the first invalid block and the results end
+    /// with its error. A batch whose blocks form a parent cycle is not processed at all and every
+    /// block gets the sorting error.
+    pub async fn process_blocks(&self, blocks: Vec<Block>, abort_on_error: bool) -> Vec<ConsensusResult<BlockStatus>> {
+        let block_count = blocks.len();
+        let sorted = match crate::block::topological_sort(blocks) {
+            Ok(sorted) => sorted,
+            Err(err) => return vec![Err(err); block_count],
+        };
+        let mut results = Vec::with_capacity(sorted.len());
+        for block in sorted {
+            let result = self.process_block(&block).await;
+            let failed = result.is_err();
+            results.push(result);
+            if failed && abort_on_error {
+                break;
+            }
+        }
+        results
+    }
+}
 
+impl Default for DefaultConsensusApi {
+    fn default() -> Self {
+        Self::new(crate::constants::DEFAULT_GHOSTDAG_K)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_block(parents: Vec<Hash>, nonce: u64) -> Block {
+        let mut header = Header::new();
+        header.parents_by_level = vec![parents];
+        header.merkle_root = crate::hashing::hash_merkle_root(&[]);
+        header.nonce = nonce;
+        Block::new(header, vec![])
+    }
+
+    #[tokio::test]
+    async fn test_process_blocks_out_of_order() {
+        let api = DefaultConsensusApi::default();
+        let genesis = create_test_block(vec![], 0);
+        let a = create_test_block(vec![genesis.hash()], 1);
+        let b = create_test_block(vec![a.hash()], 2);
+        let c = create_test_block(vec![b.hash()], 3);
+
+        let results = api.process_blocks(vec![c.clone(), a.clone(), genesis.clone(), b.clone()], true).await;
+        assert_eq!(results, vec![Ok(BlockStatus::Valid); 4]);
+        assert_eq!(api.ghostdag().get_blue_score(&c.hash()), Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_process_blocks_invalid_in_middle() {
+        let genesis = create_test_block(vec![], 0);
+        let a = create_test_block(vec![genesis.hash()], 1);
+        let mut invalid = create_test_block(vec![a.hash()], 2);
+        invalid.header.merkle_root = Hash::default();
+        let b = create_test_block(vec![a.hash()], 3);
+        let invalid_hash = invalid.hash();
+        let blocks = vec![genesis, a, invalid, b.clone()];
+
+        let api = DefaultConsensusApi::default();
+        let results = api.process_blocks(blocks.clone(), true).await;
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[2], Err(crate::errors::ConsensusError::MerkleRootMismatch));
+        assert!(api.ghostdag().get_relations(&invalid_hash).is_none());
+        assert!(api.ghostdag().get_relations(&b.hash()).is_none());
+
+        let api = DefaultConsensusApi::default();
+        let results = api.process_blocks(blocks, false).await;
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[2], Err(crate::errors::ConsensusError::MerkleRootMismatch));
+        assert_eq!(results[3], Ok(BlockStatus::Valid));
+        assert_eq!(api.counters().get_snapshot()["blocks_rejected"], 1);
+        assert_eq!(api.counters().get_snapshot()["blocks_processed"], 3);
+    }
+
+    #[tokio::test]
+    async fn test_process_block_timeout() {
+        let perf = PerfParams { block_processing_timeout_ms: 50, ..PerfParams::default() };
+        let slow_validator: BlockValidator = Arc::new(|_: &Block| {
+            Box::pin(async {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                Ok(())
+            })
+        });
+        let api = DefaultConsensusApi::default().with_perf_params(&perf).with_block_validator(slow_validator);
+        let genesis = create_test_block(vec![], 0);
+
+        assert_eq!(api.process_block(&genesis).await, Err(ConsensusError::Timeout { timeout_ms: 50 }));
+        assert!(api.ghostdag().get_relations(&genesis.hash()).is_none());
+        assert_eq!(api.counters().get_snapshot()["blocks_timed_out"], 1);
+        assert_eq!(api.counters().get_snapshot()["blocks_rejected"], 1);
+
+        let api = DefaultConsensusApi::default().with_perf_params(&perf);
+        assert_eq!(api.process_block(&genesis).await, Ok(BlockStatus::Valid));
+        assert_eq!(api.counters().get_snapshot()["blocks_timed_out"], 0);
+    }
+}
@@ -1,6 +1,7 @@
 //! Block data structures.
 
-use crate::{header::Header, hashing, Hash, errors::ConsensusResult};
+use std::collections::{HashMap, HashSet, VecDeque};
+use crate::{header::Header, hashing, tx::{Transaction, TransactionOutpoint}, utxo::{OutPoint, UtxoView}, Hash, errors::{ConsensusError, ConsensusResult}};
 
 /// Block template for mining.
 #[derive(Debug, Clone, Default)]
@@ -28,24 +29,43 @@ pub struct VirtualStateApproxId(pub u64);
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Block {
     pub header: Header,
-    pub transactions: Vec<Hash>, // Placeholder for actual transaction hashes; will be replaced with Tx type
+    pub transactions: Vec<Transaction>,
     pub ghostdag_data: Option<crate::ghostdag::GhostDagData>,
 }
 
 impl Block {
     /// Creates a new block with the given header and transactions.
-    pub fn new(header: Header, transactions: Vec<Hash>) -> Self {
+    pub fn new(header: Header, transactions: Vec<Transaction>) -> Self {
         Self { header, transactions, ghostdag_data: None }
     }
 
+    /// Creates a new block, setting the header's merkle root to commit to `transactions`.
+    pub fn new_with_txs(header: Header, transactions: Vec<Transaction>) -> Self {
+        let mut block = Self::new(header, transactions);
+        block.header.merkle_root = hashing::hash_merkle_root(&block.tx_hashes());
+        block
+    }
+
+    /// Returns the hashes the merkle root commits to, in block order. These are the transaction ids,
+    /// which leave out signature scripts so the root can't be malleated.
+    pub fn tx_hashes(&self) -> Vec<Hash> {
+        self.transactions.iter().map(|tx| tx.id()).collect()
+    }
+
     /// Validates the block.
     pub fn validate(&self) -> ConsensusResult<()> {
-        // Basic validation: check merkle root
-        let merkle_root = hashing::hash_merkle_root(&self.transactions);
-        if self.header.merkle_root != merkle_root {
+        // Basic validation: check merkle root, which commits to the malleation-free ids
+        let tx_hashes = self.tx_hashes();
+        if !self.header.has_merkle_root(hashing::hash_merkle_root(&tx_hashes)) {
             return Err(crate::errors::ConsensusError::MerkleRootMismatch);
         }
 
+        ensure_unique_tx_ids(tx_hashes.into_iter())?;
+
+        for tx in &self.transactions {
+            tx.validate()?;
+        }
+
         // Additional validations can be added here (e.g., transaction count, mass, etc.)
         Ok(())
     }
@@ -59,6 +79,92 @@ impl Block {
     pub fn is_genesis(&self) -> bool {
         self.header.parents_by_level.iter().all(|level| level.is_empty())
     }
+
+    /// Returns the level-0 parents of the block.
+    pub fn direct_parents(&self) -> &[Hash] {
+        self.header.parents_at_level(0)
+    }
+}
+
+/// Sorts a batch of blocks parents-before-children by their direct parents. Blocks whose
+/// parents are all outside the batch come first; independent blocks keep their input order.
+pub fn topological_sort(blocks: Vec<Block>) -> ConsensusResult<Vec<Block>> {
+    let nodes: Vec<_> = blocks.iter().map(|block| (block.hash(), block.direct_parents().to_vec())).collect();
+    let order = topological_order(&nodes)?;
+    let mut slots: Vec<Option<Block>> = blocks.into_iter().map(Some).collect();
+    Ok(order.into_iter().map(|i| slots[i].take().unwrap()).collect())
+}
+
+/// Kahn's algorithm over `(hash, parents)` nodes, returning node positions in sorted order.
+fn topological_order(nodes: &[(Hash, Vec<Hash>)]) -> ConsensusResult<Vec<usize>> {
+    let positions: HashMap<Hash, usize> = nodes.iter().enumerate().map(|(i, (hash, _))| (*hash, i)).collect();
+    let mut in_degrees = vec![0usize; nodes.len()];
+    let mut children = vec![Vec::new(); nodes.len()];
+    for (i, (_, parents)) in nodes.iter().enumerate() {
+        for parent in parents.iter().collect::<HashSet<_>>() {
+            if let Some(&parent_position) = positions.get(parent) {
+                in_degrees[i] += 1;
+                children[parent_position].push(i);
+            }
+        }
+    }
+
+    let mut queue: VecDeque<usize> = (0..nodes.len()).filter(|&i| in_degrees[i] == 0).collect();
+    let mut order = Vec::with_capacity(nodes.len());
+    while let Some(i) = queue.pop_front() {
+        order.push(i);
+        for &child in &children[i] {
+            in_degrees[child] -= 1;
+            if in_degrees[child] == 0 {
+                queue.push_back(child);
+            }
+        }
+    }
+
+    if order.len() != nodes.len() {
+        return Err(ConsensusError::CyclicBlockBatch);
+    }
+    Ok(order)
+}
+
+/// Validates that no transaction appears more than once in a block body.
+pub fn validate_no_duplicate_transactions(txs: &[Transaction]) -> ConsensusResult<()> {
+    ensure_unique_tx_ids(txs.iter().map(|tx| tx.id()))
+}
+
+/// Validates that every non-coinbase input in a block body spends an output that exists in
+/// `view` or was created by an earlier transaction of the block, and that no output is spent
+/// twice across the whole block.
+pub fn validate_inputs(txs: &[Transaction], view: &UtxoView) -> ConsensusResult<()> {
+    let mut created = HashSet::new();
+    let mut spent = HashSet::new();
+    for tx in txs {
+        let tx_id = tx.id();
+        if !tx.is_coinbase() {
+            for input in &tx.inputs {
+                let outpoint = OutPoint { tx_hash: input.prev_tx_hash, index: input.index };
+                let offending = || TransactionOutpoint { transaction_id: input.prev_tx_hash, index: input.index };
+                if view.get(&outpoint).is_none() && !created.contains(&outpoint) {
+                    return Err(ConsensusError::MissingInputUtxo { outpoint: offending(), tx_id });
+                }
+                if !spent.insert(outpoint) {
+                    return Err(ConsensusError::DoubleSpend { outpoint: offending(), tx_id });
+                }
+            }
+        }
+        created.extend((0..tx.outputs.len() as u32).map(|index| OutPoint { tx_hash: tx_id, index }));
+    }
+    Ok(())
+}
+
+fn ensure_unique_tx_ids(tx_ids: impl Iterator<Item = Hash>) -> ConsensusResult<()> {
+    let mut seen = HashSet::new();
+    for tx_id in tx_ids {
+        if !seen.insert(tx_id) {
+            return Err(ConsensusError::DuplicateTransaction { tx_id });
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -69,11 +175,28 @@ mod tests {
     #[test]
     fn test_block_new() {
         let header = Header::new();
-        let txs = vec![Hash::default()];
+        let txs = vec![crate::create_coinbase_transaction(50, vec![0x01])];
         let block = Block::new(header, txs);
         assert_eq!(block.transactions.len(), 1);
     }
 
+    #[test]
+    fn test_block_new_with_txs() {
+        let coinbase = crate::create_coinbase_transaction(50, vec![0x01]);
+        let tx = create_spending_tx(&[(Hash::from_slice(b"funding"), 0)]);
+        let block = Block::new_with_txs(Header::new(), vec![coinbase.clone(), tx.clone()]);
+        assert_eq!(block.tx_hashes(), vec![coinbase.id(), tx.id()]);
+        assert_eq!(block.header.merkle_root, hashing::hash_merkle_root(&block.tx_hashes()));
+        assert_eq!(block.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_block_validate_invalid_transaction() {
+        let no_outputs = Transaction::new(1, create_spending_tx(&[(Hash::from_slice(b"funding"), 0)]).inputs, vec![], 0);
+        let block = Block::new_with_txs(Header::new(), vec![no_outputs]);
+        assert!(matches!(block.validate(), Err(ConsensusError::TransactionValidation { .. })));
+    }
+
     #[test]
     fn test_block_validate_merkle_mismatch() {
         let mut header = Header::new();
@@ -82,6 +205,20 @@ mod tests {
         assert!(block.validate().is_err());
     }
 
+    #[test]
+    fn test_block_validate_merkle_root_uses_ids() {
+        let mut tx = create_spending_tx(&[(Hash::from_slice(b"funding"), 0)]);
+        tx.inputs[0].script_sig = vec![0x51];
+        assert_ne!(tx.hash(), tx.id());
+
+        let mut header = Header::new();
+        header.merkle_root = hashing::hash_merkle_root(&[tx.hash()]);
+        let block = Block::new(header, vec![tx.clone()]);
+        assert_eq!(block.validate(), Err(ConsensusError::MerkleRootMismatch));
+        assert!(!block.header.has_merkle_root(hashing::hash_merkle_root(&[tx.id()])));
+        assert_eq!(Block::new_with_txs(Header::new(), vec![tx]).validate(), Ok(()));
+    }
+
     #[test]
     fn test_block_hash() {
         let header = Header::new();
@@ -96,4 +233,111 @@ mod tests {
         let block = Block::new(header, vec![]);
         assert!(block.is_genesis());
     }
+
+    #[test]
+    fn test_validate_no_duplicate_transactions() {
+        let tx1 = crate::create_coinbase_transaction(50, vec![0x01]);
+        let tx2 = crate::create_coinbase_transaction(60, vec![0x01]);
+        assert!(validate_no_duplicate_transactions(&[tx1.clone(), tx2.clone()]).is_ok());
+        assert_eq!(
+            validate_no_duplicate_transactions(&[tx1.clone(), tx2, tx1.clone()]),
+            Err(ConsensusError::DuplicateTransaction { tx_id: tx1.id() })
+        );
+    }
+
+    #[test]
+    fn test_block_validate_duplicate_transactions() {
+        let tx = crate::create_coinbase_transaction(50, vec![0x01]);
+        let block = Block::new_with_txs(Header::new(), vec![tx.clone(), tx.clone()]);
+        assert_eq!(block.validate(), Err(ConsensusError::DuplicateTransaction { tx_id: tx.id() }));
+    }
+
+    fn create_child(parents: Vec<Hash>, nonce: u64) -> Block {
+        let mut header = Header::new();
+        header.parents_by_level = vec![parents];
+        header.nonce = nonce;
+        Block::new(header, vec![])
+    }
+
+    #[test]
+    fn test_topological_sort_shuffled() {
+        let external = Hash::from_slice(b"external");
+        let a = create_child(vec![external], 1);
+        let b = create_child(vec![a.hash()], 2);
+        let c = create_child(vec![a.hash()], 3);
+        let d = create_child(vec![b.hash(), c.hash()], 4);
+
+        let sorted = topological_sort(vec![d.clone(), c.clone(), b.clone(), a.clone()]).unwrap();
+        assert_eq!(sorted, vec![a, c, b, d]);
+    }
+
+    #[test]
+    fn test_topological_sort_cycle() {
+        // Real block hashes can't form a cycle, so exercise the ordering on raw nodes
+        let (x, y, z) = (Hash::from_slice(b"x"), Hash::from_slice(b"y"), Hash::from_slice(b"z"));
+        let nodes = vec![(x, vec![z]), (y, vec![x]), (z, vec![y])];
+        assert_eq!(topological_order(&nodes), Err(ConsensusError::CyclicBlockBatch));
+        assert_eq!(topological_order(&nodes[1..]), Ok(vec![0, 1]));
+    }
+
+    fn create_view(outpoints: &[OutPoint]) -> UtxoView {
+        let collection = crate::UtxoCollection::new();
+        for outpoint in outpoints {
+            collection.insert(outpoint.clone(), crate::tx::TxOutput { value: 100, script_pubkey: vec![] }).unwrap();
+        }
+        UtxoView::new_from_collection(&collection)
+    }
+
+    fn create_spending_tx(spends: &[(Hash, u32)]) -> Transaction {
+        let inputs = spends
+            .iter()
+            .map(|&(prev_tx_hash, index)| crate::tx::TxInput { prev_tx_hash, index, script_sig: vec![], sequence: 0 })
+            .collect();
+        Transaction::new(1, inputs, vec![crate::tx::TxOutput { value: 50, script_pubkey: vec![] }], 0)
+    }
+
+    #[test]
+    fn test_validate_inputs_clean_block() {
+        let funding = Hash::from_slice(b"funding");
+        let view = create_view(&[OutPoint { tx_hash: funding, index: 0 }, OutPoint { tx_hash: funding, index: 1 }]);
+        let coinbase = crate::create_coinbase_transaction(50, vec![0x01]);
+        let tx1 = create_spending_tx(&[(funding, 0)]);
+        // Spends both a UTXO and an output created earlier in the block
+        let tx2 = create_spending_tx(&[(funding, 1), (tx1.id(), 0)]);
+        assert_eq!(validate_inputs(&[coinbase, tx1, tx2], &view), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_inputs_double_spend() {
+        let funding = Hash::from_slice(b"funding");
+        let view = create_view(&[OutPoint { tx_hash: funding, index: 0 }]);
+        let tx1 = create_spending_tx(&[(funding, 0)]);
+        let mut tx2 = create_spending_tx(&[(funding, 0)]);
+        tx2.lock_time = 1;
+        assert_eq!(
+            validate_inputs(&[tx1, tx2.clone()], &view),
+            Err(ConsensusError::DoubleSpend {
+                outpoint: TransactionOutpoint { transaction_id: funding, index: 0 },
+                tx_id: tx2.id()
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_inputs_missing_utxo() {
+        let funding = Hash::from_slice(b"funding");
+        let view = create_view(&[OutPoint { tx_hash: funding, index: 0 }]);
+        let tx = create_spending_tx(&[(funding, 0), (funding, 5)]);
+        assert_eq!(
+            validate_inputs(std::slice::from_ref(&tx), &view),
+            Err(ConsensusError::MissingInputUtxo {
+                outpoint: TransactionOutpoint { transaction_id: funding, index: 5 },
+                tx_id: tx.id()
+            })
+        );
+
+        // An output created later in the block doesn't count
+        let early = create_spending_tx(&[(Hash::from_slice(b"later"), 0)]);
+        assert!(matches!(validate_inputs(&[early], &view), Err(ConsensusError::MissingInputUtxo { .. })));
+    }
 }
@@ -4,28 +4,22 @@ use std::collections::HashSet;
 use std::sync::Arc;
 use parking_lot::RwLock;
 use rayon::prelude::*;
-use crate::{Hash, errors::ConsensusResult, Block, ghostdag::GhostDag};
+use crate::{Hash, BlueWorkType, ChainPath, config::params::Params, errors::ConsensusResult, Block, ghostdag::GhostDag};
+
+/// How many block times the selected tip's timestamp may lag behind the clock for the node
+/// to still count as synced.
+const SYNC_TOLERANCE_BLOCK_TIMES: u64 = 10;
 
 /// Virtual state of the blockchain.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct VirtualState {
     pub selected_tip: Hash,
     pub blue_score: u64,
+    pub blue_work: BlueWorkType,
     pub daa_score: u64,
     pub merge_set: Vec<Hash>,
 }
 
-impl Default for VirtualState {
-    fn default() -> Self {
-        Self {
-            selected_tip: Hash::default(),
-            blue_score: 0,
-            daa_score: 0,
-            merge_set: Vec::new(),
-        }
-    }
-}
-
 /// Chain selector implementing tip selection and virtual state management.
 pub struct ChainSelector {
     ghostdag: Arc<GhostDag>,
@@ -49,12 +43,10 @@ impl ChainSelector {
             return Err(crate::errors::ConsensusError::NoTips);
         }
 
-        // Select tip with highest blue score
+        // Select tip with the highest chain-selection key
         let best_tip = tips
             .par_iter()
-            .max_by_key(|tip| {
-                self.ghostdag.get_blue_score(tip).unwrap_or(0)
-            })
+            .max_by_key(|tip| self.chain_key(tip))
             .cloned()
             .unwrap(); // Safe because tips is not empty
 
@@ -63,49 +55,152 @@ impl ChainSelector {
 
     /// Gets all current tips (blocks with no children).
     pub async fn get_all_tips(&self) -> ConsensusResult<Vec<Hash>> {
-        let mut tips = Vec::new();
+        Ok(self.tips())
+    }
 
-        // Find blocks that have no children
-        for entry in self.ghostdag.block_relations.iter() {
-            let block_hash = *entry.key();
-            let relations = entry.value();
+    /// Returns tips whose blue score is more than `threshold` below the best tip's.
+    pub fn stale_tips(&self, threshold: u64) -> Vec<Hash> {
+        let scored_tips: Vec<(Hash, u64)> =
+            self.tips().into_iter().map(|tip| (tip, self.ghostdag.get_blue_score(&tip).unwrap_or(0))).collect();
+        let best_score = scored_tips.iter().map(|(_, score)| *score).max().unwrap_or(0);
+
+        scored_tips
+            .into_iter()
+            .filter(|(_, score)| best_score - score > threshold)
+            .map(|(tip, _)| tip)
+            .collect()
+    }
 
-            if relations.children.read().is_empty() {
-                tips.push(block_hash);
+    /// Returns the mergeset of the virtual block, which has every current tip as a parent and the
+    /// best tip as selected parent. Blocks are ordered by ascending blue work, then hash.
+    pub fn virtual_mergeset(&self) -> Vec<Hash> {
+        let tips = self.tips();
+        let Some(selected_tip) = tips.iter().copied().max_by_key(|tip| self.chain_key(tip)) else {
+            return vec![];
+        };
+        let mut mergeset: Vec<(BlueWorkType, Hash)> = self
+            .ghostdag
+            .mergeset_of_parents(&tips, selected_tip)
+            .into_iter()
+            .map(|hash| self.chain_key(&hash).unwrap_or((BlueWorkType::from_u64(0), hash)))
+            .collect();
+        mergeset.sort();
+        mergeset.into_iter().map(|(_, hash)| hash).collect()
+    }
+
+    /// Builds a block locator from `tip`: selected-chain hashes walking back with gaps of
+    /// 1, 1, 2, 4, 8, ... blocks, always ending at genesis and holding at most `max_entries` hashes.
+    pub fn block_locator(&self, tip: Hash, max_entries: usize) -> Vec<Hash> {
+        let mut chain = vec![tip];
+        while let Some(parent) = self.ghostdag.selected_parent(*chain.last().unwrap()) {
+            chain.push(parent);
+        }
+        let genesis = *chain.last().unwrap();
+
+        let mut locator = Vec::new();
+        let (mut index, mut step) = (0, 1);
+        while index < chain.len() - 1 && locator.len() + 1 < max_entries {
+            locator.push(chain[index]);
+            index += step;
+            if locator.len() > 1 {
+                step *= 2;
             }
         }
-
-        Ok(tips)
+        if max_entries > 0 {
+            locator.push(genesis);
+        }
+        locator
     }
 
-    /// Updates the virtual state when a new block is added.
-    pub async fn update_virtual_state(&self, new_block: &Block) -> ConsensusResult<()> {
-        let current_blue_score = {
-            let state = self.virtual_state.read();
-            state.blue_score
+    /// Audits the selected chain from the best tip back to genesis: every block must claim a
+    /// strictly higher blue score than its selected parent. Returns the first violation found.
+    pub fn validate_selected_chain_scores(&self) -> ConsensusResult<()> {
+        let Some(mut current) = self.tips().into_iter().max_by_key(|tip| self.chain_key(tip)) else {
+            return Ok(());
+        };
+        let blue_score_of = |block: Hash| {
+            self.ghostdag
+                .get_relations(&block)
+                .map(|relations| relations.blue_score)
+                .ok_or(crate::errors::ConsensusError::MissingGhostDagData { block })
         };
 
-        let new_blue_score = new_block.header.blue_score;
+        let mut blue_score = blue_score_of(current)?;
+        while let Some(selected_parent) = self.ghostdag.selected_parent(current) {
+            let parent_blue_score = blue_score_of(selected_parent)?;
+            if blue_score <= parent_blue_score {
+                return Err(crate::errors::ConsensusError::NonIncreasingChainBlueScore {
+                    block: current,
+                    blue_score,
+                    selected_parent,
+                    parent_blue_score,
+                });
+            }
+            current = selected_parent;
+            blue_score = parent_blue_score;
+        }
+        Ok(())
+    }
+
+    /// Finds blocks that have no children.
+    fn tips(&self) -> Vec<Hash> {
+        self.ghostdag
+            .block_relations
+            .iter()
+            .filter(|entry| entry.value().children.read().is_empty())
+            .map(|entry| *entry.key())
+            .collect()
+    }
 
-        // Update if new block has higher blue score
-        if new_blue_score > current_blue_score {
+    /// Updates the virtual state when a new block is added.
+    pub async fn update_virtual_state(&self, new_block: &Block) -> ConsensusResult<()> {
+        // Update only if the new block wins chain selection
+        if self.should_reorg(new_block.hash()) {
+            // Blocks unknown to GhostDAG never win chain selection
+            let relations = self.ghostdag.get_relations(&new_block.hash()).ok_or(crate::errors::ConsensusError::MissingGhostDagData { block: new_block.hash() })?;
             let mut state = self.virtual_state.write();
             state.selected_tip = new_block.hash();
-            state.blue_score = new_blue_score;
+            // Score and work as computed by GhostDAG, not as claimed by the header
+            state.blue_score = relations.blue_score;
+            state.blue_work = relations.blue_work;
             state.daa_score = new_block.header.daa_score;
-            state.merge_set = new_block.ghostdag_data.as_ref()
-                .map(|data| data.merge_set_blues.clone())
-                .unwrap_or_default();
+            state.merge_set = relations.merge_set_blues;
         }
 
         Ok(())
     }
 
+    /// Checks if `candidate_tip` has strictly more blue work than the current virtual
+    /// selected tip, with ties broken by hash. Blocks unknown to GhostDAG never win.
+    pub fn should_reorg(&self, candidate_tip: Hash) -> bool {
+        let Some(candidate_key) = self.chain_key(&candidate_tip) else {
+            return false;
+        };
+        let state = self.virtual_state.read();
+        candidate_key > (state.blue_work, state.selected_tip)
+    }
+
+    /// Returns the chain-selection key of a block: its blue work, then its hash.
+    fn chain_key(&self, block_hash: &Hash) -> Option<(BlueWorkType, Hash)> {
+        self.ghostdag.get_blue_work(block_hash).map(|blue_work| (blue_work, *block_hash))
+    }
+
     /// Gets the current virtual state.
     pub fn get_virtual_state(&self) -> VirtualState {
         self.virtual_state.read().clone()
     }
 
+    /// Checks whether the node is synced: the virtual selected tip's timestamp must be within
+    /// `SYNC_TOLERANCE_BLOCK_TIMES` block times of `now`, both in milliseconds. Without a known
+    /// selected tip the node is not synced.
+    pub fn is_synced(&self, now: u64, params: &Params) -> bool {
+        let selected_tip = self.virtual_state.read().selected_tip;
+        let Some(relations) = self.ghostdag.get_relations(&selected_tip) else {
+            return false;
+        };
+        now.saturating_sub(relations.timestamp) <= SYNC_TOLERANCE_BLOCK_TIMES * params.target_time_per_block
+    }
+
     /// Handles chain reorganization.
     pub async fn handle_reorg(&self, old_tip: Hash, new_tip: Hash) -> ConsensusResult<()> {
         // Calculate blocks to add and remove during reorg
@@ -118,6 +213,14 @@ impl ChainSelector {
         Ok(())
     }
 
+    /// Returns the selected-chain path from the virtual selected tip to `candidate`: `removed`
+    /// lists the virtual chain blocks from the tip back, `added` the candidate chain blocks forward.
+    pub async fn virtual_chain_path_to(&self, candidate: Hash) -> ConsensusResult<ChainPath> {
+        let selected_tip = self.virtual_state.read().selected_tip;
+        let (added, removed) = self.calculate_reorg_path(selected_tip, candidate).await?;
+        Ok(ChainPath { added, removed })
+    }
+
     /// Calculates the reorganization path between two tips.
     async fn calculate_reorg_path(&self, old_tip: Hash, new_tip: Hash) -> ConsensusResult<(Vec<Hash>, Vec<Hash>)> {
         let mut added = Vec::new();
@@ -223,6 +326,7 @@ impl ChainSelector {
         Ok(VirtualState {
             selected_tip: tip,
             blue_score,
+            blue_work: self.ghostdag.get_blue_work(&tip).unwrap_or_default(),
             daa_score,
             merge_set,
         })
@@ -233,6 +337,16 @@ impl ChainSelector {
 mod tests {
     use super::*;
     use crate::ghostdag::GhostDag;
+    use crate::header::Header;
+
+    fn create_test_block(parents: Vec<Hash>, nonce: u64) -> Block {
+        let mut header = Header::new();
+        header.parents_by_level = vec![parents];
+        header.nonce = nonce;
+        // Blue work is derived from the target, so use a non-zero one
+        header.bits = 0x1f7fffff;
+        Block::new(header, vec![])
+    }
 
     #[tokio::test]
     async fn test_chain_selector_new() {
@@ -249,4 +363,195 @@ mod tests {
         let result = selector.select_tip().await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_is_synced() {
+        let params = Params::default();
+        let ghostdag = Arc::new(GhostDag::new(10));
+        let selector = ChainSelector::new(ghostdag.clone());
+        assert!(!selector.is_synced(0, &params));
+
+        let mut genesis = create_test_block(vec![], 0);
+        genesis.header.timestamp = 1_000_000;
+        ghostdag.add_block(&genesis).await.unwrap();
+        selector.update_virtual_state(&genesis).await.unwrap();
+
+        let tolerance = SYNC_TOLERANCE_BLOCK_TIMES * params.target_time_per_block;
+        assert!(selector.is_synced(1_000_000 + params.target_time_per_block, &params));
+        assert!(selector.is_synced(1_000_000 + tolerance, &params));
+        assert!(!selector.is_synced(1_000_000 + tolerance + 1, &params));
+        // A clock behind the tip still counts as synced
+        assert!(selector.is_synced(0, &params));
+    }
+
+    #[tokio::test]
+    async fn test_stale_tips() {
+        let ghostdag = Arc::new(GhostDag::new(10));
+        let genesis = create_test_block(vec![], 0);
+        ghostdag.add_block(&genesis).await.unwrap();
+
+        // A stale branch directly off genesis
+        let stale = create_test_block(vec![genesis.hash()], 1);
+        ghostdag.add_block(&stale).await.unwrap();
+
+        // A fresh chain growing well ahead of it
+        let mut fresh = genesis.hash();
+        for nonce in 2..7 {
+            let block = create_test_block(vec![fresh], nonce);
+            ghostdag.add_block(&block).await.unwrap();
+            fresh = block.hash();
+        }
+
+        let selector = ChainSelector::new(ghostdag);
+        assert_eq!(selector.stale_tips(2), vec![stale.hash()]);
+        assert!(selector.stale_tips(10).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_should_reorg() {
+        let ghostdag = Arc::new(GhostDag::new(10));
+        let genesis = create_test_block(vec![], 0);
+        let a = create_test_block(vec![genesis.hash()], 1);
+        let b = create_test_block(vec![a.hash()], 2);
+        let c = create_test_block(vec![genesis.hash()], 3);
+        for block in [&genesis, &a, &b, &c] {
+            ghostdag.add_block(block).await.unwrap();
+        }
+
+        let selector = ChainSelector::new(ghostdag.clone());
+        selector.update_virtual_state(&b).await.unwrap();
+        let state = selector.get_virtual_state();
+        assert_eq!(state.selected_tip, b.hash());
+        // Score and work come from GhostDAG; the header leaves its blue score at zero
+        assert_eq!(b.header.blue_score, 0);
+        assert_eq!(state.blue_score, 2);
+        assert_eq!(Some(state.blue_work), ghostdag.get_blue_work(&b.hash()));
+
+        // A lower-work tip doesn't displace the virtual
+        assert!(!selector.should_reorg(c.hash()));
+        selector.update_virtual_state(&c).await.unwrap();
+        assert_eq!(selector.get_virtual_state().selected_tip, b.hash());
+
+        // Extending the side branch past it does
+        let d = create_test_block(vec![c.hash()], 4);
+        let e = create_test_block(vec![d.hash()], 5);
+        ghostdag.add_block(&d).await.unwrap();
+        ghostdag.add_block(&e).await.unwrap();
+        assert!(selector.should_reorg(e.hash()));
+        selector.update_virtual_state(&e).await.unwrap();
+        assert_eq!(selector.get_virtual_state().selected_tip, e.hash());
+        assert_eq!(selector.select_tip().await.unwrap(), e.hash());
+    }
+
+    #[tokio::test]
+    async fn test_virtual_chain_path_to_side_chain() {
+        let ghostdag = Arc::new(GhostDag::new(10));
+        let genesis = create_test_block(vec![], 0);
+        let a = create_test_block(vec![genesis.hash()], 1);
+        let b = create_test_block(vec![a.hash()], 2);
+        let c = create_test_block(vec![genesis.hash()], 3);
+        let d = create_test_block(vec![c.hash()], 4);
+        for block in [&genesis, &a, &b, &c, &d] {
+            ghostdag.add_block(block).await.unwrap();
+        }
+
+        let selector = ChainSelector::new(ghostdag);
+        selector.update_virtual_state(&b).await.unwrap();
+
+        let path = selector.virtual_chain_path_to(d.hash()).await.unwrap();
+        assert_eq!(path.removed, vec![b.hash(), a.hash()]);
+        assert_eq!(path.added, vec![c.hash(), d.hash()]);
+
+        let path = selector.virtual_chain_path_to(b.hash()).await.unwrap();
+        assert!(path.added.is_empty() && path.removed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_virtual_mergeset() {
+        let ghostdag = Arc::new(GhostDag::new(10));
+        let genesis = create_test_block(vec![], 0);
+        ghostdag.add_block(&genesis).await.unwrap();
+
+        // Two branches off genesis: a long one that wins and a short one the virtual merges
+        let short = create_test_block(vec![genesis.hash()], 1);
+        ghostdag.add_block(&short).await.unwrap();
+        let short_tip = create_test_block(vec![short.hash()], 2);
+        ghostdag.add_block(&short_tip).await.unwrap();
+        let mut long = vec![];
+        let mut parent = genesis.hash();
+        for nonce in 3..7 {
+            let block = create_test_block(vec![parent], nonce);
+            ghostdag.add_block(&block).await.unwrap();
+            parent = block.hash();
+            long.push(parent);
+        }
+
+        let selector = ChainSelector::new(ghostdag.clone());
+        assert_eq!(selector.select_tip().await.unwrap(), *long.last().unwrap());
+
+        let mergeset: HashSet<Hash> = selector.virtual_mergeset().into_iter().collect();
+        assert_eq!(mergeset, HashSet::from([*long.last().unwrap(), short.hash(), short_tip.hash()]));
+
+        // A single block's mergeset only covers its own parents
+        let block_mergeset = ghostdag.ordered_mergeset(short_tip.hash()).unwrap();
+        assert_eq!(block_mergeset, vec![short.hash()]);
+    }
+
+    #[tokio::test]
+    async fn test_validate_selected_chain_scores() {
+        let ghostdag = Arc::new(GhostDag::new(10));
+        let selector = ChainSelector::new(ghostdag.clone());
+        assert_eq!(selector.validate_selected_chain_scores(), Ok(()));
+
+        let mut chain: Vec<Block> = vec![];
+        for nonce in 0..10 {
+            let parents = chain.last().map(|block: &Block| vec![block.hash()]).unwrap_or_default();
+            let block = create_test_block(parents, nonce);
+            ghostdag.add_block(&block).await.unwrap();
+            chain.push(block);
+        }
+        let side = create_test_block(vec![chain[3].hash()], 100);
+        ghostdag.add_block(&side).await.unwrap();
+        assert_eq!(selector.validate_selected_chain_scores(), Ok(()));
+
+        // A chain block claiming its selected parent's blue score
+        let (block, parent) = (chain[6].hash(), chain[5].hash());
+        let parent_blue_score = ghostdag.get_relations(&parent).unwrap().blue_score;
+        ghostdag.block_relations.get_mut(&block).unwrap().blue_score = parent_blue_score;
+        assert_eq!(
+            selector.validate_selected_chain_scores(),
+            Err(crate::errors::ConsensusError::NonIncreasingChainBlueScore {
+                block,
+                blue_score: parent_blue_score,
+                selected_parent: parent,
+                parent_blue_score,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_block_locator() {
+        let ghostdag = Arc::new(GhostDag::new(10));
+        let mut chain = vec![];
+        for nonce in 0..100 {
+            let parents = chain.last().map(|block: &Block| vec![block.hash()]).unwrap_or_default();
+            let block = create_test_block(parents, nonce);
+            ghostdag.add_block(&block).await.unwrap();
+            chain.push(block);
+        }
+        let selector = ChainSelector::new(ghostdag);
+        let tip = chain[99].hash();
+        let genesis = chain[0].hash();
+
+        // Heights back from the tip: gaps of 1, 1, 2, 4, ... then genesis
+        let locator = selector.block_locator(tip, 20);
+        let expected: Vec<Hash> = [99, 98, 97, 95, 91, 83, 67, 35, 0].iter().map(|&height| chain[height].hash()).collect();
+        assert_eq!(locator, expected);
+
+        // Capped locators still end at genesis
+        let capped = selector.block_locator(tip, 4);
+        assert_eq!(capped, vec![tip, chain[98].hash(), chain[97].hash(), genesis]);
+        assert!(selector.block_locator(tip, 0).is_empty());
+        assert_eq!(selector.block_locator(genesis, 20), vec![genesis]);
+    }
 }
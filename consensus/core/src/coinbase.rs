@@ -1,6 +1,14 @@
 //! Coinbase transaction utilities.
 
-use crate::{tx::{Transaction, TxInput, TxOutput}, Hash, errors::ConsensusResult};
+use crate::{
+    config::params::Params, constants::INITIAL_BLOCK_SUBSIDY, subnets::SUBNETWORK_ID_COINBASE, Hash,
+    errors::ConsensusResult,
+    tx::{
+        opcodes::{encode_ops, Op, OP_RETURN},
+        script_public_key::ScriptPublicKey,
+        Transaction, TxInput, TxOutput,
+    },
+};
 
 /// Miner data for coinbase transactions.
 #[derive(Debug, Clone, Default)]
@@ -18,23 +26,75 @@ pub fn create_coinbase_transaction(reward: u64, script_pubkey: Vec<u8>) -> Trans
         sequence: 0,
     };
     let output = TxOutput { value: reward, script_pubkey };
-    Transaction::new(1, vec![input], vec![output], 0)
+    Transaction::new(1, vec![input], vec![output], 0).with_subnetwork(SUBNETWORK_ID_COINBASE, 0, vec![])
 }
 
-/// Validates a coinbase transaction.
-/// Coinbase must have exactly one input with null prev_tx_hash, exactly one output, and the output value must be valid.
-pub fn validate_coinbase(tx: &Transaction) -> ConsensusResult<()> {
+/// Builds the coinbase reward output paying `reward` to `pay_to`. The miner's `extra_data` goes in a
+/// separate data output, see [`coinbase_outputs`].
+pub fn coinbase_output(reward: u64, _miner_data: &MinerData, pay_to: &ScriptPublicKey) -> TxOutput {
+    TxOutput { value: reward, script_pubkey: pay_to.script.clone() }
+}
+
+/// Builds the coinbase outputs: the reward output from [`coinbase_output`], followed by a zero-value
+/// `OP_RETURN` output carrying the miner's `extra_data` when there is any.
+pub fn coinbase_outputs(reward: u64, miner_data: &MinerData, pay_to: &ScriptPublicKey) -> Vec<TxOutput> {
+    let mut outputs = vec![coinbase_output(reward, miner_data, pay_to)];
+    if !miner_data.extra_data.is_empty() {
+        let script_pubkey = encode_ops(&[Op::Return, Op::push(&miner_data.extra_data)]);
+        outputs.push(TxOutput { value: 0, script_pubkey });
+    }
+    outputs
+}
+
+/// Returns the block subsidy at `blue_score`: the initial subsidy halved once per completed
+/// `halving_interval`, reaching zero once every bit has been shifted out.
+pub fn block_subsidy(blue_score: u64, params: &Params) -> u64 {
+    let era = blue_score.checked_div(params.halving_interval).unwrap_or(0);
+    u32::try_from(era).ok().and_then(|era| INITIAL_BLOCK_SUBSIDY.checked_shr(era)).unwrap_or(0)
+}
+
+/// Returns `(starting_blue_score, subsidy)` for each of the first `eras` halving eras.
+pub fn subsidy_schedule(params: &Params, eras: usize) -> Vec<(u64, u64)> {
+    (0..eras as u64)
+        .map(|era| {
+            let start = era.saturating_mul(params.halving_interval);
+            (start, block_subsidy(start, params))
+        })
+        .collect()
+}
+
+/// Validates a coinbase transaction and its payout.
+/// Coinbase must have exactly one input with null prev_tx_hash and a reward output, which may only
+/// be followed by zero-value `OP_RETURN` data outputs, see [`coinbase_outputs`]. The reward may claim
+/// at most the block subsidy at `blue_score` plus the fees of the block's transactions.
+pub fn validate_coinbase(tx: &Transaction, params: &Params, blue_score: u64, fees: u64) -> ConsensusResult<()> {
+    validate_coinbase_structure(tx)?;
+    let max_allowed = block_subsidy(blue_score, params).saturating_add(fees);
+    let paid = tx.outputs[0].value;
+    if paid > max_allowed {
+        return Err(crate::errors::ConsensusError::CoinbaseOverpay { paid, max_allowed });
+    }
+    Ok(())
+}
+
+/// Checks the shape of a coinbase transaction without its payout, for rules that don't know the
+/// block it belongs to.
+pub(crate) fn validate_coinbase_structure(tx: &Transaction) -> ConsensusResult<()> {
     if !tx.is_coinbase() {
         return Err(crate::errors::ConsensusError::TransactionValidation {
             msg: "Not a coinbase transaction".to_string(),
         });
     }
-    if tx.outputs.len() != 1 {
+    if tx.outputs.is_empty() {
+        return Err(crate::errors::ConsensusError::TransactionValidation {
+            msg: "Coinbase must have a reward output".to_string(),
+        });
+    }
+    if tx.outputs[1..].iter().any(|output| output.value != 0 || output.script_pubkey.first() != Some(&OP_RETURN)) {
         return Err(crate::errors::ConsensusError::TransactionValidation {
-            msg: "Coinbase must have exactly one output".to_string(),
+            msg: "Coinbase outputs after the reward must be zero-value OP_RETURN data".to_string(),
         });
     }
-    // Additional validations (e.g., reward amount) can be added
     Ok(())
 }
 
@@ -53,7 +113,7 @@ mod tests {
     #[test]
     fn test_validate_coinbase_valid() {
         let tx = create_coinbase_transaction(50, vec![0x01]);
-        assert!(validate_coinbase(&tx).is_ok());
+        assert!(validate_coinbase(&tx, &Params::default(), 0, 0).is_ok());
     }
 
     #[test]
@@ -66,6 +126,84 @@ mod tests {
         };
         let output = TxOutput { value: 50, script_pubkey: vec![] };
         let tx = Transaction::new(1, vec![input], vec![output], 0);
-        assert!(validate_coinbase(&tx).is_err());
+        assert!(validate_coinbase(&tx, &Params::default(), 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_coinbase_outputs() {
+        let pay_to = ScriptPublicKey::pay_to_pubkey_hash(&Hash::from_le_u64([1, 2, 3, 4]));
+        let outputs = coinbase_outputs(50, &MinerData::default(), &pay_to);
+        assert_eq!(outputs, vec![TxOutput { value: 50, script_pubkey: pay_to.script.clone() }]);
+
+        let miner_data = MinerData { extra_data: b"jio-miner/0.1".to_vec() };
+        assert_eq!(coinbase_output(50, &miner_data, &pay_to), TxOutput { value: 50, script_pubkey: pay_to.script.clone() });
+        let outputs = coinbase_outputs(50, &miner_data, &pay_to);
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(outputs[0], coinbase_output(50, &miner_data, &pay_to));
+        assert_eq!(outputs[1].value, 0);
+        assert_eq!(
+            crate::tx::opcodes::parse_ops(&outputs[1].script_pubkey).unwrap(),
+            vec![Op::Return, Op::PushBytes(miner_data.extra_data.clone())]
+        );
+
+        let params = Params::default();
+        let mut tx = create_coinbase_transaction(50, vec![]);
+        tx.outputs = outputs;
+        assert!(validate_coinbase(&tx, &params, 0, 0).is_ok());
+        tx.outputs[1].value = 1;
+        assert!(validate_coinbase(&tx, &params, 0, 0).is_err());
+        tx.outputs[1] = TxOutput { value: 0, script_pubkey: pay_to.script };
+        assert!(validate_coinbase(&tx, &params, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_subsidy_schedule() {
+        let params = Params::default();
+        let schedule = subsidy_schedule(&params, 5);
+        assert_eq!(schedule.len(), 5);
+        for (era, &(start, subsidy)) in schedule.iter().enumerate() {
+            assert_eq!(start, era as u64 * params.halving_interval);
+            assert_eq!(subsidy, INITIAL_BLOCK_SUBSIDY >> era);
+        }
+        for pair in schedule.windows(2) {
+            assert_eq!(pair[1].1, pair[0].1 / 2);
+        }
+
+        // The subsidy is constant within an era and eventually runs out
+        assert_eq!(block_subsidy(params.halving_interval - 1, &params), INITIAL_BLOCK_SUBSIDY);
+        assert_eq!(subsidy_schedule(&params, 70)[69].1, 0);
+        assert!(subsidy_schedule(&params, 0).is_empty());
+    }
+
+    #[test]
+    fn test_validate_coinbase_reward() {
+        let params = Params::default();
+        let validate = |reward, blue_score, fees| validate_coinbase(&create_coinbase_transaction(reward, vec![]), &params, blue_score, fees);
+
+        assert_eq!(validate(INITIAL_BLOCK_SUBSIDY + 7, 0, 7), Ok(()));
+        assert_eq!(
+            validate(INITIAL_BLOCK_SUBSIDY + 8, 0, 7),
+            Err(crate::errors::ConsensusError::CoinbaseOverpay { paid: INITIAL_BLOCK_SUBSIDY + 8, max_allowed: INITIAL_BLOCK_SUBSIDY + 7 })
+        );
+        assert_eq!(validate(INITIAL_BLOCK_SUBSIDY - 1, 0, 0), Ok(()));
+
+        // First halving boundary
+        let first = params.halving_interval;
+        assert_eq!(validate(INITIAL_BLOCK_SUBSIDY, first - 1, 0), Ok(()));
+        assert!(validate(INITIAL_BLOCK_SUBSIDY, first, 0).is_err());
+        assert_eq!(validate(INITIAL_BLOCK_SUBSIDY / 2, first, 0), Ok(()));
+
+        // Second halving boundary
+        let second = 2 * params.halving_interval;
+        assert_eq!(validate(INITIAL_BLOCK_SUBSIDY / 2, second - 1, 0), Ok(()));
+        assert!(validate(INITIAL_BLOCK_SUBSIDY / 2, second, 0).is_err());
+        assert_eq!(validate(INITIAL_BLOCK_SUBSIDY / 4, second, 0), Ok(()));
+
+        // Once the subsidy has run out only fees may be claimed
+        let exhausted = 64 * params.halving_interval;
+        assert_eq!(block_subsidy(exhausted, &params), 0);
+        assert_eq!(validate(5, exhausted, 5), Ok(()));
+        assert!(validate(1, exhausted, 0).is_err());
+        assert_eq!(validate(u64::MAX, u64::MAX, u64::MAX), Ok(()));
     }
 }
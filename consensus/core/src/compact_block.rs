@@ -0,0 +1,185 @@
+//! Compact block representation for relay.
+
+use crate::{block::Block, header::Header, tx::Transaction, Hash};
+use siphasher::sip::SipHasher24;
+
+/// Errors which can occur while reconstructing a block from its compact form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReconstructError {
+    /// A transaction referenced by short id was not found in the mempool.
+    MissingTransaction { index: u32, short_id: u64 },
+    /// A prefilled transaction index lies outside the block.
+    InvalidPrefilledIndex(u32),
+    /// The same index was prefilled more than once.
+    DuplicatePrefilledIndex(u32),
+}
+
+impl std::fmt::Display for ReconstructError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReconstructError::MissingTransaction { index, short_id } => {
+                write!(f, "Missing transaction at index {} (short id {:#018x})", index, short_id)
+            }
+            ReconstructError::InvalidPrefilledIndex(index) => write!(f, "Prefilled index {} is out of range", index),
+            ReconstructError::DuplicatePrefilledIndex(index) => write!(f, "Prefilled index {} appears more than once", index),
+        }
+    }
+}
+
+impl std::error::Error for ReconstructError {}
+
+/// Returns the SipHash key for the short ids of the block with `header`: the first two words of
+/// its hash. Keying per block means a transaction colliding with another in one block doesn't
+/// collide in the next, so collisions can't be precomputed against every block.
+pub fn short_id_key(header: &Header) -> (u64, u64) {
+    let [k0, k1, ..] = header.hash().as_le_u64();
+    (k0, k1)
+}
+
+/// Computes the short id used to reference a transaction in a compact block: SipHash-2-4 of the
+/// transaction id under the block's [`short_id_key`].
+pub fn short_id(key: (u64, u64), tx_hash: &Hash) -> u64 {
+    SipHasher24::new_with_keys(key.0, key.1).hash(tx_hash.as_bytes())
+}
+
+/// Compact block: the header, short ids for transactions the receiver likely
+/// already has, and the transactions it likely does not (e.g. the coinbase).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactBlock {
+    pub header: Header,
+    pub short_ids: Vec<u64>,
+    pub prefilled: Vec<(u32, Transaction)>,
+}
+
+impl CompactBlock {
+    /// Builds a compact block, sending the transactions at `prefilled` indices in full and
+    /// referencing every other transaction of the block by short id. Indices outside the block
+    /// and repeated indices are ignored.
+    pub fn from_block(block: &Block, prefilled: &[u32]) -> Self {
+        let mut indices: Vec<u32> = prefilled.iter().copied().filter(|index| (*index as usize) < block.transactions.len()).collect();
+        indices.sort_unstable();
+        indices.dedup();
+
+        let key = short_id_key(&block.header);
+        let short_ids = block
+            .transactions
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| indices.binary_search(&(*index as u32)).is_err())
+            .map(|(_, tx)| short_id(key, &tx.id()))
+            .collect();
+        let prefilled = indices.into_iter().map(|index| (index, block.transactions[index as usize].clone())).collect();
+        Self { header: block.header.clone(), short_ids, prefilled }
+    }
+
+    /// Computes the short id of `tx_hash` under this block's key, e.g. to index the mempool
+    /// before calling [`Self::reconstruct`].
+    pub fn short_id(&self, tx_hash: &Hash) -> u64 {
+        short_id(short_id_key(&self.header), tx_hash)
+    }
+
+    /// Returns the number of transactions in the full block.
+    pub fn tx_count(&self) -> usize {
+        self.short_ids.len() + self.prefilled.len()
+    }
+
+    /// Reconstructs the full block, resolving short ids through `mempool`.
+    pub fn reconstruct(&self, mempool: &dyn Fn(u64) -> Option<Transaction>) -> Result<Block, ReconstructError> {
+        let tx_count = self.tx_count();
+        let mut slots: Vec<Option<Transaction>> = vec![None; tx_count];
+        for (index, tx) in &self.prefilled {
+            let slot = slots.get_mut(*index as usize).ok_or(ReconstructError::InvalidPrefilledIndex(*index))?;
+            if slot.is_some() {
+                return Err(ReconstructError::DuplicatePrefilledIndex(*index));
+            }
+            *slot = Some(tx.clone());
+        }
+
+        let mut short_ids = self.short_ids.iter();
+        for (index, slot) in slots.iter_mut().enumerate().filter(|(_, slot)| slot.is_none()) {
+            // The counts line up by construction, so every empty slot has a short id
+            let short_id = *short_ids.next().expect("short id count matches empty slots");
+            let tx = mempool(short_id).ok_or(ReconstructError::MissingTransaction { index: index as u32, short_id })?;
+            *slot = Some(tx);
+        }
+
+        let transactions = slots.into_iter().map(|slot| slot.expect("all slots are filled")).collect();
+        Ok(Block::new(self.header.clone(), transactions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coinbase::create_coinbase_transaction;
+    use crate::tx::{TxInput, TxOutput};
+    use std::collections::HashMap;
+
+    fn create_test_tx(seed: u8) -> Transaction {
+        let input = TxInput { prev_tx_hash: Hash::from_slice(&[seed; 32]), index: 0, script_sig: vec![], sequence: 0 };
+        Transaction::new(1, vec![input], vec![TxOutput { value: seed as u64, script_pubkey: vec![] }], 0)
+    }
+
+    fn create_test_block(txs: &[Transaction]) -> Block {
+        Block::new_with_txs(Header::new(), txs.to_vec())
+    }
+
+    #[test]
+    fn test_reconstruct_from_mempool() {
+        let coinbase = create_coinbase_transaction(50, vec![0x01]);
+        let txs = vec![coinbase.clone(), create_test_tx(1), create_test_tx(2)];
+        let block = create_test_block(&txs);
+
+        let compact = CompactBlock::from_block(&block, &[0]);
+        assert_eq!(compact.short_ids.len(), 2);
+        assert_eq!(compact.prefilled, vec![(0, coinbase)]);
+        assert_eq!(compact.tx_count(), 3);
+
+        let mempool: HashMap<u64, Transaction> = txs[1..].iter().map(|tx| (compact.short_id(&tx.id()), tx.clone())).collect();
+        let reconstructed = compact.reconstruct(&|id| mempool.get(&id).cloned()).unwrap();
+        assert_eq!(reconstructed, block);
+    }
+
+    #[test]
+    fn test_reconstruct_missing_transaction() {
+        let coinbase = create_coinbase_transaction(50, vec![0x01]);
+        let txs = vec![coinbase.clone(), create_test_tx(1), create_test_tx(2)];
+        let block = create_test_block(&txs);
+
+        let compact = CompactBlock::from_block(&block, &[0]);
+        let mempool: HashMap<u64, Transaction> = [(compact.short_id(&txs[1].id()), txs[1].clone())].into_iter().collect();
+        let result = compact.reconstruct(&|id| mempool.get(&id).cloned());
+        assert_eq!(result, Err(ReconstructError::MissingTransaction { index: 2, short_id: compact.short_id(&txs[2].id()) }));
+    }
+
+    #[test]
+    fn test_short_ids_keyed_per_block() {
+        let tx = create_test_tx(1);
+        let block = create_test_block(&[create_coinbase_transaction(50, vec![0x01]), tx.clone()]);
+        let mut other_header = block.header.clone();
+        other_header.nonce += 1;
+        let other = Block::new(other_header, block.transactions.clone());
+
+        let (ours, theirs) = (CompactBlock::from_block(&block, &[0]), CompactBlock::from_block(&other, &[0]));
+        assert_eq!(ours.short_ids, vec![short_id(short_id_key(&block.header), &tx.id())]);
+        assert_ne!(ours.short_ids, theirs.short_ids);
+        assert_ne!(ours.short_ids[0], tx.id().as_le_u64()[0]);
+    }
+
+    #[test]
+    fn test_from_block_prefilled_indices() {
+        let txs = vec![create_coinbase_transaction(50, vec![0x01]), create_test_tx(1), create_test_tx(2)];
+        let block = create_test_block(&txs);
+        let compact = CompactBlock::from_block(&block, &[2, 0, 2, 7]);
+        assert_eq!(compact.prefilled, vec![(0, txs[0].clone()), (2, txs[2].clone())]);
+        assert_eq!(compact.short_ids, vec![compact.short_id(&txs[1].id())]);
+        assert_eq!(compact.reconstruct(&|_| Some(txs[1].clone())).unwrap(), block);
+    }
+
+    #[test]
+    fn test_reconstruct_invalid_prefilled_index() {
+        let coinbase = create_coinbase_transaction(50, vec![0x01]);
+        let compact = CompactBlock { header: Header::new(), short_ids: vec![], prefilled: vec![(3, coinbase)] };
+        assert_eq!(compact.reconstruct(&|_| None), Err(ReconstructError::InvalidPrefilledIndex(3)));
+    }
+}
@@ -1,4 +1,5 @@
-use crate::Hash;
+use crate::{config::params::Params, header::Header, BlueWorkType, Hash};
+use jio_math::Uint256;
 
 #[cfg(feature = "devnet-prealloc")]
 use crate::utxo::utxo_collection::UtxoCollection;
@@ -10,10 +11,10 @@ use std::sync::Arc;
 pub struct GenesisParams {
     /// Hash of the genesis block
     pub genesis_hash: Hash,
-    /// Timestamp of the genesis block
+    /// Timestamp of the genesis block in milliseconds; must match `Params::genesis_timestamp`
     pub genesis_timestamp: u64,
-    /// Initial difficulty
-    pub initial_difficulty: u64,
+    /// Compact target bits of the genesis block; must match `Params::genesis_bits`
+    pub genesis_bits: u32,
     /// Pre-allocated UTXO set for devnet
     #[cfg(feature = "devnet-prealloc")]
     pub initial_utxo_set: Arc<UtxoCollection>,
@@ -26,8 +27,8 @@ impl GenesisParams {
     pub fn mainnet() -> Self {
         Self {
             genesis_hash: Hash::from_le_u64([0; 4]), // Placeholder
-            genesis_timestamp: 1_600_000_000,
-            initial_difficulty: 1,
+            genesis_timestamp: 1_600_000_000_000,
+            genesis_bits: Params::default().genesis_bits,
             #[cfg(feature = "devnet-prealloc")]
             initial_utxo_set: Arc::new(UtxoCollection::new()),
             process_genesis: true,
@@ -38,13 +39,56 @@ impl GenesisParams {
     pub fn testnet() -> Self {
         Self {
             genesis_hash: Hash::from_le_u64([1; 4]), // Placeholder
-            genesis_timestamp: 1_600_000_000,
-            initial_difficulty: 1,
+            genesis_timestamp: 1_600_000_000_000,
+            genesis_bits: Params::default().genesis_bits,
             #[cfg(feature = "devnet-prealloc")]
             initial_utxo_set: Arc::new(UtxoCollection::new()),
             process_genesis: true,
         }
     }
+
+    /// Derives the genesis difficulty and timestamp from the consensus parameters.
+    pub fn from_params(params: &Params) -> Self {
+        Self { genesis_timestamp: params.genesis_timestamp, genesis_bits: params.genesis_bits, ..Self::mainnet() }
+    }
+
+    /// Work implied by the genesis bits.
+    pub fn initial_difficulty(&self) -> BlueWorkType {
+        BlueWorkType::work_from_bits(self.genesis_bits)
+    }
+
+    /// Verify the genesis difficulty and timestamp are consistent with the consensus parameters
+    pub fn verify(&self, params: &Params) -> Result<(), &'static str> {
+        if self.genesis_bits != params.genesis_bits {
+            return Err("genesis_bits must match Params::genesis_bits");
+        }
+        if self.genesis_timestamp != params.genesis_timestamp {
+            return Err("genesis_timestamp must match Params::genesis_timestamp");
+        }
+        if Uint256::from_compact_target_bits(self.genesis_bits) == Uint256::default() {
+            return Err("genesis_bits must encode a non-zero target");
+        }
+        if self.initial_difficulty() < params.min_difficulty {
+            return Err("genesis difficulty is below Params::min_difficulty");
+        }
+        Ok(())
+    }
+
+    /// Verify a constructed genesis header: it must have no parents and carry the bits and
+    /// timestamp of these params, which must in turn be consistent with the consensus parameters.
+    pub fn verify_header(&self, header: &Header, params: &Params) -> Result<(), &'static str> {
+        self.verify(params)?;
+        if header.parents_by_level.iter().any(|level| !level.is_empty()) {
+            return Err("genesis header must not have parents");
+        }
+        if header.bits != self.genesis_bits {
+            return Err("genesis header bits must match genesis_bits");
+        }
+        if header.timestamp != self.genesis_timestamp {
+            return Err("genesis header timestamp must match genesis_timestamp");
+        }
+        Ok(())
+    }
 }
 
 impl Default for GenesisParams {
@@ -60,13 +104,50 @@ mod tests {
     #[test]
     fn test_genesis_params_default() {
         let params = GenesisParams::default();
-        assert_eq!(params.genesis_timestamp, 1_600_000_000);
+        assert_eq!(params.genesis_timestamp, 1_600_000_000_000);
         assert!(params.process_genesis);
     }
 
     #[test]
     fn test_genesis_params_mainnet() {
         let params = GenesisParams::mainnet();
-        assert_eq!(params.initial_difficulty, 1);
+        assert_eq!(params.genesis_bits, Params::default().genesis_bits);
+        assert!(params.verify(&Params::default()).is_ok());
+    }
+
+    #[test]
+    fn test_genesis_params_verify() {
+        let params = Params { genesis_bits: 0x1f7fffff, ..Default::default() };
+        assert!(GenesisParams::from_params(&params).verify(&params).is_ok());
+
+        // Mismatched bits
+        assert!(GenesisParams::mainnet().verify(&params).is_err());
+
+        // Bits whose work falls short of the minimum difficulty
+        let strict = Params { min_difficulty: BlueWorkType::from_u64(1 << 40), ..params.clone() };
+        assert!(GenesisParams::from_params(&strict).verify(&strict).is_err());
+
+        // Bits decoding to a zero target
+        let zero = Params { genesis_bits: 0, ..params };
+        assert!(GenesisParams::from_params(&zero).verify(&zero).is_err());
+    }
+
+    #[test]
+    fn test_genesis_timestamp_verify() {
+        let params = Params::default();
+        let genesis = GenesisParams::from_params(&params);
+        let shifted = GenesisParams { genesis_timestamp: params.genesis_timestamp + 1, ..genesis.clone() };
+        assert_eq!(shifted.verify(&params), Err("genesis_timestamp must match Params::genesis_timestamp"));
+
+        let mut header = Header::new();
+        header.bits = params.genesis_bits;
+        header.timestamp = params.genesis_timestamp;
+        assert_eq!(genesis.verify_header(&header, &params), Ok(()));
+
+        header.timestamp += 1;
+        assert_eq!(genesis.verify_header(&header, &params), Err("genesis header timestamp must match genesis_timestamp"));
+        header.timestamp -= 1;
+        header.parents_by_level = vec![vec![Hash::from_le_u64([1, 0, 0, 0])]];
+        assert!(genesis.verify_header(&header, &params).is_err());
     }
 }
@@ -1,4 +1,9 @@
-use crate::{network::NetworkId, BlueWorkType};
+use crate::{
+    constants::{TARGET_BLOCK_TIME, TIMESTAMP_UNIT},
+    network::NetworkId,
+    BlueWorkType,
+};
+use jio_math::Uint256;
 
 /// Consensus parameters defining the network rules and constants.
 #[derive(Clone, Debug, PartialEq)]
@@ -11,14 +16,22 @@ pub struct Params {
     pub max_block_mass: u64,
     /// Maximum transaction mass
     pub max_tx_mass: u64,
+    /// Maximum transaction payload size in bytes
+    pub max_tx_payload_size: usize,
     /// Halving interval for block rewards
     pub halving_interval: u64,
     /// Maximum number of blocks in a chain
     pub max_block_parents: u8,
-    /// Timestamp deviation tolerance
+    /// How far, in milliseconds, a header timestamp may run ahead of the local clock
     pub timestamp_deviation_tolerance: u64,
-    /// Genesis timestamp
+    /// Maximum allowed drift of a header timestamp into the future, in milliseconds
+    pub max_future_time_offset: u64,
+    /// Genesis timestamp in milliseconds
     pub genesis_timestamp: u64,
+    /// Compact target bits of the genesis block
+    pub genesis_bits: u32,
+    /// The easiest target allowed on the network; retargeting never goes past it
+    pub pow_limit: Uint256,
     /// Maximum number of transactions per block
     pub max_txs_per_block: usize,
     /// Difficulty adjustment window
@@ -30,6 +43,25 @@ pub struct Params {
 }
 
 impl Params {
+    /// Default parameters for `network_id`, with its preset pow limit.
+    pub fn for_network(network_id: NetworkId) -> Self {
+        Self { network_id, pow_limit: Self::pow_limit_of(network_id), ..Default::default() }
+    }
+
+    /// The pow limit preset of each network: production networks start at the genesis target,
+    /// development networks allow near-trivial targets so blocks can be mined on a CPU.
+    pub fn pow_limit_of(network_id: NetworkId) -> Uint256 {
+        match network_id {
+            NetworkId::Mainnet | NetworkId::Testnet => Uint256::from_compact_target_bits(0x1d00ffff),
+            NetworkId::Devnet | NetworkId::Simnet => Uint256::from_compact_target_bits(0x207fffff),
+        }
+    }
+
+    /// Compact bits of the pow limit, i.e. of the lowest difficulty the network allows.
+    pub fn max_difficulty_bits(&self) -> u32 {
+        self.pow_limit.to_compact_target_bits()
+    }
+
     /// Validate the parameters for consistency
     pub fn validate(&self) -> Result<(), &'static str> {
         if self.target_time_per_block == 0 {
@@ -38,6 +70,12 @@ impl Params {
         if self.max_block_mass == 0 {
             return Err("max_block_mass must be positive");
         }
+        if self.pow_limit == Uint256::default() {
+            return Err("pow_limit must be non-zero");
+        }
+        if Uint256::from_compact_target_bits(self.genesis_bits) > self.pow_limit {
+            return Err("genesis_bits must not be easier than pow_limit");
+        }
         Ok(())
     }
 }
@@ -47,13 +85,17 @@ impl Default for Params {
         // Mainnet defaults
         Self {
             network_id: NetworkId::Mainnet,
-            target_time_per_block: 1000, // 1 second
+            target_time_per_block: TARGET_BLOCK_TIME,
             max_block_mass: 500_000, // 500KB
             max_tx_mass: 100_000, // 100KB
+            max_tx_payload_size: crate::constants::MAX_TX_PAYLOAD_SIZE,
             halving_interval: 2_100_000,
             max_block_parents: 10,
-            timestamp_deviation_tolerance: 132,
-            genesis_timestamp: 1_600_000_000, // Example timestamp
+            timestamp_deviation_tolerance: TIMESTAMP_UNIT.from_secs(132),
+            max_future_time_offset: TIMESTAMP_UNIT.from_secs(2 * 60 * 60),
+            genesis_timestamp: 1_600_000_000_000, // Example timestamp
+            genesis_bits: 0x1d00ffff,
+            pow_limit: Self::pow_limit_of(NetworkId::Mainnet),
             max_txs_per_block: 1000,
             difficulty_adjustment_window: 2646,
             min_difficulty: BlueWorkType::from_u64(1),
@@ -73,10 +115,24 @@ mod tests {
         assert!(params.validate().is_ok());
     }
 
+    #[test]
+    fn test_pow_limit_presets() {
+        for network in NetworkId::ALL {
+            let params = Params::for_network(network);
+            assert_eq!(params.network_id, network);
+            assert!(params.validate().is_ok());
+            assert_eq!(Uint256::from_compact_target_bits(params.max_difficulty_bits()), params.pow_limit);
+        }
+        assert_eq!(Params::default().max_difficulty_bits(), Params::default().genesis_bits);
+        assert!(Params::for_network(NetworkId::Devnet).pow_limit > Params::for_network(NetworkId::Mainnet).pow_limit);
+
+        let easy_genesis = Params { genesis_bits: 0x1f7fffff, ..Default::default() };
+        assert_eq!(easy_genesis.validate(), Err("genesis_bits must not be easier than pow_limit"));
+    }
+
     #[test]
     fn test_params_validation() {
-        let mut params = Params::default();
-        params.target_time_per_block = 0;
+        let params = Params { target_time_per_block: 0, ..Default::default() };
         assert!(params.validate().is_err());
     }
 }
@@ -5,8 +5,33 @@ use crate::BlueWorkType;
 /// Maximum mass allowed for a block in grams.
 pub const MAX_BLOCK_MASS: u64 = 500_000;
 
-/// Target time between blocks in seconds.
-pub const TARGET_BLOCK_TIME: u64 = 1;
+/// Unit of header timestamps and of every duration consensus derives from them (block times,
+/// tolerances, DAA windows), so that all of them compare without conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampUnit {
+    /// Milliseconds, with timestamps counted from the Unix epoch
+    Milliseconds,
+}
+
+impl TimestampUnit {
+    /// Number of units in a second.
+    pub const fn per_second(self) -> u64 {
+        match self {
+            TimestampUnit::Milliseconds => 1000,
+        }
+    }
+
+    /// Converts a duration of `secs` seconds into this unit.
+    pub const fn from_secs(self, secs: u64) -> u64 {
+        secs * self.per_second()
+    }
+}
+
+/// The unit of every timestamp and duration in consensus.
+pub const TIMESTAMP_UNIT: TimestampUnit = TimestampUnit::Milliseconds;
+
+/// Target time between blocks, one second in `TIMESTAMP_UNIT`.
+pub const TARGET_BLOCK_TIME: u64 = TIMESTAMP_UNIT.from_secs(1);
 
 /// Number of blocks between difficulty adjustments.
 pub const DIFFICULTY_ADJUSTMENT_WINDOW: u32 = 1024;
@@ -17,6 +42,9 @@ pub const INITIAL_TARGET: BlueWorkType = BlueWorkType::from_u64(0x1d00ffff);
 /// Halving interval in blocks.
 pub const HALVING_INTERVAL: u64 = 210_000;
 
+/// Block subsidy of the first halving era, in sompi.
+pub const INITIAL_BLOCK_SUBSIDY: u64 = 50 * 100_000_000;
+
 /// Maximum number of transactions per block.
 pub const MAX_TRANSACTIONS_PER_BLOCK: usize = 10_000;
 
@@ -29,12 +57,21 @@ pub const COINBASE_MATURITY: u64 = 100;
 /// Maximum script size in bytes.
 pub const MAX_SCRIPT_SIZE: usize = 10_000;
 
+/// Maximum transaction payload size in bytes.
+pub const MAX_TX_PAYLOAD_SIZE: usize = 32 * 1024;
+
 /// Maximum stack size for script execution.
 pub const MAX_STACK_SIZE: usize = 1000;
 
 /// Maximum number of signature operations per block.
 pub const MAX_SIGOPS_PER_BLOCK: u32 = 20_000;
 
+/// Lowest block header version accepted by consensus.
+pub const MIN_BLOCK_VERSION: u16 = 1;
+
+/// Highest block header version accepted by consensus.
+pub const MAX_BLOCK_VERSION: u16 = 1;
+
 /// Network magic bytes.
 pub const NETWORK_MAGIC: [u8; 4] = [0xAB, 0xCD, 0xEF, 0x12];
 
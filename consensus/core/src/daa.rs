@@ -0,0 +1,134 @@
+//! Difficulty adjustment algorithm (DAA).
+
+use crate::daa_score_timestamp::DaaScoreTimestamp;
+use jio_math::Uint256;
+
+/// Maximum factor by which the target may move in a single retarget.
+pub const MAX_RETARGET_FACTOR: u64 = 4;
+
+/// Window of recent blocks used for retargeting.
+#[derive(Debug, Clone, Default)]
+pub struct DaaWindow {
+    /// Blocks in the window, ordered from oldest to newest.
+    pub blocks: Vec<DaaScoreTimestamp>,
+    /// Compact target bits of the newest block in the window.
+    pub current_bits: u32,
+}
+
+impl DaaWindow {
+    /// Creates a new DAA window.
+    pub fn new(blocks: Vec<DaaScoreTimestamp>, current_bits: u32) -> Self {
+        Self { blocks, current_bits }
+    }
+
+    /// Checks if the window holds enough blocks to measure an interval.
+    pub fn is_sufficient(&self) -> bool {
+        self.blocks.len() >= 2
+    }
+}
+
+/// Calculates the compact bits for the next block by scaling the current target
+/// with the ratio of the observed average block interval to the expected one, never
+/// producing a target easier than `pow_limit`.
+pub fn calculate_next_bits(window: &[DaaScoreTimestamp], target_time_per_block: u64, current_bits: u32, pow_limit: &Uint256) -> u32 {
+    if window.len() < 2 || target_time_per_block == 0 {
+        return current_bits;
+    }
+
+    let first = window.first().unwrap().timestamp;
+    let last = window.last().unwrap().timestamp;
+    let intervals = (window.len() - 1) as u64;
+    let expected_timespan = target_time_per_block.saturating_mul(intervals);
+    let actual_timespan = last
+        .saturating_sub(first)
+        .clamp(expected_timespan / MAX_RETARGET_FACTOR, expected_timespan.saturating_mul(MAX_RETARGET_FACTOR));
+
+    let next_bits = scale_bits(current_bits, actual_timespan, expected_timespan);
+    if Uint256::from_compact_target_bits(next_bits) > *pow_limit {
+        pow_limit.to_compact_target_bits()
+    } else {
+        next_bits
+    }
+}
+
+/// Scales the target encoded by `bits` by `numerator / denominator` and returns it re-encoded.
+fn scale_bits(bits: u32, numerator: u64, denominator: u64) -> u32 {
+    let mut exponent = bits >> 24;
+    let mut mantissa = (bits & 0x007f_ffff) as u128 * numerator as u128 / denominator.max(1) as u128;
+
+    // Keep the mantissa clear of the sign bit
+    while mantissa > 0x007f_ffff {
+        mantissa >>= 8;
+        exponent += 1;
+    }
+    // Retain precision when the target shrinks
+    while mantissa != 0 && mantissa < 0x8000 && exponent > 3 {
+        mantissa <<= 8;
+        exponent -= 1;
+    }
+    // A zero target can never be met
+    let mantissa = mantissa.max(1) as u32;
+
+    (exponent.min(0xff) << 24) | mantissa
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_window(count: u64, interval: u64) -> Vec<DaaScoreTimestamp> {
+        (0..count).map(|i| DaaScoreTimestamp::new(i, 1_000_000 + i * interval)).collect()
+    }
+
+    #[test]
+    fn test_calculate_next_bits_on_target() {
+        let window = create_window(10, 1000);
+        assert_eq!(calculate_next_bits(&window, 1000, 0x1c7fff00, &Uint256::MAX), 0x1c7fff00);
+    }
+
+    #[test]
+    fn test_calculate_next_bits_clamps_factor() {
+        let slow = create_window(10, 100_000);
+        // Target may grow at most 4x: 0x1c100000 * 4 = 0x1c400000
+        assert_eq!(calculate_next_bits(&slow, 1000, 0x1c100000, &Uint256::MAX), 0x1c400000);
+    }
+
+    #[test]
+    fn test_calculate_next_bits_fast_blocks_raise_difficulty() {
+        let current_bits = 0x1c7fff00;
+        // Blocks twice as fast as targeted halve the target
+        let next_bits = calculate_next_bits(&create_window(10, 500), 1000, current_bits, &Uint256::MAX);
+        let (current, next) = (Uint256::from_compact_target_bits(current_bits), Uint256::from_compact_target_bits(next_bits));
+        assert!(next < current);
+        assert_eq!(next_bits, 0x1c3fff80);
+    }
+
+    #[test]
+    fn test_calculate_next_bits_slow_blocks_lower_difficulty() {
+        let current_bits = 0x1c100000;
+        // Blocks twice as slow as targeted double the target
+        let next_bits = calculate_next_bits(&create_window(10, 2000), 1000, current_bits, &Uint256::MAX);
+        let (current, next) = (Uint256::from_compact_target_bits(current_bits), Uint256::from_compact_target_bits(next_bits));
+        assert!(next > current);
+        assert_eq!(next_bits, 0x1c200000);
+    }
+
+    #[test]
+    fn test_calculate_next_bits_respects_pow_limit() {
+        let pow_limit = Uint256::from_compact_target_bits(0x1d00ffff);
+        let slow = create_window(10, 100_000);
+        for current_bits in [0x1c100000, 0x1c7fff00, 0x1d00ffff] {
+            let next_bits = calculate_next_bits(&slow, 1000, current_bits, &pow_limit);
+            assert!(Uint256::from_compact_target_bits(next_bits) <= pow_limit);
+        }
+        assert_eq!(calculate_next_bits(&slow, 1000, 0x1d00ffff, &pow_limit), 0x1d00ffff);
+        // Within the limit the retarget is unaffected
+        assert_eq!(calculate_next_bits(&slow, 1000, 0x1c100000, &pow_limit), 0x1c400000);
+    }
+
+    #[test]
+    fn test_calculate_next_bits_insufficient_window() {
+        let window = create_window(1, 1000);
+        assert_eq!(calculate_next_bits(&window, 1000, 0x1d00ffff, &Uint256::MAX), 0x1d00ffff);
+    }
+}
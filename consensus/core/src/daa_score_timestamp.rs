@@ -6,6 +6,7 @@ use crate::errors::ConsensusResult;
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DaaScoreTimestamp {
     pub daa_score: u64,
+    /// Block timestamp in milliseconds.
     pub timestamp: u64,
 }
 
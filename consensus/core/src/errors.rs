@@ -1,6 +1,6 @@
 //! Error types for the consensus core.
 
-use crate::{Hash, KType};
+use crate::{tx::TransactionOutpoint, Hash, KType};
 use std::fmt;
 
 /// Block-related errors.
@@ -45,18 +45,38 @@ pub enum ConsensusError {
 
     InvalidBlockHeader { msg: String },
 
+    TimestampTooOld { timestamp: u64, median_time_past: u64 },
+
+    TimestampTooFarInFuture { timestamp: u64, max_allowed: u64 },
+
+    ZeroTarget { bits: u32 },
+
+    NegativeTarget { bits: u32 },
+
     TransactionValidation { msg: String },
 
     UtxoNotFound { output: Hash },
 
+    MissingInputUtxo { outpoint: TransactionOutpoint, tx_id: Hash },
+
+    DoubleSpend { outpoint: TransactionOutpoint, tx_id: Hash },
+
     InsufficientFunds,
 
+    CoinbaseOverpay { paid: u64, max_allowed: u64 },
+
     InvalidSignature,
 
     ScriptValidation { msg: String },
 
     MerkleRootMismatch,
 
+    TooManySigops { sigops: u64, max_allowed: u64 },
+
+    DuplicateTransaction { tx_id: Hash },
+
+    OrphanTransaction { tx_id: Hash },
+
     MiningRuleViolation { msg: String },
 
     DaaScoreCalculationFailed,
@@ -67,18 +87,28 @@ pub enum ConsensusError {
 
     NetworkProtocol { msg: String },
 
-    MissingGhostDagData,
+    Timeout { timeout_ms: u64 },
+
+    MissingGhostDagData { block: Hash },
 
     InvalidSelectedParent,
 
+    InvalidParent { parent: Hash },
+
     NoValidParent,
 
     NoTips,
 
     NoCommonAncestor,
 
+    CyclicBlockBatch,
+
     InvalidAnticone,
 
+    ReachabilityInconsistency { ancestor: Hash, descendant: Hash },
+
+    NonIncreasingChainBlueScore { block: Hash, blue_score: u64, selected_parent: Hash, parent_blue_score: u64 },
+
     Generic { msg: String },
 }
 
@@ -91,24 +121,54 @@ impl fmt::Display for ConsensusError {
             ConsensusError::InvalidBlockHeader { msg } => {
                 write!(f, "Invalid block header: {}", msg)
             }
+            ConsensusError::TimestampTooOld { timestamp, median_time_past } => {
+                write!(f, "Block timestamp {} is not after the median time past {}", timestamp, median_time_past)
+            }
+            ConsensusError::TimestampTooFarInFuture { timestamp, max_allowed } => {
+                write!(f, "Block timestamp {} is too far in the future, max allowed is {}", timestamp, max_allowed)
+            }
+            ConsensusError::ZeroTarget { bits } => {
+                write!(f, "Bits {:#010x} encode a zero target", bits)
+            }
+            ConsensusError::NegativeTarget { bits } => {
+                write!(f, "Bits {:#010x} have the sign bit set", bits)
+            }
             ConsensusError::TransactionValidation { msg } => {
                 write!(f, "Transaction validation failed: {}", msg)
             }
             ConsensusError::UtxoNotFound { output } => {
                 write!(f, "UTXO not found for output {}", output)
             }
+            ConsensusError::MissingInputUtxo { outpoint, tx_id } => {
+                write!(f, "Transaction {} spends missing UTXO {}:{}", tx_id, outpoint.transaction_id, outpoint.index)
+            }
+            ConsensusError::DoubleSpend { outpoint, tx_id } => {
+                write!(f, "Transaction {} double-spends {}:{} within the block", tx_id, outpoint.transaction_id, outpoint.index)
+            }
             ConsensusError::InsufficientFunds => {
                 write!(f, "Insufficient funds in transaction")
             }
+            ConsensusError::CoinbaseOverpay { paid, max_allowed } => {
+                write!(f, "Coinbase pays {}, more than the allowed subsidy plus fees of {}", paid, max_allowed)
+            }
             ConsensusError::InvalidSignature => {
                 write!(f, "Invalid signature")
             }
             ConsensusError::ScriptValidation { msg } => {
                 write!(f, "Script validation failed: {}", msg)
             }
+            ConsensusError::TooManySigops { sigops, max_allowed } => {
+                write!(f, "Block has {} signature operations, max allowed is {}", sigops, max_allowed)
+            }
             ConsensusError::MerkleRootMismatch => {
                 write!(f, "Merkle root mismatch")
             }
+            ConsensusError::DuplicateTransaction { tx_id } => {
+                write!(f, "Duplicate transaction {} in block", tx_id)
+            }
+            ConsensusError::OrphanTransaction { tx_id } => {
+                write!(f, "Transaction {} spends outputs of unknown transactions", tx_id)
+            }
             ConsensusError::MiningRuleViolation { msg } => {
                 write!(f, "Mining rule violation: {}", msg)
             }
@@ -124,12 +184,18 @@ impl fmt::Display for ConsensusError {
             ConsensusError::NetworkProtocol { msg } => {
                 write!(f, "Network protocol error: {}", msg)
             }
-            ConsensusError::MissingGhostDagData => {
-                write!(f, "Missing GhostDAG data for block")
+            ConsensusError::Timeout { timeout_ms } => {
+                write!(f, "Operation timed out after {} ms", timeout_ms)
+            }
+            ConsensusError::MissingGhostDagData { block } => {
+                write!(f, "Missing GhostDAG data for block {}", block)
             }
             ConsensusError::InvalidSelectedParent => {
                 write!(f, "Invalid selected parent in GhostDAG data")
             }
+            ConsensusError::InvalidParent { parent } => {
+                write!(f, "Block has invalid parent {}", parent)
+            }
             ConsensusError::NoValidParent => {
                 write!(f, "No valid parent found for block")
             }
@@ -139,9 +205,22 @@ impl fmt::Display for ConsensusError {
             ConsensusError::NoCommonAncestor => {
                 write!(f, "No common ancestor found for reorganization")
             }
+            ConsensusError::CyclicBlockBatch => {
+                write!(f, "Block batch contains a parent cycle")
+            }
             ConsensusError::InvalidAnticone => {
                 write!(f, "Invalid anticone calculation")
             }
+            ConsensusError::ReachabilityInconsistency { ancestor, descendant } => {
+                write!(f, "Ancestry query disagrees with the parent links on whether {} is in the past of {}", ancestor, descendant)
+            }
+            ConsensusError::NonIncreasingChainBlueScore { block, blue_score, selected_parent, parent_blue_score } => {
+                write!(
+                    f,
+                    "Selected chain block {} has blue score {}, not above the {} of its selected parent {}",
+                    block, blue_score, parent_blue_score, selected_parent
+                )
+            }
             ConsensusError::Generic { msg } => {
                 write!(f, "Generic consensus error: {}", msg)
             }
@@ -154,6 +233,12 @@ impl std::error::Error for ConsensusError {}
 /// Result type alias for consensus operations.
 pub type ConsensusResult<T> = Result<T, ConsensusError>;
 
+impl From<crate::script::ScriptError> for ConsensusError {
+    fn from(err: crate::script::ScriptError) -> Self {
+        ConsensusError::ScriptValidation { msg: err.to_string() }
+    }
+}
+
 impl From<crate::utxo::UtxoError> for ConsensusError {
     fn from(err: crate::utxo::UtxoError) -> Self {
         ConsensusError::Generic { msg: err.to_string() }
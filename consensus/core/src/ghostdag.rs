@@ -1,8 +1,9 @@
 //! GhostDAG consensus implementation using PHANTOM algorithm.
 
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 use std::sync::Arc;
-use dashmap::DashMap;
+use dashmap::{mapref::entry::Entry, DashMap, DashSet};
 use parking_lot::RwLock;
 use rayon::prelude::*;
 use crate::{Hash, KType, BlueWorkType, errors::ConsensusResult, Block};
@@ -31,6 +32,36 @@ impl Default for GhostDagData {
     }
 }
 
+impl GhostDagData {
+    /// Serializes the data with a fixed field order and anticone sizes sorted by hash,
+    /// so equal values serialize identically on every node.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&self.blue_score.to_le_bytes());
+        data.extend_from_slice(&self.blue_work.to_le_bytes());
+        data.extend_from_slice(self.selected_parent.as_bytes());
+        for set in [&self.merge_set_blues, &self.merge_set_reds] {
+            data.extend_from_slice(&(set.len() as u64).to_le_bytes());
+            for hash in set {
+                data.extend_from_slice(hash.as_bytes());
+            }
+        }
+        let mut anticone_sizes: Vec<_> = self.blues_anticone_sizes.iter().collect();
+        anticone_sizes.sort_unstable_by_key(|(hash, _)| **hash);
+        data.extend_from_slice(&(anticone_sizes.len() as u64).to_le_bytes());
+        for (hash, size) in anticone_sizes {
+            data.extend_from_slice(hash.as_bytes());
+            data.extend_from_slice(&size.to_le_bytes());
+        }
+        data
+    }
+
+    /// Computes a hash commitment over the canonical bytes.
+    pub fn commitment(&self) -> Hash {
+        crate::hashing::hash_data(&self.canonical_bytes())
+    }
+}
+
 /// Block relations in the DAG.
 #[derive(Debug, Clone)]
 pub struct BlockRelations {
@@ -38,16 +69,29 @@ pub struct BlockRelations {
     pub children: Arc<RwLock<Vec<Hash>>>,
     pub is_blue: bool,
     pub blue_score: u64,
+    pub blue_work: BlueWorkType,
+    /// Compact target bits from the block header, kept so the block's work can be recomputed
+    pub bits: u32,
+    /// Header timestamp in milliseconds, used to judge how recent the selected tip is
+    pub timestamp: u64,
+    /// The parent with the most blue work, `None` only for genesis
     pub selected_parent: Option<Hash>,
     pub merge_set_blues: Vec<Hash>,
     pub merge_set_reds: Vec<Hash>,
+    /// Blue anticone sizes, as seen from this block, of its mergeset blues and of the older blues
+    /// whose blue anticone grew when they were coloured
+    pub blues_anticone_sizes: HashMap<Hash, u64>,
 }
 
+/// DAG size up to which `verify_reachability_consistency` checks every pair of blocks.
+const REACHABILITY_FULL_CHECK_LIMIT: usize = 64;
+
 /// GhostDAG manager implementing PHANTOM algorithm.
 pub struct GhostDag {
     k: KType,
     pub block_relations: DashMap<Hash, BlockRelations>,
     blue_scores: DashMap<Hash, u64>,
+    invalid_blocks: DashSet<Hash>,
 }
 
 impl GhostDag {
@@ -57,6 +101,7 @@ impl GhostDag {
             k,
             block_relations: DashMap::new(),
             blue_scores: DashMap::new(),
+            invalid_blocks: DashSet::new(),
         }
     }
 
@@ -69,199 +114,343 @@ impl GhostDag {
             .cloned()
             .collect();
 
-        // Calculate blue and red sets using PHANTOM algorithm
-        let (blue_set, red_set) = self.calculate_blue_set(block, &all_parents).await?;
+        // Blocks building on unknown parents belong in the orphan pool; genesis has no parents
+        if all_parents.iter().any(|parent| !self.block_relations.contains_key(parent)) {
+            return Err(crate::errors::ConsensusError::NoValidParent);
+        }
+
+        // Refuse to build on parents whose GhostDAG data was never computed
+        self.ensure_parents_have_data(&all_parents)?;
 
-        // Select parent with highest blue score
-        let selected_parent = self.select_parent(&all_parents).await?;
+        // Descendants of invalid blocks are invalid as well
+        if let Some(parent) = all_parents.iter().find(|parent| self.is_invalid(parent)) {
+            self.invalid_blocks.insert(block.hash());
+            return Err(crate::errors::ConsensusError::InvalidParent { parent: *parent });
+        }
+
+        // Select the parent with the most blue work; genesis has none
+        let selected_parent = self.select_parent(&all_parents);
 
-        // Calculate blue work
-        let blue_work = self.calculate_blue_work_proper(&blue_set).await?;
+        // Colour the mergeset using PHANTOM algorithm
+        let (blue_set, red_set, blues_anticone_sizes) = self.calculate_blue_set(&all_parents, selected_parent);
 
-        // Calculate blue score
-        let blue_score = blue_set.len() as u64;
+        // Blue score and blue work extend those of the selected parent by the mergeset blues
+        let (parent_blue_score, parent_blue_work) = selected_parent
+            .and_then(|parent| self.block_relations.get(&parent).map(|relations| (relations.blue_score, relations.blue_work)))
+            .unwrap_or((0, BlueWorkType::from_u64(0)));
+        let blue_work = parent_blue_work.saturating_add(self.calculate_blue_work_proper(&blue_set).await?);
+        let blue_score = parent_blue_score + blue_set.len() as u64;
 
         // Store block relations
-        let relations = BlockRelations {
+        let mut relations = BlockRelations {
             parents: all_parents.clone(),
             children: Arc::new(RwLock::new(Vec::new())),
             is_blue: blue_set.contains(&block.hash()),
             blue_score,
-            selected_parent: Some(selected_parent),
+            blue_work,
+            bits: block.header.bits,
+            timestamp: block.header.timestamp,
+            selected_parent,
             merge_set_blues: blue_set.clone(),
             merge_set_reds: red_set.clone(),
+            blues_anticone_sizes: blues_anticone_sizes.clone(),
         };
 
-        self.block_relations.insert(block.hash(), relations);
+        // A re-added block keeps the children already linked to it
+        match self.block_relations.entry(block.hash()) {
+            Entry::Occupied(mut entry) => {
+                relations.children = entry.get().children.clone();
+                entry.insert(relations);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(relations);
+            }
+        }
         self.blue_scores.insert(block.hash(), blue_score);
 
-        // Update children for parent blocks
+        // Update children for parent blocks. The check and push happen under the parent's
+        // children lock, so concurrent or repeated adds never link a child twice.
         for parent in &all_parents {
-            if let Some(parent_relations) = self.block_relations.get_mut(parent) {
-                parent_relations.children.write().push(block.hash());
+            if let Some(parent_relations) = self.block_relations.get(parent) {
+                let mut children = parent_relations.children.write();
+                if !children.contains(&block.hash()) {
+                    children.push(block.hash());
+                }
             }
         }
 
-        // Calculate anticone sizes for blue blocks
-        let parents_set = HashSet::from_iter(all_parents.iter().cloned());
-        let blues_anticone_sizes = self.calculate_blues_anticone_sizes(&blue_set, &parents_set).await?;
-
         Ok(GhostDagData {
             blue_score,
             blue_work,
-            selected_parent,
+            // GhostDAG data keeps the zero hash for genesis
+            selected_parent: selected_parent.unwrap_or_default(),
             merge_set_blues: blue_set,
             merge_set_reds: red_set,
             blues_anticone_sizes,
         })
     }
 
-    /// Calculates blue and red sets using PHANTOM algorithm.
-    async fn calculate_blue_set(&self, _block: &Block, parents: &[Hash]) -> ConsensusResult<(Vec<Hash>, Vec<Hash>)> {
-        let mut blue_set = Vec::new();
-        let mut red_set = Vec::new();
-        let mut queue = VecDeque::new();
-        let mut visited = HashSet::new();
-
-        // Start with parents
+    /// Ensures every known parent has computed GhostDAG data.
+    fn ensure_parents_have_data(&self, parents: &[Hash]) -> ConsensusResult<()> {
         for parent in parents {
-            queue.push_back(*parent);
+            if self.block_relations.contains_key(parent) && !self.blue_scores.contains_key(parent) {
+                return Err(crate::errors::ConsensusError::MissingGhostDagData { block: *parent });
+            }
         }
+        Ok(())
+    }
 
-        while let Some(current) = queue.pop_front() {
-            if visited.contains(&current) {
-                continue;
+    /// Colours the mergeset of a block with the given parents using the PHANTOM algorithm. The
+    /// selected parent is blue; the rest of the mergeset follows in topological order, and a
+    /// candidate is blue if at most `k` blues of the block are in its anticone and none of those
+    /// already has `k` blues in its own anticone. Returns the blues, the reds and the blue anticone
+    /// sizes that colouring produced.
+    fn calculate_blue_set(&self, parents: &[Hash], selected_parent: Option<Hash>) -> (Vec<Hash>, Vec<Hash>, HashMap<Hash, u64>) {
+        let Some(selected_parent) = selected_parent else {
+            return (Vec::new(), Vec::new(), HashMap::new());
+        };
+        let mut candidates: Vec<Hash> =
+            self.mergeset_of_parents(parents, selected_parent).into_iter().filter(|hash| *hash != selected_parent).collect();
+        // Blue scores strictly grow from parent to child, so this order is topological
+        candidates.sort_unstable_by_key(|hash| (self.get_blue_score(hash).unwrap_or(0), *hash));
+
+        let mut blue_set = vec![selected_parent];
+        let mut red_set = Vec::new();
+        let mut blues_anticone_sizes = HashMap::from([(selected_parent, 0)]);
+        for candidate in candidates {
+            match self.blue_anticone_within_k(candidate, &blue_set, &blues_anticone_sizes, selected_parent) {
+                Some(anticone_blues) => {
+                    for (blue, size) in &anticone_blues {
+                        blues_anticone_sizes.insert(*blue, size + 1);
+                    }
+                    blues_anticone_sizes.insert(candidate, anticone_blues.len() as u64);
+                    blue_set.push(candidate);
+                }
+                None => red_set.push(candidate),
             }
-            visited.insert(current);
+        }
 
-            // Calculate anticone size with optimization
-            let anticone_size = self.calculate_anticone_size_optimized(&current, &HashSet::new()).await?;
+        (blue_set, red_set, blues_anticone_sizes)
+    }
 
-            if anticone_size <= self.k as u64 {
-                blue_set.push(current);
-            } else {
-                red_set.push(current);
+    /// Collects the blues in the anticone of `candidate` together with their current blue anticone
+    /// sizes, or returns `None` once the candidate can't join the k-cluster. Blues are visited from
+    /// the new block down its selected chain, stopping at the first chain block in the candidate's
+    /// past since every blue below it is in that past as well.
+    fn blue_anticone_within_k(
+        &self,
+        candidate: Hash,
+        blue_set: &[Hash],
+        blues_anticone_sizes: &HashMap<Hash, u64>,
+        selected_parent: Hash,
+    ) -> Option<Vec<(Hash, u64)>> {
+        let mut anticone_blues = Vec::new();
+        let mut chain_blues = blue_set.to_vec();
+        let mut next_chain_block = Some(selected_parent);
+        loop {
+            for blue in chain_blues {
+                if self.is_ancestor(blue, candidate) {
+                    continue;
+                }
+                let size = self.blue_anticone_size(blue, blues_anticone_sizes, selected_parent);
+                if size >= self.k as u64 || anticone_blues.len() >= self.k as usize {
+                    return None;
+                }
+                anticone_blues.push((blue, size));
             }
 
-            // Add ancestors to queue
-            if let Some(relations) = self.block_relations.get(&current) {
-                for parent in &relations.parents {
-                    queue.push_back(*parent);
-                }
+            let Some(chain_block) = next_chain_block else { break };
+            if self.is_ancestor(chain_block, candidate) {
+                break;
             }
+            let Some(relations) = self.block_relations.get(&chain_block) else { break };
+            chain_blues = relations.merge_set_blues.clone();
+            next_chain_block = relations.selected_parent;
         }
-
-        Ok((blue_set, red_set))
+        Some(anticone_blues)
     }
 
-    /// Selects the parent with the highest blue score.
-    async fn select_parent(&self, parents: &[Hash]) -> ConsensusResult<Hash> {
-        if parents.is_empty() {
-            // Genesis block has no parents, return default hash
-            return Ok(Hash::default());
+    /// Looks up the blue anticone size of `blue` as seen from the block being coloured: first in
+    /// the sizes coloured so far, then along the selected chain starting at `selected_parent`.
+    fn blue_anticone_size(&self, blue: Hash, blues_anticone_sizes: &HashMap<Hash, u64>, selected_parent: Hash) -> u64 {
+        if let Some(size) = blues_anticone_sizes.get(&blue) {
+            return *size;
         }
+        let mut chain_block = Some(selected_parent);
+        while let Some(hash) = chain_block {
+            let relations = self.block_relations.get(&hash).expect("selected chain blocks are in the DAG");
+            if let Some(size) = relations.blues_anticone_sizes.get(&blue) {
+                return *size;
+            }
+            chain_block = relations.selected_parent;
+        }
+        unreachable!("blue {} has no anticone size along the selected chain", blue)
+    }
 
-        let selected = parents
-            .par_iter()
-            .max_by_key(|parent| {
-                self.blue_scores.get(parent).map(|s| *s).unwrap_or(0)
-            })
-            .cloned()
-            .ok_or(crate::errors::ConsensusError::NoValidParent)?;
-
-        Ok(selected)
+    /// Selects the parent with the most blue work, breaking ties by hash, or `None` for genesis.
+    /// This is the same `(blue_work, hash)` key `ChainSelector` uses for the virtual tip.
+    fn select_parent(&self, parents: &[Hash]) -> Option<Hash> {
+        parents.par_iter().max_by_key(|parent| (self.get_blue_work(parent).unwrap_or_default(), **parent)).copied()
     }
 
     /// Calculates the accumulated blue work for a set of blocks.
     async fn calculate_blue_work_proper(&self, blue_set: &[Hash]) -> ConsensusResult<BlueWorkType> {
-        let mut total_work: u128 = 0;
+        let mut total_work = BlueWorkType::from_u64(0);
+        for block_hash in blue_set {
+            total_work = total_work.saturating_add(self.get_block_work(block_hash).await?);
+        }
+        Ok(total_work)
+    }
+
+    /// Gets the work contributed by a block, derived from its target bits. Blocks not in the DAG
+    /// contribute no work.
+    async fn get_block_work(&self, block_hash: &Hash) -> ConsensusResult<BlueWorkType> {
+        Ok(self
+            .block_relations
+            .get(block_hash)
+            .map_or(BlueWorkType::from_u64(0), |relations| BlueWorkType::work_from_bits(relations.bits)))
+    }
 
-        for &block_hash in blue_set {
-            // Accumulate actual work (placeholder - implement proper work calculation)
-            let _block_work = self.get_block_work(&block_hash).await?;
-            // For now, convert to u128 for accumulation (simplified)
-            // In real implementation, proper big integer addition needed
-            total_work += 1; // Placeholder
+    /// Checks the `blues_anticone_sizes` claimed in the GhostDAG data of a batch of blocks, e.g.
+    /// during trusted sync, against the sizes produced by colouring each block's mergeset over the
+    /// current DAG. Blocks sharing parents share one colouring, and the distinct colourings run in
+    /// parallel.
+    pub fn verify_anticone_sizes(&self, blocks: &[Block]) -> ConsensusResult<()> {
+        let mut claims: HashMap<Vec<Hash>, Vec<&HashMap<Hash, u64>>> = HashMap::new();
+        for block in blocks {
+            let data = block.ghostdag_data.as_ref().ok_or(crate::errors::ConsensusError::MissingGhostDagData { block: block.hash() })?;
+            let mut parents: Vec<Hash> = block.header.parents_by_level.iter().flatten().copied().collect();
+            parents.sort_unstable();
+            parents.dedup();
+            claims.entry(parents).or_default().push(&data.blues_anticone_sizes);
         }
 
-        Ok(BlueWorkType::from_u64(total_work as u64))
+        let valid = claims.par_iter().all(|(parents, claimed)| {
+            let (_, _, sizes) = self.calculate_blue_set(parents, self.select_parent(parents));
+            claimed.iter().all(|claimed| **claimed == sizes)
+        });
+        if !valid {
+            return Err(crate::errors::ConsensusError::InvalidAnticone);
+        }
+        Ok(())
     }
 
-    /// Gets the work contributed by a block.
-    async fn get_block_work(&self, _block_hash: &Hash) -> ConsensusResult<BlueWorkType> {
-        // Placeholder: implement based on difficulty target
-        // Work = 2^256 / (target + 1) for Bitcoin-style
-        Ok(BlueWorkType::from_u64(1))
+    /// Checks if a reference block is reachable from a candidate block through parent links.
+    pub async fn is_in_past_cone(&self, candidate: &Hash, reference: &Hash) -> ConsensusResult<bool> {
+        Ok(self.is_ancestor(*reference, *candidate))
     }
 
-    /// Calculates anticone size for a block with optimization.
-    async fn calculate_anticone_size_optimized(
-        &self,
-        block_hash: &Hash,
-        visited: &HashSet<Hash>
-    ) -> ConsensusResult<u64> {
-        let mut size = 0u64;
-        let mut to_visit = VecDeque::new();
-        let mut visited_local = HashSet::new();
-
-        // Start from block's future (descendants)
-        to_visit.push_back(*block_hash);
-
-        while let Some(current) = to_visit.pop_front() {
-            if visited_local.contains(&current) || visited.contains(&current) {
-                continue;
+    /// Checks if `ancestor` is in the past of `descendant`, following all parents rather than only
+    /// the selected parent. A block counts as its own ancestor. Blue scores strictly grow from
+    /// parent to child, so the walk doesn't descend below the blue score of `ancestor`.
+    pub fn is_ancestor(&self, ancestor: Hash, descendant: Hash) -> bool {
+        if ancestor == descendant {
+            return true;
+        }
+        let Some(floor) = self.get_blue_score(&ancestor) else { return false };
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::from([descendant]);
+        while let Some(current) = queue.pop_front() {
+            let Some(relations) = self.block_relations.get(&current) else { continue };
+            for parent in &relations.parents {
+                if *parent == ancestor {
+                    return true;
+                }
+                if self.get_blue_score(parent).is_some_and(|score| score > floor) && visited.insert(*parent) {
+                    queue.push_back(*parent);
+                }
             }
-            visited_local.insert(current);
+        }
+        false
+    }
 
-            if current != *block_hash {
-                size += 1;
-            }
-            // Add children to visit
+    /// Returns the blocks that are neither in the past nor in the future of `block`, sorted by
+    /// hash. Unknown blocks have an empty anticone.
+    pub fn anticone(&self, block: Hash) -> Vec<Hash> {
+        if !self.block_relations.contains_key(&block) {
+            return vec![];
+        }
+        let past = self.brute_force_past(&block);
+        let mut future = HashSet::new();
+        let mut queue = VecDeque::from([block]);
+        while let Some(current) = queue.pop_front() {
             if let Some(relations) = self.block_relations.get(&current) {
                 for child in relations.children.read().iter() {
-                    to_visit.push_back(*child);
+                    if future.insert(*child) {
+                        queue.push_back(*child);
+                    }
                 }
             }
         }
 
-        Ok(size)
+        let mut anticone: Vec<Hash> = self
+            .block_relations
+            .iter()
+            .map(|entry| *entry.key())
+            .filter(|hash| *hash != block && !past.contains(hash) && !future.contains(hash))
+            .collect();
+        anticone.sort_unstable();
+        anticone
     }
 
-    /// Checks if a candidate block is in the past cone of a reference block.
-    async fn is_in_past_cone(&self, candidate: &Hash, reference: &Hash) -> ConsensusResult<bool> {
-        let mut current = *candidate;
-        while current != *reference {
-            if let Some(relations) = self.block_relations.get(&current) {
-                if let Some(parent) = relations.selected_parent {
-                    current = parent;
-                } else {
-                    return Ok(false);
+    /// Cross-checks `is_ancestor`, which prunes its walk by blue score, against a brute-force BFS
+    /// over every parent link, returning the first pair on which the two disagree. All pairs are
+    /// checked for DAGs of up to `REACHABILITY_FULL_CHECK_LIMIT` blocks; larger DAGs are sampled
+    /// with a fixed stride. Compute-intensive, meant to run only with sanity checks enabled.
+    pub fn verify_reachability_consistency(&self) -> ConsensusResult<()> {
+        let mut blocks: Vec<Hash> = self.block_relations.iter().map(|entry| *entry.key()).collect();
+        blocks.sort_unstable();
+        let stride = blocks.len().div_ceil(REACHABILITY_FULL_CHECK_LIMIT).max(1);
+
+        for descendant in &blocks {
+            let past = self.brute_force_past(descendant);
+            for ancestor in blocks.iter().step_by(stride) {
+                let expected = ancestor == descendant || past.contains(ancestor);
+                if self.is_ancestor(*ancestor, *descendant) != expected {
+                    return Err(crate::errors::ConsensusError::ReachabilityInconsistency {
+                        ancestor: *ancestor,
+                        descendant: *descendant,
+                    });
                 }
-            } else {
-                return Ok(false);
             }
         }
-        Ok(true)
+        Ok(())
     }
 
-    /// Calculates anticone sizes for blue blocks.
-    async fn calculate_blues_anticone_sizes(&self, blue_set: &[Hash], parents: &HashSet<Hash>) -> ConsensusResult<HashMap<Hash, u64>> {
-        let mut sizes = HashMap::new();
-
-        // Parallel calculation for performance
-        let results: Vec<_> = blue_set.par_iter()
-            .map(|blue_block| {
-                let size = self.calculate_anticone_size_optimized(blue_block, parents);
-                (blue_block, size)
-            })
-            .collect();
-
-        for (blue_block, size_result) in results {
-            let size = size_result.await?;
-            sizes.insert(*blue_block, size);
+    /// Collects every known ancestor of a block by walking all parent links.
+    fn brute_force_past(&self, block_hash: &Hash) -> HashSet<Hash> {
+        let mut past = HashSet::new();
+        let mut queue = VecDeque::from([*block_hash]);
+        while let Some(current) = queue.pop_front() {
+            if let Some(relations) = self.block_relations.get(&current) {
+                for parent in &relations.parents {
+                    if past.insert(*parent) {
+                        queue.push_back(*parent);
+                    }
+                }
+            }
         }
+        past
+    }
 
-        Ok(sizes)
+    /// Computes the mergeset of a (possibly hypothetical) block with the given parents: the parents
+    /// and their past, minus the past of `selected_parent`. The walk goes back from the other
+    /// parents and stops at blocks in the selected parent's past, so it only visits the mergeset
+    /// and its boundary.
+    pub(crate) fn mergeset_of_parents(&self, parents: &[Hash], selected_parent: Hash) -> HashSet<Hash> {
+        let mut mergeset = HashSet::from([selected_parent]);
+        let mut visited = HashSet::from([selected_parent]);
+        let mut queue: VecDeque<Hash> = parents.iter().copied().collect();
+        while let Some(current) = queue.pop_front() {
+            if !visited.insert(current) || self.is_ancestor(current, selected_parent) {
+                continue;
+            }
+            mergeset.insert(current);
+            if let Some(relations) = self.block_relations.get(&current) {
+                queue.extend(relations.parents.iter().copied());
+            }
+        }
+        mergeset
     }
 
     /// Gets the blue score for a block.
@@ -269,6 +458,96 @@ impl GhostDag {
         self.blue_scores.get(block_hash).map(|s| *s)
     }
 
+    /// Gets the accumulated blue work for a block.
+    pub fn get_blue_work(&self, block_hash: &Hash) -> Option<BlueWorkType> {
+        self.block_relations.get(block_hash).map(|r| r.blue_work)
+    }
+
+    /// Marks a block and all of its known descendants as invalid, returning every block reached
+    /// (including those that were already invalid).
+    pub fn mark_invalid(&self, block: Hash) -> Vec<Hash> {
+        let mut affected = Vec::new();
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::from([block]);
+        while let Some(current) = queue.pop_front() {
+            if !visited.insert(current) {
+                continue;
+            }
+            self.invalid_blocks.insert(current);
+            affected.push(current);
+            if let Some(relations) = self.block_relations.get(&current) {
+                queue.extend(relations.children.read().iter().copied());
+            }
+        }
+        affected
+    }
+
+    /// Checks whether a block was marked invalid.
+    pub fn is_invalid(&self, block_hash: &Hash) -> bool {
+        self.invalid_blocks.contains(block_hash)
+    }
+
+    /// Returns the mergeset of a block, i.e. the blocks in its past but not in the past of its
+    /// selected parent (including the selected parent itself). The order is topological, preferring
+    /// blues over reds and then lower hashes whenever several blocks are ready.
+    pub fn ordered_mergeset(&self, block: Hash) -> ConsensusResult<Vec<Hash>> {
+        let relations = self.get_relations(&block).ok_or(crate::errors::ConsensusError::MissingGhostDagData { block })?;
+        let mergeset = match relations.selected_parent {
+            Some(selected_parent) => self.mergeset_of_parents(&relations.parents, selected_parent),
+            None => HashSet::new(),
+        };
+        let reds: HashSet<&Hash> = relations.merge_set_reds.iter().collect();
+
+        let mut in_degrees: HashMap<Hash, usize> = HashMap::new();
+        let mut children: HashMap<Hash, Vec<Hash>> = HashMap::new();
+        for hash in &mergeset {
+            let parents = self.block_relations.get(hash).map(|r| r.parents.clone()).unwrap_or_default();
+            let parents: HashSet<Hash> = parents.into_iter().filter(|parent| mergeset.contains(parent)).collect();
+            in_degrees.insert(*hash, parents.len());
+            for parent in parents {
+                children.entry(parent).or_default().push(*hash);
+            }
+        }
+
+        let mut ready: BinaryHeap<_> = in_degrees
+            .iter()
+            .filter(|(_, &degree)| degree == 0)
+            .map(|(hash, _)| Reverse((reds.contains(hash), *hash)))
+            .collect();
+        let mut order = Vec::with_capacity(mergeset.len());
+        while let Some(Reverse((_, hash))) = ready.pop() {
+            order.push(hash);
+            for child in children.get(&hash).into_iter().flatten() {
+                let degree = in_degrees.get_mut(child).unwrap();
+                *degree -= 1;
+                if *degree == 0 {
+                    ready.push(Reverse((reds.contains(child), *child)));
+                }
+            }
+        }
+        Ok(order)
+    }
+
+    /// Orders the mergeset recorded in `data` for consensus: blues first, then reds, each sorted by
+    /// ascending blue work with the hash breaking ties. Blocks unknown to the DAG count as zero work.
+    pub fn consensus_ordered_mergeset(&self, data: &GhostDagData) -> Vec<Hash> {
+        let sorted = |hashes: &[Hash]| {
+            let mut keyed: Vec<(BlueWorkType, Hash)> = hashes
+                .iter()
+                .map(|hash| (self.get_blue_work(hash).unwrap_or(BlueWorkType::from_u64(0)), *hash))
+                .collect();
+            keyed.sort();
+            keyed.into_iter().map(|(_, hash)| hash)
+        };
+        sorted(&data.merge_set_blues).chain(sorted(&data.merge_set_reds)).collect()
+    }
+
+    /// Returns the selected parent chosen when the block was added, or `None` for unknown blocks
+    /// and for genesis.
+    pub fn selected_parent(&self, block: Hash) -> Option<Hash> {
+        self.block_relations.get(&block)?.selected_parent
+    }
+
     /// Gets block relations.
     pub fn get_relations(&self, block_hash: &Hash) -> Option<BlockRelations> {
         self.block_relations.get(block_hash).map(|r| r.clone())
@@ -283,9 +562,37 @@ mod tests {
     fn create_test_block(parents: Vec<Hash>) -> Block {
         let mut header = Header::new();
         header.parents_by_level = vec![parents];
+        // Real bits, since a zero target would saturate every block's work and tie parent selection
+        header.bits = 0x1f7fffff;
         Block::new(header, vec![])
     }
 
+    #[test]
+    fn test_canonical_bytes_ignores_insertion_order() {
+        let hashes: Vec<Hash> = (1..=5).map(|i| Hash::from_le_u64([i, 0, 0, 0])).collect();
+        let mut data1 = GhostDagData {
+            blue_score: 5,
+            blue_work: BlueWorkType::from_u64(5),
+            selected_parent: hashes[0],
+            merge_set_blues: hashes.clone(),
+            ..Default::default()
+        };
+        let mut data2 = data1.clone();
+        for (i, hash) in hashes.iter().enumerate() {
+            data1.blues_anticone_sizes.insert(*hash, i as u64);
+        }
+        for (i, hash) in hashes.iter().enumerate().rev() {
+            data2.blues_anticone_sizes.insert(*hash, i as u64);
+        }
+
+        assert_eq!(data1, data2);
+        assert_eq!(data1.canonical_bytes(), data2.canonical_bytes());
+        assert_eq!(data1.commitment(), data2.commitment());
+
+        data2.blue_score += 1;
+        assert_ne!(data1.commitment(), data2.commitment());
+    }
+
     #[tokio::test]
     async fn test_ghostdag_add_block() {
         let ghostdag = GhostDag::new(10);
@@ -298,19 +605,6 @@ mod tests {
         assert_eq!(data.blue_score, 0); // Genesis has no parents
     }
 
-    #[tokio::test]
-    async fn test_calculate_anticone_size() {
-        let ghostdag = GhostDag::new(10);
-        let block = create_test_block(vec![]);
-
-        // Add genesis block
-        ghostdag.add_block(&block).await.unwrap();
-
-        let visited = HashSet::new();
-        let size = ghostdag.calculate_anticone_size_optimized(&block.hash(), &visited).await.unwrap();
-        assert_eq!(size, 0); // No other blocks
-    }
-
     #[tokio::test]
     async fn test_complex_dag_scenario() {
         let ghostdag = GhostDag::new(3);
@@ -336,6 +630,53 @@ mod tests {
         assert_eq!(data.blue_score, 2); // child1 + child2
     }
 
+    #[tokio::test]
+    async fn test_add_block_unknown_parent() {
+        let ghostdag = GhostDag::new(10);
+        let genesis = create_test_block(vec![]);
+        let parent = create_test_block(vec![genesis.hash()]);
+        let child = create_test_block(vec![parent.hash()]);
+        ghostdag.add_block(&genesis).await.unwrap();
+
+        assert_eq!(ghostdag.add_block(&child).await, Err(crate::errors::ConsensusError::NoValidParent));
+        assert!(ghostdag.get_relations(&child.hash()).is_none());
+        assert!(ghostdag.get_relations(&genesis.hash()).unwrap().children.read().is_empty());
+
+        ghostdag.add_block(&parent).await.unwrap();
+        let data = ghostdag.add_block(&child).await.unwrap();
+        assert_eq!(data.selected_parent, parent.hash());
+        assert_eq!(data.blue_score, 2);
+    }
+
+    #[tokio::test]
+    async fn test_add_block_parent_missing_data() {
+        let ghostdag = GhostDag::new(10);
+
+        // Register a parent in the DAG without computing its GhostDAG data
+        let parent = create_test_block(vec![]);
+        ghostdag.block_relations.insert(
+            parent.hash(),
+            BlockRelations {
+                parents: vec![],
+                children: Arc::new(RwLock::new(Vec::new())),
+                is_blue: true,
+                blue_score: 0,
+                blue_work: BlueWorkType::from_u64(0),
+                bits: 0,
+                timestamp: 0,
+                selected_parent: None,
+                merge_set_blues: vec![],
+                merge_set_reds: vec![],
+                blues_anticone_sizes: HashMap::new(),
+            },
+        );
+
+        let child = create_test_block(vec![parent.hash()]);
+        let result = ghostdag.add_block(&child).await;
+        assert_eq!(result, Err(crate::errors::ConsensusError::MissingGhostDagData { block: parent.hash() }));
+        assert!(ghostdag.get_relations(&child.hash()).is_none());
+    }
+
     #[tokio::test]
     async fn test_multi_level_parents() {
         let ghostdag = GhostDag::new(10);
@@ -355,4 +696,421 @@ mod tests {
         let result = ghostdag.add_block(&block).await;
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_reachability_consistency_random_dag() {
+        let ghostdag = GhostDag::new(10);
+        let genesis = create_test_block(vec![]);
+        ghostdag.add_block(&genesis).await.unwrap();
+
+        // Deterministic xorshift so the DAG shape is reproducible
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let mut hashes = vec![genesis.hash()];
+        for nonce in 1..40u64 {
+            let parent_count = 1 + (next() % 3) as usize;
+            let mut parents: Vec<Hash> = (0..parent_count).map(|_| hashes[next() as usize % hashes.len()]).collect();
+            parents.sort_unstable();
+            parents.dedup();
+            let mut block = create_test_block(parents);
+            block.header.nonce = nonce;
+            ghostdag.add_block(&block).await.unwrap();
+            hashes.push(block.hash());
+        }
+
+        assert_eq!(ghostdag.verify_reachability_consistency(), Ok(()));
+
+        // The bounded mergeset walk agrees with the set difference of full pasts
+        for block in &hashes[1..] {
+            let relations = ghostdag.get_relations(block).unwrap();
+            let selected_parent = relations.selected_parent.unwrap();
+            let selected_past = ghostdag.brute_force_past(&selected_parent);
+            let expected: HashSet<Hash> = ghostdag.brute_force_past(block).difference(&selected_past).copied().collect();
+            assert_eq!(ghostdag.mergeset_of_parents(&relations.parents, selected_parent), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reachability_inconsistency_detected() {
+        let ghostdag = GhostDag::new(10);
+        let genesis = create_test_block(vec![]);
+        let a = create_test_block(vec![genesis.hash()]);
+        let b = create_test_block(vec![a.hash()]);
+        for block in [&genesis, &a, &b] {
+            ghostdag.add_block(block).await.unwrap();
+        }
+        assert_eq!(ghostdag.verify_reachability_consistency(), Ok(()));
+
+        // A blue score no higher than the ancestor's makes `is_ancestor` prune the walk through `a`
+        ghostdag.blue_scores.insert(a.hash(), 0);
+        assert_eq!(
+            ghostdag.verify_reachability_consistency(),
+            Err(crate::errors::ConsensusError::ReachabilityInconsistency { ancestor: genesis.hash(), descendant: b.hash() })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mark_invalid_propagates_to_descendants() {
+        let ghostdag = GhostDag::new(10);
+        let genesis = create_test_block(vec![]);
+        ghostdag.add_block(&genesis).await.unwrap();
+        let a = create_test_block(vec![genesis.hash()]);
+        ghostdag.add_block(&a).await.unwrap();
+        let mut b = create_test_block(vec![genesis.hash()]);
+        b.header.nonce = 1;
+        ghostdag.add_block(&b).await.unwrap();
+        let c = create_test_block(vec![a.hash(), b.hash()]);
+        ghostdag.add_block(&c).await.unwrap();
+
+        let affected: HashSet<Hash> = ghostdag.mark_invalid(a.hash()).into_iter().collect();
+        assert_eq!(affected, HashSet::from([a.hash(), c.hash()]));
+        assert!(ghostdag.is_invalid(&a.hash()));
+        assert!(ghostdag.is_invalid(&c.hash()));
+        assert!(!ghostdag.is_invalid(&b.hash()));
+        assert!(!ghostdag.is_invalid(&genesis.hash()));
+
+        // New children of an invalid block are rejected and marked too
+        let d = create_test_block(vec![c.hash()]);
+        assert_eq!(ghostdag.add_block(&d).await, Err(crate::errors::ConsensusError::InvalidParent { parent: c.hash() }));
+        assert!(ghostdag.is_invalid(&d.hash()));
+    }
+
+    #[tokio::test]
+    async fn test_ordered_mergeset() {
+        let ghostdag = GhostDag::new(10);
+        let genesis = create_test_block(vec![]);
+        ghostdag.add_block(&genesis).await.unwrap();
+        let mut chain = vec![genesis.hash()];
+        for nonce in 1..=3 {
+            let mut block = create_test_block(vec![*chain.last().unwrap()]);
+            block.header.nonce = nonce;
+            ghostdag.add_block(&block).await.unwrap();
+            chain.push(block.hash());
+        }
+        let a = create_test_block(vec![genesis.hash()]);
+        ghostdag.add_block(&a).await.unwrap();
+        let a2 = create_test_block(vec![a.hash()]);
+        ghostdag.add_block(&a2).await.unwrap();
+        let merge = create_test_block(vec![a2.hash(), chain[3]]);
+        ghostdag.add_block(&merge).await.unwrap();
+
+        // The longer chain is selected, so the side branch `a <- a2` is merged
+        let selected_parent = ghostdag.get_relations(&merge.hash()).unwrap().selected_parent.unwrap();
+        assert_eq!(selected_parent, chain[3]);
+        let mergeset = ghostdag.ordered_mergeset(merge.hash()).unwrap();
+        assert_eq!(mergeset.iter().copied().collect::<HashSet<_>>(), HashSet::from([chain[3], a.hash(), a2.hash()]));
+        let position = |hash: Hash| mergeset.iter().position(|h| *h == hash).unwrap();
+        assert!(position(a.hash()) < position(a2.hash()));
+
+        assert_eq!(ghostdag.ordered_mergeset(genesis.hash()), Ok(vec![]));
+        assert_eq!(
+            ghostdag.ordered_mergeset(Hash::default()),
+            Err(crate::errors::ConsensusError::MissingGhostDagData { block: Hash::default() })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_selected_parent() {
+        let ghostdag = GhostDag::new(10);
+        let genesis = create_test_block(vec![]);
+        ghostdag.add_block(&genesis).await.unwrap();
+        let short = create_test_block(vec![genesis.hash()]);
+        ghostdag.add_block(&short).await.unwrap();
+        let mut long = genesis.hash();
+        for _ in 0..3 {
+            let block = create_test_block(vec![long]);
+            ghostdag.add_block(&block).await.unwrap();
+            long = block.hash();
+        }
+
+        let merge = create_test_block(vec![short.hash(), long]);
+        let data = ghostdag.add_block(&merge).await.unwrap();
+        assert_eq!(data.selected_parent, long);
+        assert_eq!(ghostdag.selected_parent(merge.hash()), Some(data.selected_parent));
+        assert_eq!(ghostdag.selected_parent(short.hash()), Some(genesis.hash()));
+        assert_eq!(ghostdag.selected_parent(genesis.hash()), None);
+        assert_eq!(ghostdag.selected_parent(Hash::from_le_u64([9, 9, 9, 9])), None);
+    }
+
+    #[tokio::test]
+    async fn test_genesis_has_no_selected_parent() {
+        let ghostdag = GhostDag::new(10);
+        let genesis = create_test_block(vec![]);
+        let data = ghostdag.add_block(&genesis).await.unwrap();
+        assert_eq!(ghostdag.get_relations(&genesis.hash()).unwrap().selected_parent, None);
+        assert_eq!(data.selected_parent, Hash::default());
+
+        // Walks along the selected chain stop at genesis instead of the zero hash
+        let child = create_test_block(vec![genesis.hash()]);
+        ghostdag.add_block(&child).await.unwrap();
+        assert!(ghostdag.is_in_past_cone(&child.hash(), &genesis.hash()).await.unwrap());
+        assert!(!ghostdag.is_in_past_cone(&child.hash(), &Hash::default()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_large_anticone_colours_merged_blocks_red() {
+        let ghostdag = GhostDag::new(2);
+        let genesis = create_test_block(vec![]);
+        ghostdag.add_block(&genesis).await.unwrap();
+        let mut siblings = Vec::new();
+        for nonce in 1..=5 {
+            let mut sibling = create_test_block(vec![genesis.hash()]);
+            sibling.header.nonce = nonce;
+            ghostdag.add_block(&sibling).await.unwrap();
+            siblings.push(sibling.hash());
+        }
+        siblings.sort_unstable();
+
+        // The five siblings are pairwise in each other's anticone, so with k = 2 only the selected
+        // parent and two more fit; genesis is in the selected parent's past, not the mergeset
+        let merge = create_test_block(siblings.clone());
+        let data = ghostdag.add_block(&merge).await.unwrap();
+        assert_eq!(data.selected_parent, siblings[4]);
+        assert_eq!(data.merge_set_blues, vec![siblings[4], siblings[0], siblings[1]]);
+        assert_eq!(data.merge_set_reds, vec![siblings[2], siblings[3]]);
+        assert_eq!(data.blue_score, 4);
+        assert!(data.blues_anticone_sizes.values().all(|&size| size == 2));
+
+        // A chain stays entirely blue however long it gets
+        let mut tip = merge.hash();
+        for nonce in 10..20 {
+            let mut block = create_test_block(vec![tip]);
+            block.header.nonce = nonce;
+            let data = ghostdag.add_block(&block).await.unwrap();
+            assert_eq!(data.merge_set_blues, vec![tip]);
+            assert!(data.merge_set_reds.is_empty());
+            tip = block.hash();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_diamond_reachability() {
+        let ghostdag = GhostDag::new(10);
+        let genesis = create_test_block(vec![]);
+        ghostdag.add_block(&genesis).await.unwrap();
+        let mut left = create_test_block(vec![genesis.hash()]);
+        left.header.nonce = 1;
+        let mut right = create_test_block(vec![genesis.hash()]);
+        right.header.nonce = 2;
+        ghostdag.add_block(&left).await.unwrap();
+        ghostdag.add_block(&right).await.unwrap();
+        let mut parents = vec![left.hash(), right.hash()];
+        parents.sort_unstable();
+        let merge = create_test_block(parents);
+        let data = ghostdag.add_block(&merge).await.unwrap();
+
+        // Each side of the diamond has the other in its blue anticone; genesis isn't merged again
+        assert_eq!(data.blues_anticone_sizes, HashMap::from([(left.hash(), 1), (right.hash(), 1)]));
+        assert_eq!(data.blue_score, 3);
+
+        assert!(!ghostdag.is_ancestor(left.hash(), right.hash()));
+        assert!(!ghostdag.is_ancestor(right.hash(), left.hash()));
+        assert_eq!(ghostdag.anticone(left.hash()), vec![right.hash()]);
+        assert_eq!(ghostdag.anticone(right.hash()), vec![left.hash()]);
+        assert!(ghostdag.anticone(genesis.hash()).is_empty());
+        assert!(ghostdag.anticone(merge.hash()).is_empty());
+        assert!(ghostdag.anticone(Hash::from_le_u64([9, 9, 9, 9])).is_empty());
+
+        // Both sides of the diamond are in the merge block's past, not only the selected parent
+        let selected = ghostdag.selected_parent(merge.hash()).unwrap();
+        let merged = if selected == left.hash() { right.hash() } else { left.hash() };
+        for ancestor in [genesis.hash(), left.hash(), right.hash(), merge.hash()] {
+            assert!(ghostdag.is_ancestor(ancestor, merge.hash()));
+        }
+        assert!(ghostdag.is_in_past_cone(&merge.hash(), &merged).await.unwrap());
+        assert!(!ghostdag.is_ancestor(merge.hash(), left.hash()));
+        assert!(!ghostdag.is_ancestor(Hash::from_le_u64([9, 9, 9, 9]), merge.hash()));
+    }
+
+    #[tokio::test]
+    async fn test_blue_work_follows_difficulty() {
+        let ghostdag = GhostDag::new(10);
+        let block_with_bits = |parents, bits| {
+            let mut header = Header::new();
+            header.parents_by_level = vec![parents];
+            header.bits = bits;
+            Block::new(header, vec![])
+        };
+        let genesis = block_with_bits(vec![], 0x1d00ffff);
+        ghostdag.add_block(&genesis).await.unwrap();
+
+        // Higher target, hence lower difficulty
+        let easy = block_with_bits(vec![genesis.hash()], 0x1f7fffff);
+        let hard = block_with_bits(vec![genesis.hash()], 0x1d00ffff);
+        ghostdag.add_block(&easy).await.unwrap();
+        ghostdag.add_block(&hard).await.unwrap();
+
+        let on_easy = ghostdag.add_block(&create_test_block(vec![easy.hash()])).await.unwrap();
+        let on_hard = ghostdag.add_block(&create_test_block(vec![hard.hash()])).await.unwrap();
+        let genesis_work = BlueWorkType::work_from_bits(genesis.header.bits);
+        assert_eq!(on_easy.blue_work, genesis_work.saturating_add(BlueWorkType::from_u64(512)));
+        assert!(on_easy.blue_work < on_hard.blue_work);
+    }
+
+    #[tokio::test]
+    async fn test_select_parent_by_blue_work() {
+        let ghostdag = GhostDag::new(10);
+        let block_with_bits = |parents, bits| {
+            let mut header = Header::new();
+            header.parents_by_level = vec![parents];
+            header.bits = bits;
+            Block::new(header, vec![])
+        };
+        let genesis = block_with_bits(vec![], 0x1d00ffff);
+        ghostdag.add_block(&genesis).await.unwrap();
+
+        // A longer chain of easy blocks has the higher blue score but less work than a single hard
+        // block, whose work shows up in the blue work of its child
+        let easy1 = block_with_bits(vec![genesis.hash()], 0x1f7fffff);
+        let easy2 = block_with_bits(vec![easy1.hash()], 0x1f7fffff);
+        let easy3 = block_with_bits(vec![easy2.hash()], 0x1f7fffff);
+        let hard = block_with_bits(vec![genesis.hash()], 0x1d00ffff);
+        let on_hard = block_with_bits(vec![hard.hash()], 0x1f7fffff);
+        for block in [&easy1, &easy2, &easy3, &hard, &on_hard] {
+            ghostdag.add_block(block).await.unwrap();
+        }
+        assert!(ghostdag.get_blue_score(&easy3.hash()) > ghostdag.get_blue_score(&on_hard.hash()));
+        assert!(ghostdag.get_blue_work(&easy3.hash()) < ghostdag.get_blue_work(&on_hard.hash()));
+
+        let merge = ghostdag.add_block(&create_test_block(vec![easy3.hash(), on_hard.hash()])).await.unwrap();
+        assert_eq!(merge.selected_parent, on_hard.hash());
+    }
+
+    #[tokio::test]
+    async fn test_consensus_ordered_mergeset() {
+        let ghostdag = GhostDag::new(10);
+        let block_with_bits = |parents, bits| {
+            let mut header = Header::new();
+            header.parents_by_level = vec![parents];
+            header.bits = bits;
+            Block::new(header, vec![])
+        };
+        let genesis = block_with_bits(vec![], 0x1f7fffff);
+        let left = block_with_bits(vec![genesis.hash()], 0x1f7fffff);
+        let right = block_with_bits(vec![genesis.hash()], 0x1f7ffffe);
+        for block in [&genesis, &left, &right] {
+            ghostdag.add_block(block).await.unwrap();
+        }
+        let merge = block_with_bits(vec![left.hash(), right.hash()], 0x1f7fffff);
+        let mut data = ghostdag.add_block(&merge).await.unwrap();
+
+        let order = ghostdag.consensus_ordered_mergeset(&data);
+        // The siblings tie on work and are ordered by hash; genesis is in the selected parent's past
+        let (first, second) = if left.hash() < right.hash() { (left.hash(), right.hash()) } else { (right.hash(), left.hash()) };
+        assert_eq!(order, vec![first, second]);
+
+        // Stable regardless of how the mergeset was collected
+        data.merge_set_blues.reverse();
+        assert_eq!(ghostdag.consensus_ordered_mergeset(&data), order);
+        assert_eq!(ghostdag.consensus_ordered_mergeset(&data), order);
+
+        // Reds always follow blues
+        let red = data.merge_set_blues.remove(0);
+        data.merge_set_reds.push(red);
+        assert_eq!(ghostdag.consensus_ordered_mergeset(&data).last(), Some(&red));
+    }
+
+    #[tokio::test]
+    async fn test_verify_anticone_sizes_batch() {
+        let ghostdag = GhostDag::new(10);
+        let block_with_nonce = |parents, nonce| {
+            let mut header = Header::new();
+            header.parents_by_level = vec![parents];
+            header.nonce = nonce;
+            Block::new(header, vec![])
+        };
+        let genesis = block_with_nonce(vec![], 0);
+        let left = block_with_nonce(vec![genesis.hash()], 1);
+        let right = block_with_nonce(vec![genesis.hash()], 2);
+        let merge = block_with_nonce(vec![left.hash(), right.hash()], 3);
+        let sibling = block_with_nonce(vec![left.hash(), right.hash()], 4);
+        let tip = block_with_nonce(vec![merge.hash(), sibling.hash()], 5);
+
+        // Claims are recorded as each block joins the DAG, as a syncing peer would send them
+        ghostdag.add_block(&genesis).await.unwrap();
+        let mut batch = vec![left, right, merge, sibling, tip];
+        for block in batch.iter_mut() {
+            block.ghostdag_data = Some(ghostdag.add_block(block).await.unwrap());
+        }
+        let sizes = |block: &Block| block.ghostdag_data.as_ref().unwrap().blues_anticone_sizes.clone();
+        assert_eq!(sizes(&batch[2]), HashMap::from([(batch[0].hash(), 1), (batch[1].hash(), 1)]));
+        assert_eq!(sizes(&batch[4]), HashMap::from([(batch[2].hash(), 1), (batch[3].hash(), 1)]));
+        assert_eq!(ghostdag.verify_anticone_sizes(&batch), Ok(()));
+
+        // Blocks added later don't change the colouring of the batch's mergesets
+        let side = block_with_nonce(vec![batch[2].hash()], 6);
+        let later = block_with_nonce(vec![side.hash(), batch[4].hash()], 7);
+        for block in [&side, &later] {
+            ghostdag.add_block(block).await.unwrap();
+        }
+        assert_eq!(ghostdag.verify_anticone_sizes(&batch), Ok(()));
+
+        let mut tampered = batch.clone();
+        let merge_hash = tampered[2].hash();
+        let data = tampered[4].ghostdag_data.as_mut().unwrap();
+        *data.blues_anticone_sizes.get_mut(&merge_hash).unwrap() += 1;
+        assert_eq!(ghostdag.verify_anticone_sizes(&tampered), Err(crate::errors::ConsensusError::InvalidAnticone));
+
+        let mut missing = batch.clone();
+        missing[1].ghostdag_data.as_mut().unwrap().blues_anticone_sizes.clear();
+        assert_eq!(ghostdag.verify_anticone_sizes(&missing), Err(crate::errors::ConsensusError::InvalidAnticone));
+
+        batch[0].ghostdag_data = None;
+        assert_eq!(
+            ghostdag.verify_anticone_sizes(&batch),
+            Err(crate::errors::ConsensusError::MissingGhostDagData { block: batch[0].hash() })
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_children_not_duplicated() {
+        let ghostdag = Arc::new(GhostDag::new(10));
+        let parent = create_test_block(vec![]);
+        ghostdag.add_block(&parent).await.unwrap();
+        let middle = create_test_block(vec![parent.hash()]);
+        ghostdag.add_block(&middle).await.unwrap();
+        let grandchild = create_test_block(vec![middle.hash()]);
+        ghostdag.add_block(&grandchild).await.unwrap();
+
+        let children: Vec<Block> = (1..=4)
+            .map(|nonce| {
+                let mut header = Header::new();
+                // The parent repeats across levels as well
+                header.parents_by_level = vec![vec![parent.hash()], vec![parent.hash()]];
+                header.nonce = nonce;
+                Block::new(header, vec![])
+            })
+            .collect();
+
+        // Every task adds every child, so each child is added many times concurrently
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let ghostdag = ghostdag.clone();
+                let children = children.clone();
+                tokio::spawn(async move {
+                    for child in &children {
+                        ghostdag.add_block(child).await.unwrap();
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        // Re-adding a block keeps the children linked to it
+        ghostdag.add_block(&middle).await.unwrap();
+
+        let linked = ghostdag.get_relations(&parent.hash()).unwrap().children.read().clone();
+        let unique: HashSet<Hash> = linked.iter().copied().collect();
+        assert_eq!(linked.len(), unique.len());
+        let mut expected: HashSet<Hash> = children.iter().map(|child| child.hash()).collect();
+        expected.insert(middle.hash());
+        assert_eq!(unique, expected);
+        assert_eq!(*ghostdag.get_relations(&middle.hash()).unwrap().children.read(), vec![grandchild.hash()]);
+    }
 }
@@ -1,4 +1,5 @@
 use crate::{BlueWorkType, Hash};
+use jio_math::Uint256;
 use std::hash::Hasher;
 
 pub trait HasherExtensions {
@@ -20,8 +21,8 @@ pub trait HasherExtensions {
     /// Writes the u64 as a little endian u8 array  
     fn write_u64(&mut self, element: u64) -> &mut Self;
 
-    /// Writes blue work as big endian bytes w/o the leading zeros
-    /// (emulates bigint.bytes() in the jiopad golang ref)
+    /// Writes blue work as big endian bytes w/o the leading zeros. Zero work is written
+    /// as an empty byte array (emulates bigint.bytes() in the jiopad golang ref)
     fn write_blue_work(&mut self, work: BlueWorkType) -> &mut Self;
 
     /// Writes the number of bytes followed by the bytes themselves
@@ -73,7 +74,8 @@ impl<T: Hasher> HasherExtensions for T {
 
     #[inline(always)]
     fn write_blue_work(&mut self, work: BlueWorkType) -> &mut Self {
-        let be_bytes = work.to_le_bytes();
+        let mut be_bytes = work.to_le_bytes();
+        be_bytes.reverse();
         let start = be_bytes.iter().copied().position(|byte| byte != 0).unwrap_or(be_bytes.len());
 
         self.write_var_bytes(&be_bytes[start..])
@@ -136,23 +138,124 @@ pub fn hash_transaction(data: &[u8]) -> Hash {
 
 /// Calculate the target from compact bits representation.
 pub fn target_from_bits(bits: u32) -> [u8; 32] {
-    let mut target = [0u8; 32];
-    let exponent = (bits >> 24) as usize;
-    let mantissa = bits & 0x00ffffff;
-    if exponent <= 3 {
-        let shift = 3 - exponent;
-        target[32 - shift..32].copy_from_slice(&(mantissa << (8 * shift)).to_be_bytes()[..shift]);
-    } else {
-        let shift = exponent - 3;
-        if shift < 29 {
-            target[32 - 4 - shift..32 - shift].copy_from_slice(&mantissa.to_be_bytes());
-        }
-    }
-    target
+    *Uint256::from_compact_target_bits(bits).as_bytes()
+}
+
+/// Calculate the canonical compact bits representation of a target.
+pub fn bits_from_target(target: &[u8; 32]) -> u32 {
+    Uint256::from(*target).to_compact_target_bits()
 }
 
-/// Check if hash meets the target.
+/// Check if hash meets the target, i.e. `hash <= target` as 256-bit numbers. The hash bytes are
+/// little-endian while the target is big-endian, as produced by [`target_from_bits`].
 pub fn meets_target(hash: &Hash, target: &[u8; 32]) -> bool {
-    hash.as_bytes() < target
+    let mut hash_be = *hash.as_bytes();
+    hash_be.reverse();
+    hash_be <= *target
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hasher recording every written byte, for pinning serialization.
+    #[derive(Default)]
+    struct RecordingHasher(Vec<u8>);
+
+    impl Hasher for RecordingHasher {
+        fn finish(&self) -> u64 {
+            0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            self.0.extend_from_slice(bytes);
+        }
+    }
+
+    #[test]
+    fn test_write_blue_work() {
+        let tests: Vec<(BlueWorkType, Vec<u8>)> = vec![
+            (BlueWorkType::from_u64(0), vec![0, 0, 0, 0, 0, 0, 0, 0]),
+            (BlueWorkType::from_u64(123456), vec![3, 0, 0, 0, 0, 0, 0, 0, 1, 226, 64]),
+        ];
+
+        for (work, expected) in tests {
+            let mut hasher = RecordingHasher::default();
+            hasher.write_blue_work(work);
+            assert_eq!(hasher.0, expected);
+        }
+    }
+
+    #[test]
+    fn test_bits_round_trip() {
+        for exponent in 1..=32u32 {
+            for mantissa in [0x01_0000, 0x12_3456, 0x7f_ffff] {
+                let bits = exponent << 24 | mantissa;
+                let target = target_from_bits(bits);
+                if exponent < 3 && mantissa & ((1 << (8 * (3 - exponent))) - 1) != 0 {
+                    // Low mantissa bytes are dropped, so the target re-encodes to a normalized form
+                    assert_eq!(target_from_bits(bits_from_target(&target)), target);
+                } else {
+                    assert_eq!(bits_from_target(&target), bits, "bits {bits:#010x}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bits_from_target_normalizes() {
+        let mut target = [0u8; 32];
+        assert_eq!(bits_from_target(&target), 0);
+
+        // A high byte of 0x80 would read as negative, so it takes an extra exponent byte
+        target[31] = 0x80;
+        assert_eq!(bits_from_target(&target), 0x0200_8000);
+        assert_eq!(target_from_bits(0x0200_8000), target);
+
+        // The full-width maximum needs exponent 33 and still decodes back to its truncated form
+        let max = [0xff; 32];
+        let bits = bits_from_target(&max);
+        assert_eq!(bits, 0x2100_ffff);
+        let truncated = target_from_bits(bits);
+        assert_eq!(&truncated[..2], &[0xff, 0xff]);
+        assert!(truncated[2..].iter().all(|&byte| byte == 0));
+        assert_eq!(bits_from_target(&truncated), bits);
+
+        // Non-canonical encodings collapse onto the canonical one
+        assert_eq!(bits_from_target(&target_from_bits(0x0500_1234)), 0x0412_3400);
+        assert_eq!(target_from_bits(0x2200_0001)[0], 0x01);
+        assert_eq!(target_from_bits(0x2300_0001), [0u8; 32]);
+    }
+
+    #[test]
+    fn test_meets_target_boundary() {
+        let target = target_from_bits(0x1d00_ffff);
+        let mut equal = target;
+        equal.reverse();
+        assert!(meets_target(&Hash::from_slice(&equal), &target));
+
+        // One unit above the target, in the little-endian hash bytes
+        let mut above = equal;
+        for byte in above.iter_mut() {
+            let (sum, carry) = byte.overflowing_add(1);
+            *byte = sum;
+            if !carry {
+                break;
+            }
+        }
+        assert!(!meets_target(&Hash::from_slice(&above), &target));
+    }
+
+    #[test]
+    fn test_meets_target_byte_order() {
+        // 0x1d00ffff is 0x00000000ffff0000...0000 big-endian
+        let target = target_from_bits(0x1d00_ffff);
+        assert_eq!(&target[..6], &[0, 0, 0, 0, 0xff, 0xff]);
+
+        // A large value in the low-order (first) hash byte is still a tiny number
+        assert!(meets_target(&Hash::from_le_u64([u64::MAX, 0, 0, 0]), &target));
+        // The last hash byte is the most significant, so this is far above the target
+        assert!(!meets_target(&Hash::from_le_u64([0, 0, 0, 1 << 56]), &target));
+        assert!(Hash::from_le_u64([0, 0, 0, 1 << 56]).to_string().starts_with("01"));
+    }
+}
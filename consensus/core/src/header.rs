@@ -1,6 +1,8 @@
 //! Block header data structures.
 
-use crate::{hashing, Hash, BlueWorkType};
+use crate::{config::params::Params, errors::{ConsensusError, ConsensusResult}, hashing, Hash, BlueWorkType};
+use jio_hashes::PowHash;
+use jio_math::Uint256;
 
 /// Block header.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -8,6 +10,7 @@ pub struct Header {
     pub version: u16,
     pub parents_by_level: Vec<Vec<Hash>>,
     pub merkle_root: Hash,
+    /// Unix time in milliseconds, see [`crate::constants::TimestampUnit`].
     pub timestamp: u64,
     pub bits: u32,
     pub nonce: u64,
@@ -37,6 +40,46 @@ impl Header {
         }
     }
 
+    /// Returns the parents at `level`, or an empty slice if the header has no such level.
+    pub fn parents_at_level(&self, level: usize) -> &[Hash] {
+        self.parents_by_level.get(level).map_or(&[], |parents| parents.as_slice())
+    }
+
+    /// Returns the number of parent levels.
+    pub fn num_levels(&self) -> usize {
+        self.parents_by_level.len()
+    }
+
+    /// Checks the header's merkle root against a root computed from transaction ids. Headers-first
+    /// sync can only compare against a root it was given, so the body must still be checked once
+    /// it arrives, see [`crate::Block::validate`].
+    pub fn has_merkle_root(&self, root: Hash) -> bool {
+        self.merkle_root == root
+    }
+
+    /// Validates the parent lists: level 0 may hold at most `max_block_parents` hashes, no level
+    /// may repeat a hash, and only genesis (no parents at any level) may have an empty level 0.
+    pub fn validate_parents(&self, params: &Params) -> ConsensusResult<()> {
+        let direct_parents = self.parents_at_level(0);
+        if direct_parents.len() > params.max_block_parents as usize {
+            return Err(ConsensusError::InvalidBlockHeader {
+                msg: format!("{} direct parents exceed the maximum of {}", direct_parents.len(), params.max_block_parents),
+            });
+        }
+        if direct_parents.is_empty() && self.parents_by_level.iter().any(|level| !level.is_empty()) {
+            return Err(ConsensusError::InvalidBlockHeader { msg: "Non-genesis header has no direct parents".to_string() });
+        }
+        for (level, parents) in self.parents_by_level.iter().enumerate() {
+            let mut seen = std::collections::HashSet::with_capacity(parents.len());
+            if let Some(duplicate) = parents.iter().find(|parent| !seen.insert(**parent)) {
+                return Err(ConsensusError::InvalidBlockHeader {
+                    msg: format!("Duplicate parent {} at level {}", duplicate, level),
+                });
+            }
+        }
+        Ok(())
+    }
+
     /// Computes the hash of the header.
     pub fn hash(&self) -> Hash {
         self.hash_with_nonce(self.nonce)
@@ -44,11 +87,59 @@ impl Header {
 
     /// Computes the hash of the header with a specific nonce (for mining optimization).
     pub fn hash_with_nonce(&self, nonce: u64) -> Hash {
-        // Serialize header fields except nonce, then append nonce
+        self.hash_override_nonce_time(nonce, self.timestamp)
+    }
+
+    /// Computes the hash of the header using the provided nonce and timestamp instead of its own.
+    pub fn hash_override_nonce_time(&self, nonce: u64, timestamp: u64) -> Hash {
         let mut data = Vec::new();
+        self.write_fields(&mut data, nonce, timestamp);
+        hashing::hash_block_header(&data)
+    }
+
+    /// Serializes the header in its canonical binary form, the same bytes its hash commits to.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        self.write_fields(&mut data, self.nonce, self.timestamp);
+        data
+    }
+
+    /// Deserializes a header produced by [`Header::serialize`]. The whole input must be consumed.
+    pub fn deserialize(bytes: &[u8]) -> Result<Header, HeaderDecodeError> {
+        let mut reader = HeaderReader { data: bytes };
+        let version = u16::from_le_bytes(reader.take_array()?);
+        let num_levels = reader.read_u32()? as usize;
+        let mut parents_by_level = Vec::with_capacity(num_levels.min(reader.data.len() / 4));
+        for _ in 0..num_levels {
+            let len = reader.read_u32()? as usize;
+            let level = reader.take(len.checked_mul(32).ok_or(HeaderDecodeError::UnexpectedEnd)?)?;
+            parents_by_level.push(level.chunks_exact(32).map(Hash::from_slice).collect());
+        }
+        let header = Header {
+            version,
+            parents_by_level,
+            merkle_root: reader.read_hash()?,
+            timestamp: reader.read_u64()?,
+            bits: u32::from_le_bytes(reader.take_array()?),
+            nonce: reader.read_u64()?,
+            daa_score: reader.read_u64()?,
+            blue_score: reader.read_u64()?,
+            blue_work: BlueWorkType::from_le_bytes(reader.take_array()?),
+            pruning_point: reader.read_hash()?,
+            cached_hash: None,
+        };
+        if !reader.data.is_empty() {
+            return Err(HeaderDecodeError::TrailingBytes { len: reader.data.len() });
+        }
+        Ok(header)
+    }
+
+    /// Writes every field except the cached hash, with `nonce` and `timestamp` overriding the
+    /// header's own.
+    fn write_fields(&self, data: &mut Vec<u8>, nonce: u64, timestamp: u64) {
         data.extend_from_slice(&self.version.to_le_bytes());
         // Serialize parents_by_level
-        data.extend_from_slice(&(self.parents_by_level.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(self.num_levels() as u32).to_le_bytes());
         for level in &self.parents_by_level {
             data.extend_from_slice(&(level.len() as u32).to_le_bytes());
             for parent in level {
@@ -56,16 +147,83 @@ impl Header {
             }
         }
         data.extend_from_slice(self.merkle_root.as_bytes());
-        data.extend_from_slice(&self.timestamp.to_le_bytes());
+        data.extend_from_slice(&timestamp.to_le_bytes());
         data.extend_from_slice(&self.bits.to_le_bytes());
         data.extend_from_slice(&nonce.to_le_bytes());
         data.extend_from_slice(&self.daa_score.to_le_bytes());
         data.extend_from_slice(&self.blue_score.to_le_bytes());
-        // BlueWorkType serialization placeholder
         data.extend_from_slice(&self.blue_work.to_le_bytes());
         data.extend_from_slice(self.pruning_point.as_bytes());
+    }
 
-        hashing::hash_block_header(&data)
+    /// Returns the mining work for this header as `(pre_pow_hash, timestamp)`, suitable for
+    /// `PowHash::new`. The pre-pow hash commits to every field except the nonce and timestamp,
+    /// so a pool may rotate the timestamp without redistributing the pre-pow hash.
+    pub fn mining_prefix(&self) -> (Hash, u64) {
+        (self.hash_override_nonce_time(0, 0), self.timestamp)
+    }
+
+    /// Computes the PoW hash of the header with its own nonce and timestamp.
+    pub fn pow_hash(&self) -> Hash {
+        let (pre_pow_hash, timestamp) = self.mining_prefix();
+        PowHash::new(pre_pow_hash, timestamp).finalize_with_nonce(self.nonce)
+    }
+
+    /// Decodes the compact `bits` into the full PoW target.
+    pub fn target(&self) -> Uint256 {
+        Uint256::from_compact_target_bits(self.bits)
+    }
+}
+
+/// Errors raised when decoding a serialized header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderDecodeError {
+    /// The input ended before every field was read.
+    UnexpectedEnd,
+    /// Bytes were left over after the last field.
+    TrailingBytes { len: usize },
+}
+
+impl std::fmt::Display for HeaderDecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeaderDecodeError::UnexpectedEnd => write!(f, "Serialized header ended unexpectedly"),
+            HeaderDecodeError::TrailingBytes { len } => write!(f, "Serialized header has {} trailing bytes", len),
+        }
+    }
+}
+
+impl std::error::Error for HeaderDecodeError {}
+
+/// Cursor over a serialized header.
+struct HeaderReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> HeaderReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], HeaderDecodeError> {
+        if self.data.len() < len {
+            return Err(HeaderDecodeError::UnexpectedEnd);
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], HeaderDecodeError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn read_u32(&mut self) -> Result<u32, HeaderDecodeError> {
+        Ok(u32::from_le_bytes(self.take_array()?))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, HeaderDecodeError> {
+        Ok(u64::from_le_bytes(self.take_array()?))
+    }
+
+    fn read_hash(&mut self) -> Result<Hash, HeaderDecodeError> {
+        Ok(Hash::from_slice(self.take(32)?))
     }
 }
 
@@ -75,6 +233,34 @@ impl Default for Header {
     }
 }
 
+/// Validates the header timestamp: it must not predate genesis, must be after the median time
+/// past of its ancestors and no further than `params.max_future_time_offset` ahead of `now`.
+pub fn validate_header_timestamp(header: &Header, median_time_past: u64, now: u64, params: &Params) -> ConsensusResult<()> {
+    if header.timestamp < params.genesis_timestamp {
+        return Err(ConsensusError::InvalidBlockHeader {
+            msg: format!("Timestamp {} predates the genesis timestamp {}", header.timestamp, params.genesis_timestamp),
+        });
+    }
+    check_timestamp(header, median_time_past, now.saturating_add(params.max_future_time_offset))
+}
+
+/// Validates the header timestamp against the consensus deviation tolerance: it must be after
+/// the median time past and at most `params.timestamp_deviation_tolerance` ahead of `now`.
+/// Timestamps are in milliseconds.
+pub fn validate_timestamp(header: &Header, median_time_past: u64, now: u64, params: &Params) -> ConsensusResult<()> {
+    check_timestamp(header, median_time_past, now.saturating_add(params.timestamp_deviation_tolerance))
+}
+
+fn check_timestamp(header: &Header, median_time_past: u64, max_allowed: u64) -> ConsensusResult<()> {
+    if header.timestamp <= median_time_past {
+        return Err(ConsensusError::TimestampTooOld { timestamp: header.timestamp, median_time_past });
+    }
+    if header.timestamp > max_allowed {
+        return Err(ConsensusError::TimestampTooFarInFuture { timestamp: header.timestamp, max_allowed });
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -92,4 +278,228 @@ mod tests {
         let hash = header.hash();
         assert!(!hash.as_bytes().is_empty());
     }
+
+    #[test]
+    fn test_mining_prefix() {
+        let mut header = Header::new();
+        header.timestamp = 1_700_000_000_000;
+        header.nonce = 5;
+        let (pre_pow_hash, timestamp) = header.mining_prefix();
+        assert_eq!(timestamp, header.timestamp);
+
+        // The prefix doesn't depend on the nonce or timestamp
+        let mut other = header.clone();
+        other.nonce = 9;
+        other.timestamp += 1000;
+        assert_eq!(other.mining_prefix().0, pre_pow_hash);
+
+        // The pre-pow hash is the header hash with the nonce and timestamp zeroed
+        let mut zeroed = header.clone();
+        zeroed.nonce = 0;
+        zeroed.timestamp = 0;
+        assert_eq!(pre_pow_hash, hashing::hash_block_header(&zeroed.serialize()));
+
+        // PRE_POW_HASH || TIME || 32 zero bytes || NONCE, through SHA3-256 and the HeavyHash matrix
+        let expected_pow_hash = |nonce: u64| {
+            use sha3::Digest;
+            let mut hasher = sha3::Sha3_256::new();
+            hasher.update(pre_pow_hash.as_bytes());
+            hasher.update(timestamp.to_le_bytes());
+            hasher.update([0u8; 32]);
+            hasher.update(nonce.to_le_bytes());
+            jio_hashes::Matrix::generate(pre_pow_hash).heavy_hash(Hash::from_slice(&hasher.finalize()))
+        };
+        assert_eq!(header.pow_hash(), expected_pow_hash(5));
+        header.nonce = 9;
+        assert_eq!(header.pow_hash(), expected_pow_hash(9));
+        assert_ne!(header.pow_hash(), expected_pow_hash(5));
+    }
+
+    #[test]
+    fn test_validate_header_timestamp_future_drift() {
+        let params = Params::default();
+        let now = 1_700_000_000_000;
+        let mut header = Header::new();
+
+        header.timestamp = now + params.max_future_time_offset;
+        assert!(validate_header_timestamp(&header, now - 1000, now, &params).is_ok());
+
+        header.timestamp += 1;
+        assert_eq!(
+            validate_header_timestamp(&header, now - 1000, now, &params),
+            Err(ConsensusError::TimestampTooFarInFuture {
+                timestamp: header.timestamp,
+                max_allowed: now + params.max_future_time_offset
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_header_timestamp_median_time_past() {
+        let params = Params::default();
+        let now = 1_700_000_000_000;
+        let mut header = Header::new();
+
+        header.timestamp = now - 1000;
+        assert_eq!(
+            validate_header_timestamp(&header, now - 1000, now, &params),
+            Err(ConsensusError::TimestampTooOld { timestamp: now - 1000, median_time_past: now - 1000 })
+        );
+
+        header.timestamp += 1;
+        assert!(validate_header_timestamp(&header, now - 1000, now, &params).is_ok());
+    }
+
+    #[test]
+    fn test_validate_header_timestamp_before_genesis() {
+        let params = Params::default();
+        let mut header = Header::new();
+
+        header.timestamp = params.genesis_timestamp - 1;
+        assert!(matches!(validate_header_timestamp(&header, 0, params.genesis_timestamp, &params), Err(ConsensusError::InvalidBlockHeader { .. })));
+
+        header.timestamp = params.genesis_timestamp;
+        assert_eq!(validate_header_timestamp(&header, 0, params.genesis_timestamp, &params), Ok(()));
+        header.timestamp = params.genesis_timestamp + 1000;
+        assert_eq!(validate_header_timestamp(&header, params.genesis_timestamp, params.genesis_timestamp + 1000, &params), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_timestamp_tolerance_boundary() {
+        let params = Params::default();
+        let now = 1_700_000_000_000;
+        let tolerance_ms = params.timestamp_deviation_tolerance;
+        let mut header = Header::new();
+
+        header.timestamp = now + tolerance_ms;
+        assert!(validate_timestamp(&header, now - 1000, now, &params).is_ok());
+
+        header.timestamp += 1;
+        assert_eq!(
+            validate_timestamp(&header, now - 1000, now, &params),
+            Err(ConsensusError::TimestampTooFarInFuture { timestamp: header.timestamp, max_allowed: now + tolerance_ms })
+        );
+    }
+
+    #[test]
+    fn test_validate_timestamp_median_time_past_boundary() {
+        let params = Params::default();
+        let now = 1_700_000_000_000;
+        let mut header = Header::new();
+
+        header.timestamp = now - 1000;
+        assert_eq!(
+            validate_timestamp(&header, now - 1000, now, &params),
+            Err(ConsensusError::TimestampTooOld { timestamp: now - 1000, median_time_past: now - 1000 })
+        );
+        header.timestamp += 1;
+        assert!(validate_timestamp(&header, now - 1000, now, &params).is_ok());
+    }
+
+    #[test]
+    fn test_header_target() {
+        let mut header = Header::new();
+        header.bits = 0x1d00ffff;
+        assert_eq!(header.target(), Uint256::from_compact_target_bits(0x1d00ffff));
+        header.bits = 0x1c0fffff;
+        assert!(header.target() < Uint256::from_compact_target_bits(0x1d00ffff));
+    }
+
+    #[test]
+    fn test_parents_at_level() {
+        let mut header = Header::new();
+        let parent = Hash::from_le_u64([1, 2, 3, 4]);
+        header.parents_by_level = vec![vec![parent], vec![]];
+        assert_eq!(header.num_levels(), 2);
+        assert_eq!(header.parents_at_level(0), &[parent]);
+        assert!(header.parents_at_level(1).is_empty());
+        assert!(header.parents_at_level(2).is_empty());
+        assert!(header.parents_at_level(usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn test_validate_parents() {
+        let params = Params::default();
+        let parents: Vec<Hash> = (0..params.max_block_parents as u64).map(|i| Hash::from_le_u64([i, 0, 0, 0])).collect();
+        let mut header = Header::new();
+        assert!(header.validate_parents(&params).is_ok());
+
+        header.parents_by_level = vec![parents.clone(), vec![parents[0]]];
+        assert!(header.validate_parents(&params).is_ok());
+    }
+
+    #[test]
+    fn test_validate_parents_too_many() {
+        let params = Params::default();
+        let mut header = Header::new();
+        header.parents_by_level = vec![(0..=params.max_block_parents as u64).map(|i| Hash::from_le_u64([i, 0, 0, 0])).collect()];
+        assert!(matches!(header.validate_parents(&params), Err(ConsensusError::InvalidBlockHeader { .. })));
+    }
+
+    #[test]
+    fn test_validate_parents_duplicate() {
+        let params = Params::default();
+        let (a, b) = (Hash::from_le_u64([1, 0, 0, 0]), Hash::from_le_u64([2, 0, 0, 0]));
+        let mut header = Header::new();
+        header.parents_by_level = vec![vec![a, b, a]];
+        assert!(header.validate_parents(&params).is_err());
+
+        // Duplicates are rejected at higher levels too
+        header.parents_by_level = vec![vec![a], vec![b, b]];
+        assert!(header.validate_parents(&params).is_err());
+    }
+
+    #[test]
+    fn test_validate_parents_empty_direct_level() {
+        let params = Params::default();
+        let mut header = Header::new();
+        header.parents_by_level = vec![vec![], vec![Hash::from_le_u64([1, 0, 0, 0])]];
+        assert!(header.validate_parents(&params).is_err());
+    }
+
+    fn sample_header() -> Header {
+        let mut header = Header::new();
+        header.parents_by_level = vec![
+            vec![Hash::from_le_u64([1, 0, 0, 0]), Hash::from_le_u64([2, 0, 0, 0])],
+            vec![Hash::from_le_u64([3, 0, 0, 0])],
+            vec![],
+        ];
+        header.merkle_root = Hash::from_le_u64([4, 0, 0, 0]);
+        header.timestamp = 1_700_000_000_000;
+        header.bits = 0x1d00ffff;
+        header.nonce = 42;
+        header.daa_score = 7;
+        header.blue_score = 6;
+        header.blue_work = BlueWorkType::from_be_bytes([0xab; 24]);
+        header.pruning_point = Hash::from_le_u64([5, 0, 0, 0]);
+        header
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        for header in [Header::new(), sample_header()] {
+            let bytes = header.serialize();
+            let decoded = Header::deserialize(&bytes).unwrap();
+            assert_eq!(decoded, header);
+            assert_eq!(decoded.parents_by_level.len(), header.parents_by_level.len());
+            // The hash commits to exactly the serialized bytes
+            assert_eq!(hashing::hash_block_header(&bytes), header.hash());
+        }
+    }
+
+    #[test]
+    fn test_deserialize_malformed() {
+        let bytes = sample_header().serialize();
+        assert_eq!(Header::deserialize(&bytes[..bytes.len() - 1]), Err(HeaderDecodeError::UnexpectedEnd));
+        assert_eq!(Header::deserialize(&[]), Err(HeaderDecodeError::UnexpectedEnd));
+
+        let mut extended = bytes.clone();
+        extended.push(0);
+        assert_eq!(Header::deserialize(&extended), Err(HeaderDecodeError::TrailingBytes { len: 1 }));
+
+        // A parent count larger than the input is rejected without allocating for it
+        let mut huge_level = bytes[..6].to_vec();
+        huge_level.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(Header::deserialize(&huge_level), Err(HeaderDecodeError::UnexpectedEnd));
+    }
 }
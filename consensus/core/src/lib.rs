@@ -19,9 +19,11 @@ pub mod block;
 pub mod blockhash;
 pub mod blockstatus;
 pub mod coinbase;
+pub mod compact_block;
 pub mod config;
 
 pub mod constants;
+pub mod daa;
 pub mod daa_score_timestamp;
 pub mod errors;
 
@@ -31,7 +33,9 @@ pub mod merkle;
 pub mod mining_rules;
 pub mod muhash;
 pub mod network;
+pub mod orphans;
 pub mod pruning;
+pub mod script;
 pub mod sign;
 pub mod subnets;
 pub mod trusted;
@@ -148,42 +152,47 @@ impl BuildHasher for BlockHasher {
 
 pub type BlockLevel = u8;
 
-#[cfg(test)]
-mod tests {
-    use super::BlockHasher;
-    use jio_hashes::Hash;
-    use std::hash::{Hash as _, Hasher as _};
-    #[test]
-    fn test_block_hasher() {
-        let hash = Hash::from_le_u64([1, 2, 3, 4]);
-        let mut hasher = BlockHasher::default();
-        hash.hash(&mut hasher);
-        assert_eq!(hasher.finish(), 4);
-    }
-}
-
 // Re-export modules for public API
 pub use acceptance_data::AcceptanceData;
 pub use api::{ConsensusApi, DefaultConsensusApi};
 pub use block::Block;
 pub use blockhash::{block_hash, is_valid_block_hash};
 pub use blockstatus::BlockStatus;
-pub use coinbase::{create_coinbase_transaction, validate_coinbase};
+pub use coinbase::{
+    block_subsidy, coinbase_output, coinbase_outputs, create_coinbase_transaction, subsidy_schedule, validate_coinbase,
+};
+pub use compact_block::{CompactBlock, ReconstructError};
 pub use config::Config as ConsensusConfig;
 pub use constants::*;
 pub use daa_score_timestamp::DaaScoreTimestamp;
 pub use errors::{ConsensusError, ConsensusResult};
 pub use hashing::{hash_data, hash_block_header};
-pub use header::Header;
-pub use mass::{calculate_block_mass, validate_block_mass, BlockMass};
+pub use header::{Header, HeaderDecodeError};
+pub use mass::{
+    calculate_block_mass, overall_transaction_mass, storage_mass, transaction_mass, validate_block_mass, validate_tx_mass, BlockMass,
+};
 pub use merkle::{MerkleTree, calculate_merkle_root};
-pub use mining_rules::{validate_mining_rules, check_proof_of_work};
+pub use mining_rules::{validate_mining_rules, validate_bits, check_proof_of_work, bits_to_target_hex, target_hex_to_bits};
 pub use muhash::MuHash;
-pub use network::{NetworkId, PeerAddress, NetworkMessage};
+pub use network::{NetworkId, PeerAddress, NetworkMessage, ProtocolError};
+pub use orphans::OrphanPool;
 pub use pruning::PruningManager;
-pub use sign::{sign_data, verify_signature};
-pub use subnets::{Subnet, SubnetId};
+pub use sign::{sign_data, verify_signature, SignError};
+pub use subnets::{Subnet, SubnetId, SubnetworkId};
 pub use trusted::{TrustedNode, TrustedData};
 pub use tx::{Transaction, TxInput, TxOutput};
 pub use utxo::{UtxoCollection, OutPoint};
 
+#[cfg(test)]
+mod tests {
+    use super::BlockHasher;
+    use jio_hashes::Hash;
+    use std::hash::{Hash as _, Hasher as _};
+    #[test]
+    fn test_block_hasher() {
+        let hash = Hash::from_le_u64([1, 2, 3, 4]);
+        let mut hasher = BlockHasher::default();
+        hash.hash(&mut hasher);
+        assert_eq!(hasher.finish(), 4);
+    }
+}
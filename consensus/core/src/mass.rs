@@ -1,6 +1,21 @@
 //! Block mass calculation utilities.
 
-use crate::errors::ConsensusResult;
+use crate::{config::params::Params, errors::ConsensusResult, tx::Transaction};
+
+/// Mass charged per byte of the serialized transaction.
+pub const MASS_PER_TX_BYTE: u64 = 1;
+
+/// Mass charged per byte an output adds to the UTXO set (its value and script public key), on
+/// top of its serialized bytes, since outputs are stored until they're spent.
+pub const MASS_PER_OUTPUT_STORAGE_BYTE: u64 = 10;
+
+/// Mass charged per signature operation.
+pub const MASS_PER_SIG_OP: u64 = 1000;
+
+/// The storage mass parameter `C` of KIP-9, in sompi² per unit of mass. An output worth `v`
+/// sompi adds `C / v` storage mass, so a single 1 JIO (10^8 sompi) output weighs 10,000 while
+/// dust outputs quickly exceed any mass limit.
+pub const STORAGE_MASS_PARAMETER: u64 = 1_000_000_000_000;
 
 /// Contextual masses for transactions.
 #[derive(Debug, Clone, Default)]
@@ -13,6 +28,55 @@ pub struct NonContextualMasses(pub u64);
 /// Block mass type.
 pub type BlockMass = u64;
 
+/// Calculates the mass of a transaction from its serialized size, the UTXO storage its outputs
+/// take and its signature operations.
+pub fn transaction_mass(tx: &Transaction) -> u64 {
+    let size_mass = tx.estimated_serialized_size() * MASS_PER_TX_BYTE;
+    let output_bytes_mass: u64 =
+        tx.outputs.iter().map(|output| (8 + output.script_pubkey.len() as u64) * MASS_PER_OUTPUT_STORAGE_BYTE).sum();
+    let sigop_mass = tx.sigops() as u64 * MASS_PER_SIG_OP;
+    size_mass + output_bytes_mass + sigop_mass
+}
+
+/// Calculates the KIP-9 storage mass `C * (sum(1 / out) - N / mean(in))`, clamped at zero, where
+/// `N` is the number of inputs. Splitting value into many small outputs grows the harmonic sum
+/// far beyond what the inputs release, while consolidating them costs nothing. Zero-value
+/// outputs are charged as if they held a single sompi.
+pub fn storage_mass(inputs: &[u64], outputs: &[u64]) -> u64 {
+    let c = STORAGE_MASS_PARAMETER as u128;
+    let harmonic_outputs: u128 = outputs.iter().map(|&value| c / value.max(1) as u128).sum();
+
+    let total_inputs: u128 = inputs.iter().map(|&value| value as u128).sum();
+    let released = match inputs.len() as u128 {
+        0 => 0,
+        count => count * (c / (total_inputs / count).max(1)),
+    };
+    harmonic_outputs.saturating_sub(released).min(u64::MAX as u128) as u64
+}
+
+/// Calculates the overall mass of a transaction spending outputs worth `input_values`: the larger
+/// of its compute mass and its storage mass. Coinbase transactions spend nothing and carry no
+/// storage mass.
+pub fn overall_transaction_mass(tx: &Transaction, input_values: &[u64]) -> u64 {
+    let compute_mass = transaction_mass(tx);
+    if tx.is_coinbase() {
+        return compute_mass;
+    }
+    let output_values: Vec<u64> = tx.outputs.iter().map(|output| output.value).collect();
+    compute_mass.max(storage_mass(input_values, &output_values))
+}
+
+/// Validates that a transaction's mass doesn't exceed the network's `max_tx_mass`.
+pub fn validate_tx_mass(tx: &Transaction, params: &Params) -> ConsensusResult<()> {
+    let mass = tx.mass();
+    if mass > params.max_tx_mass {
+        return Err(crate::errors::ConsensusError::TransactionValidation {
+            msg: format!("Transaction mass {} exceeds the maximum of {}", mass, params.max_tx_mass),
+        });
+    }
+    Ok(())
+}
+
 /// Calculates the mass of a block based on its transactions.
 pub fn calculate_block_mass(transactions: &[crate::tx::Transaction]) -> BlockMass {
     let mut mass = 0;
@@ -36,11 +100,85 @@ pub fn validate_block_mass(mass: BlockMass) -> ConsensusResult<()> {
 mod tests {
     use super::*;
 
+    use crate::tx::{TxInput, TxOutput};
+
+    fn tx_with_scripts(script_sig_len: usize, script_pubkey_len: usize) -> Transaction {
+        let input = TxInput { prev_tx_hash: crate::Hash::from_le_u64([1, 0, 0, 0]), index: 0, script_sig: vec![0; script_sig_len], sequence: 0 };
+        Transaction::new(1, vec![input], vec![TxOutput { value: 1, script_pubkey: vec![0; script_pubkey_len] }], 0)
+    }
+
     #[test]
     fn test_calculate_block_mass() {
-        let tx = crate::tx::Transaction::new(1, vec![], vec![], 0);
-        let mass = calculate_block_mass(&[tx]);
-        assert_eq!(mass, 100);
+        let tx = Transaction::new(1, vec![], vec![], 0);
+        let mass = calculate_block_mass(std::slice::from_ref(&tx));
+        assert_eq!(mass, tx.estimated_serialized_size());
+
+        let txs = [tx_with_scripts(1, 1), tx_with_scripts(2, 2)];
+        assert_eq!(calculate_block_mass(&txs), txs[0].mass() + txs[1].mass());
+    }
+
+    #[test]
+    fn test_transaction_mass_scales_with_scripts() {
+        let tiny = tx_with_scripts(0, 0);
+        let large_sig = tx_with_scripts(1000, 0);
+        let large_pubkey = tx_with_scripts(0, 1000);
+
+        assert_eq!(large_sig.mass() - tiny.mass(), 1000 * MASS_PER_TX_BYTE);
+        assert_eq!(large_pubkey.mass() - tiny.mass(), 1000 * (MASS_PER_TX_BYTE + MASS_PER_OUTPUT_STORAGE_BYTE));
+        assert!(tx_with_scripts(0, 2000).mass() > large_pubkey.mass());
+    }
+
+    #[test]
+    fn test_transaction_mass_counts_sigops() {
+        let plain = tx_with_scripts(0, 1);
+        let mut checksig = plain.clone();
+        checksig.outputs[0].script_pubkey = vec![crate::tx::opcodes::OP_CHECKSIG];
+        assert_eq!(checksig.mass() - plain.mass(), MASS_PER_SIG_OP);
+    }
+
+    #[test]
+    fn test_storage_mass_split_and_consolidate() {
+        const JIO: u64 = 100_000_000;
+
+        // One 100 JIO output split into 100 outputs of 0.01 JIO
+        let split = storage_mass(&[100 * JIO], &[JIO / 100; 100]);
+        assert_eq!(split, 100 * (STORAGE_MASS_PARAMETER / (JIO / 100)) - STORAGE_MASS_PARAMETER / (100 * JIO));
+        assert!(split > Params::default().max_tx_mass);
+
+        // 100 outputs of 1 JIO consolidated into one
+        assert_eq!(storage_mass(&[JIO; 100], &[100 * JIO]), 0);
+
+        // A plain payment with change stays small
+        assert!(storage_mass(&[10 * JIO], &[6 * JIO, 4 * JIO]) < 10_000);
+        assert_eq!(storage_mass(&[JIO], &[0]), STORAGE_MASS_PARAMETER - STORAGE_MASS_PARAMETER / JIO);
+    }
+
+    #[test]
+    fn test_overall_transaction_mass() {
+        let mut split = tx_with_scripts(0, 0);
+        split.outputs = vec![TxOutput { value: 1000, script_pubkey: vec![] }; 10];
+        assert_eq!(overall_transaction_mass(&split, &[10_000]), storage_mass(&[10_000], &[1000; 10]));
+        assert!(overall_transaction_mass(&split, &[10_000]) > split.mass());
+
+        let mut consolidation = tx_with_scripts(0, 0);
+        consolidation.outputs[0].value = 10_000;
+        assert_eq!(overall_transaction_mass(&consolidation, &[1000; 10]), consolidation.mass());
+
+        let coinbase = crate::coinbase::create_coinbase_transaction(1, vec![]);
+        assert_eq!(overall_transaction_mass(&coinbase, &[]), coinbase.mass());
+    }
+
+    #[test]
+    fn test_validate_tx_mass() {
+        let params = Params::default();
+        assert!(validate_tx_mass(&tx_with_scripts(100, 100), &params).is_ok());
+
+        let oversized = tx_with_scripts(0, params.max_tx_mass as usize / MASS_PER_OUTPUT_STORAGE_BYTE as usize);
+        assert!(oversized.mass() > params.max_tx_mass);
+        assert!(matches!(
+            validate_tx_mass(&oversized, &params),
+            Err(crate::errors::ConsensusError::TransactionValidation { .. })
+        ));
     }
 
     #[test]
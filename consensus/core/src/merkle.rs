@@ -1,6 +1,7 @@
 //! Merkle tree implementation for consensus.
 
 use crate::{hashing, Hash, errors::ConsensusResult};
+use rayon::prelude::*;
 
 /// Merkle tree node.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -9,59 +10,184 @@ pub enum MerkleNode {
     Internal(Hash, Box<MerkleNode>, Box<MerkleNode>),
 }
 
+impl MerkleNode {
+    /// Gets the hash of the node.
+    pub fn hash(&self) -> Hash {
+        match self {
+            MerkleNode::Leaf(h) => *h,
+            MerkleNode::Internal(h, _, _) => *h,
+        }
+    }
+}
+
+/// Merkle inclusion proof: sibling hashes from leaf to root, each flagged `true` when
+/// the sibling is the left child, plus the leaf's index and the leaf count committed to by the root.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MerkleProof {
+    pub path: Vec<(Hash, bool)>,
+    pub index: u64,
+    pub leaf_count: u64,
+}
+
 /// Merkle tree structure.
+///
+/// The tree is split at midpoints rather than padded (there is no Bitcoin-style duplication of an
+/// odd last node), so its shape is fully determined by the
+/// number of leaves. The root commits to that leaf count on top of the top node hash, so a list
+/// reshaped around an internal node (or padded as in the Bitcoin duplicate-leaf CVE-2012-2459)
+/// doesn't share a root with the original. Proofs get the same guarantee by checking the path
+/// shape against the leaf index and count in `verify_proof`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MerkleTree {
     root: MerkleNode,
+    leaf_count: usize,
 }
 
 impl MerkleTree {
     /// Builds a Merkle tree from transaction hashes.
     pub fn from_tx_hashes(tx_hashes: &[Hash]) -> ConsensusResult<Self> {
         if tx_hashes.is_empty() {
-            return Ok(Self { root: MerkleNode::Leaf(Hash::default()) });
+            return Ok(Self { root: MerkleNode::Leaf(Hash::default()), leaf_count: 0 });
         }
 
-        let root = Self::build_tree(tx_hashes, 0, tx_hashes.len() - 1)?;
-        Ok(Self { root })
+        let root = Self::build_tree(tx_hashes);
+        Ok(Self { root, leaf_count: tx_hashes.len() })
     }
 
-    /// Computes the Merkle root hash.
+    /// Computes the Merkle root hash, committing to the leaf count. An empty tree has the default hash.
     pub fn root(&self) -> Hash {
-        match &self.root {
-            MerkleNode::Leaf(h) => *h,
-            MerkleNode::Internal(h, _, _) => *h,
+        if self.leaf_count == 0 {
+            return Hash::default();
         }
+        Self::commit_leaf_count(self.leaf_count as u64, &self.root.hash())
     }
 
-    fn build_tree(tx_hashes: &[Hash], start: usize, end: usize) -> ConsensusResult<MerkleNode> {
-        if start == end {
-            return Ok(MerkleNode::Leaf(tx_hashes[start]));
+    /// Gets the number of leaves (transactions) in the tree.
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    fn commit_leaf_count(leaf_count: u64, node_hash: &Hash) -> Hash {
+        let mut data = leaf_count.to_le_bytes().to_vec();
+        data.extend_from_slice(node_hash.as_bytes());
+        hashing::double_sha256(&data)
+    }
+
+    /// Builds the tree over a non-empty list of leaves without recursion. Inclusive ranges are
+    /// split at their midpoint, the left half taking the extra leaf, and completed subtrees are
+    /// joined in post-order on an explicit stack.
+    ///
+    /// This is deliberately not a level-by-level build. Pairing adjacent nodes per level only
+    /// matches the midpoint split when the leaf count is a power of two (with 5 leaves it yields
+    /// `((0,1),(2,3)),4` instead of `((0,1),2),(3,4)`), so it would change the root of every other
+    /// block and, with it, consensus. The nodes are boxed because `MerkleNode` owns its children.
+    fn build_tree(tx_hashes: &[Hash]) -> MerkleNode {
+        enum Task {
+            Split(usize, usize),
+            Join,
         }
 
-        let mid = start + (end - start) / 2;
-        let left = Self::build_tree(tx_hashes, start, mid)?;
-        let right = Self::build_tree(tx_hashes, mid + 1, end)?;
+        let mut tasks = vec![Task::Split(0, tx_hashes.len() - 1)];
+        let mut nodes: Vec<MerkleNode> = Vec::with_capacity(usize::BITS as usize);
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Split(start, end) if start == end => nodes.push(MerkleNode::Leaf(tx_hashes[start])),
+                Task::Split(start, end) => {
+                    let mid = start + (end - start) / 2;
+                    tasks.extend([Task::Join, Task::Split(mid + 1, end), Task::Split(start, mid)]);
+                }
+                Task::Join => {
+                    let right = nodes.pop().expect("a join follows both of its subtrees");
+                    let left = nodes.pop().expect("a join follows both of its subtrees");
+                    let node_hash = Self::combine(&left.hash(), &right.hash());
+                    nodes.push(MerkleNode::Internal(node_hash, Box::new(left), Box::new(right)));
+                }
+            }
+        }
+        nodes.pop().expect("a non-empty list builds a root")
+    }
 
-        let left_hash = match &left {
-            MerkleNode::Leaf(h) => *h,
-            MerkleNode::Internal(h, _, _) => *h,
-        };
-        let right_hash = match &right {
-            MerkleNode::Leaf(h) => *h,
-            MerkleNode::Internal(h, _, _) => *h,
-        };
+    fn combine(left: &Hash, right: &Hash) -> Hash {
+        let combined = left.as_bytes().iter().chain(right.as_bytes().iter()).cloned().collect::<Vec<u8>>();
+        hashing::double_sha256(&combined)
+    }
+
+    /// Builds an inclusion proof for `tx_hash`, or `None` if it isn't a leaf of the tree.
+    pub fn proof(&self, tx_hash: Hash) -> Option<MerkleProof> {
+        if self.leaf_count == 0 {
+            return None;
+        }
+        let mut path = Vec::new();
+        let index = Self::collect_path(&self.root, &tx_hash, 0, self.leaf_count - 1, &mut path)?;
+        Some(MerkleProof { path, index: index as u64, leaf_count: self.leaf_count as u64 })
+    }
+
+    /// Appends the siblings on the way back up from the leaf, so the path ends at the root, and
+    /// returns the leaf's index. `start..=end` is the range of leaves under `node`.
+    fn collect_path(node: &MerkleNode, tx_hash: &Hash, start: usize, end: usize, path: &mut Vec<(Hash, bool)>) -> Option<usize> {
+        match node {
+            MerkleNode::Leaf(h) => (h == tx_hash).then_some(start),
+            MerkleNode::Internal(_, left, right) => {
+                let mid = start + (end - start) / 2;
+                if let Some(index) = Self::collect_path(left, tx_hash, start, mid, path) {
+                    path.push((right.hash(), false));
+                    Some(index)
+                } else if let Some(index) = Self::collect_path(right, tx_hash, mid + 1, end, path) {
+                    path.push((left.hash(), true));
+                    Some(index)
+                } else {
+                    None
+                }
+            }
+        }
+    }
 
-        let combined = left_hash.as_bytes().iter().chain(right_hash.as_bytes().iter()).cloned().collect::<Vec<u8>>();
-        let node_hash = hashing::double_sha256(&combined);
+    /// Sibling-is-left flags from leaf to root for the leaf at `index`, following the same midpoint
+    /// split as `build_tree`, or `None` if the index is out of range.
+    fn path_directions(index: u64, leaf_count: u64) -> Option<Vec<bool>> {
+        if index >= leaf_count {
+            return None;
+        }
+        let (mut start, mut end) = (0, leaf_count - 1);
+        let mut directions = Vec::new();
+        while start < end {
+            let mid = start + (end - start) / 2;
+            if index <= mid {
+                directions.push(false);
+                end = mid;
+            } else {
+                directions.push(true);
+                start = mid + 1;
+            }
+        }
+        directions.reverse();
+        Some(directions)
+    }
 
-        Ok(MerkleNode::Internal(node_hash, Box::new(left), Box::new(right)))
+    /// Verifies a Merkle proof by recomputing the root from `tx_hash` and the proof path. The path
+    /// must have exactly the depth and left/right flags of leaf `proof.index` in a tree of
+    /// `proof.leaf_count` leaves, so an internal node can't be proven as a leaf.
+    pub fn verify_proof(tx_hash: Hash, root: Hash, proof: &MerkleProof) -> bool {
+        let Some(directions) = Self::path_directions(proof.index, proof.leaf_count) else {
+            return false;
+        };
+        if directions.len() != proof.path.len() || directions.iter().zip(&proof.path).any(|(expected, (_, flag))| expected != flag) {
+            return false;
+        }
+        let computed = proof.path.iter().fold(tx_hash, |current, (sibling, sibling_is_left)| {
+            if *sibling_is_left {
+                Self::combine(sibling, &current)
+            } else {
+                Self::combine(&current, sibling)
+            }
+        });
+        Self::commit_leaf_count(proof.leaf_count, &computed) == root
     }
 
-    /// Verifies a Merkle proof (placeholder for full proof verification).
-    pub fn verify_proof(_tx_hash: Hash, _root: Hash, _proof: &[Hash]) -> bool {
-        // Placeholder; implement actual proof verification
-        true
+    /// Verifies each `(tx_hash, proof)` pair against `root` in parallel, returning the result
+    /// for every item at its index.
+    pub fn verify_proofs(root: Hash, items: &[(Hash, MerkleProof)]) -> Vec<bool> {
+        items.par_iter().map(|(tx_hash, proof)| Self::verify_proof(*tx_hash, root, proof)).collect()
     }
 }
 
@@ -71,7 +197,7 @@ pub fn calculate_merkle_root(tx_hashes: &[Hash]) -> Hash {
         return Hash::default();
     }
 
-    let tree = MerkleTree::from_tx_hashes(tx_hashes).unwrap_or_else(|_| MerkleTree { root: MerkleNode::Leaf(Hash::default()) });
+    let tree = MerkleTree::from_tx_hashes(tx_hashes).unwrap_or_else(|_| MerkleTree { root: MerkleNode::Leaf(Hash::default()), leaf_count: 0 });
     tree.root()
 }
 
@@ -80,11 +206,18 @@ mod tests {
     use super::*;
     use crate::Hash;
 
+    fn committed_root(leaf_count: u64, node_hash: Hash) -> Hash {
+        let mut data = leaf_count.to_le_bytes().to_vec();
+        data.extend_from_slice(node_hash.as_bytes());
+        hashing::double_sha256(&data)
+    }
+
     #[test]
     fn test_merkle_tree_single_tx() {
         let tx_hash = Hash::from_slice(b"single_tx");
         let tree = MerkleTree::from_tx_hashes(&[tx_hash]).unwrap();
-        assert_eq!(tree.root(), tx_hash);
+        assert_eq!(tree.root(), committed_root(1, tx_hash));
+        assert_eq!(tree.leaf_count(), 1);
     }
 
     #[test]
@@ -94,20 +227,139 @@ mod tests {
         let tree = MerkleTree::from_tx_hashes(&[tx1, tx2]).unwrap();
 
         let combined = tx1.as_bytes().iter().chain(tx2.as_bytes().iter()).cloned().collect::<Vec<u8>>();
-        let expected_root = hashing::double_sha256(&combined);
+        let expected_root = committed_root(2, hashing::double_sha256(&combined));
         assert_eq!(tree.root(), expected_root);
+        assert_eq!(tree.leaf_count(), 2);
     }
 
     #[test]
     fn test_merkle_tree_empty() {
         let tree = MerkleTree::from_tx_hashes(&[]).unwrap();
         assert_eq!(tree.root(), Hash::default());
+        assert_eq!(tree.leaf_count(), 0);
+        assert_eq!(tree.proof(Hash::default()), None);
     }
 
     #[test]
     fn test_calculate_merkle_root() {
         let tx_hashes = vec![Hash::from_slice(b"tx1")];
         let root = calculate_merkle_root(&tx_hashes);
-        assert_eq!(root, tx_hashes[0]);
+        assert_eq!(root, committed_root(1, tx_hashes[0]));
+    }
+
+    #[test]
+    fn test_merkle_proof() {
+        let tx_hashes: Vec<Hash> = (1..=5).map(|i| Hash::from_le_u64([i, 0, 0, 0])).collect();
+        let tree = MerkleTree::from_tx_hashes(&tx_hashes).unwrap();
+        for tx_hash in &tx_hashes {
+            let proof = tree.proof(*tx_hash).unwrap();
+            assert!(MerkleTree::verify_proof(*tx_hash, tree.root(), &proof));
+            assert!(!MerkleTree::verify_proof(Hash::from_slice(b"other"), tree.root(), &proof));
+        }
+
+        let single = MerkleTree::from_tx_hashes(&tx_hashes[..1]).unwrap();
+        let proof = single.proof(tx_hashes[0]).unwrap();
+        assert!(proof.path.is_empty());
+        assert!(MerkleTree::verify_proof(tx_hashes[0], single.root(), &proof));
+    }
+
+    #[test]
+    fn test_merkle_proof_tampered_sibling() {
+        let tx_hashes: Vec<Hash> = (1..=4).map(|i| Hash::from_le_u64([i, 0, 0, 0])).collect();
+        let tree = MerkleTree::from_tx_hashes(&tx_hashes).unwrap();
+        let mut proof = tree.proof(tx_hashes[2]).unwrap();
+        proof.path[0].0 = Hash::from_slice(b"tampered");
+        assert!(!MerkleTree::verify_proof(tx_hashes[2], tree.root(), &proof));
+
+        let mut proof = tree.proof(tx_hashes[2]).unwrap();
+        proof.path[1].1 = !proof.path[1].1;
+        assert!(!MerkleTree::verify_proof(tx_hashes[2], tree.root(), &proof));
+    }
+
+    #[test]
+    fn test_verify_proofs_batch() {
+        let tx_hashes: Vec<Hash> = (1..=9).map(|i| Hash::from_le_u64([i, 0, 0, 0])).collect();
+        let tree = MerkleTree::from_tx_hashes(&tx_hashes).unwrap();
+        let mut items: Vec<(Hash, MerkleProof)> = tx_hashes.iter().map(|tx_hash| (*tx_hash, tree.proof(*tx_hash).unwrap())).collect();
+        items[4].1.path[0].0 = Hash::from_slice(b"tampered");
+
+        let results = MerkleTree::verify_proofs(tree.root(), &items);
+        assert_eq!(results.len(), items.len());
+        for (index, valid) in results.into_iter().enumerate() {
+            assert_eq!(valid, index != 4, "item {}", index);
+        }
+        assert_eq!(MerkleTree::verify_proofs(Hash::from_slice(b"other root"), &items[..2]), vec![false, false]);
+        assert!(MerkleTree::verify_proofs(tree.root(), &[]).is_empty());
+    }
+
+    #[test]
+    fn test_merkle_proof_absent_tx() {
+        let tx_hashes: Vec<Hash> = (1..=3).map(|i| Hash::from_le_u64([i, 0, 0, 0])).collect();
+        let tree = MerkleTree::from_tx_hashes(&tx_hashes).unwrap();
+        assert_eq!(tree.proof(Hash::from_le_u64([9, 0, 0, 0])), None);
+    }
+
+    /// The recursive midpoint-split build the iterative one replaced.
+    fn recursive_node_hash(tx_hashes: &[Hash]) -> Hash {
+        if tx_hashes.len() == 1 {
+            return tx_hashes[0];
+        }
+        let (left, right) = tx_hashes.split_at(tx_hashes.len().div_ceil(2));
+        MerkleTree::combine(&recursive_node_hash(left), &recursive_node_hash(right))
+    }
+
+    #[test]
+    fn test_iterative_build_matches_recursive() {
+        let tx_hashes: Vec<Hash> = (0..crate::constants::MAX_TRANSACTIONS_PER_BLOCK as u64).map(|i| Hash::from_le_u64([i, 1, 2, 3])).collect();
+        for len in (1..=17).chain([tx_hashes.len()]) {
+            let tree = MerkleTree::from_tx_hashes(&tx_hashes[..len]).unwrap();
+            assert_eq!(tree.root(), committed_root(len as u64, recursive_node_hash(&tx_hashes[..len])));
+        }
+
+        let tree = MerkleTree::from_tx_hashes(&tx_hashes).unwrap();
+        let proof = tree.proof(tx_hashes[7777]).unwrap();
+        assert!(proof.path.len() <= 14);
+        assert!(MerkleTree::verify_proof(tx_hashes[7777], tree.root(), &proof));
+    }
+
+    #[test]
+    fn test_merkle_root_forgery_regression() {
+        let (x, y, z) = (Hash::from_slice(b"x"), Hash::from_slice(b"y"), Hash::from_slice(b"z"));
+
+        // An internal node passed off as a single leaf
+        let pair = MerkleTree::from_tx_hashes(&[x, y]).unwrap();
+        let inner = MerkleTree::combine(&x, &y);
+        assert_ne!(pair.root(), MerkleTree::from_tx_hashes(&[inner]).unwrap().root());
+
+        // Duplicated trailing leaves
+        let odd = MerkleTree::from_tx_hashes(&[x, y, z]).unwrap();
+        assert_ne!(odd.root(), MerkleTree::from_tx_hashes(&[x, y, z, z]).unwrap().root());
+
+        // A proof can't be replayed under a different leaf count
+        let mut proof = pair.proof(x).unwrap();
+        proof.leaf_count = 3;
+        assert!(!MerkleTree::verify_proof(x, pair.root(), &proof));
+    }
+
+    #[test]
+    fn test_merkle_proof_internal_node_as_leaf() {
+        let h: Vec<Hash> = (1..=4).map(|i| Hash::from_le_u64([i, 0, 0, 0])).collect();
+        let tree = MerkleTree::from_tx_hashes(&h).unwrap();
+        let inner = MerkleTree::combine(&h[0], &h[1]);
+        for index in 0..4 {
+            let forged = MerkleProof { path: vec![(MerkleTree::combine(&h[2], &h[3]), false)], index, leaf_count: 4 };
+            assert!(!MerkleTree::verify_proof(inner, tree.root(), &forged), "index {}", index);
+        }
+
+        // The real proofs carry their leaf index, and moving a proof to another index fails
+        for (index, tx_hash) in h.iter().enumerate() {
+            let mut proof = tree.proof(*tx_hash).unwrap();
+            assert_eq!(proof.index, index as u64);
+            proof.index = (proof.index + 1) % 4;
+            assert!(!MerkleTree::verify_proof(*tx_hash, tree.root(), &proof));
+        }
+        let mut proof = tree.proof(h[0]).unwrap();
+        proof.index = 4;
+        assert!(!MerkleTree::verify_proof(h[0], tree.root(), &proof));
     }
 }
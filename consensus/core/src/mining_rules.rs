@@ -1,9 +1,24 @@
 //! Mining rules for block validation.
 
-use crate::{block::Block, errors::ConsensusResult, hashing};
+use crate::{
+    block::Block,
+    config::params::Params,
+    constants::{MAX_BLOCK_VERSION, MAX_SIGOPS_PER_BLOCK, MIN_BLOCK_VERSION},
+    daa::{self, DaaWindow},
+    errors::ConsensusResult,
+    hashing,
+    header::Header,
+};
+use jio_math::Uint256;
+
+/// Sign bit of the compact mantissa; Bitcoin decodes such bits as a negative target.
+const COMPACT_SIGN_BIT: u32 = 0x0080_0000;
 
 /// Validates mining rules for a block.
 pub fn validate_mining_rules(block: &Block) -> ConsensusResult<()> {
+    validate_header(&block.header)?;
+    validate_block_sigops(block)?;
+
     if !check_proof_of_work(block) {
         return Err(crate::errors::ConsensusError::MiningRuleViolation {
             msg: "Proof of work not satisfied".to_string(),
@@ -16,6 +31,56 @@ pub fn validate_mining_rules(block: &Block) -> ConsensusResult<()> {
     Ok(())
 }
 
+/// Validates the header fields that don't depend on the DAG: the version must lie within
+/// `MIN_BLOCK_VERSION..=MAX_BLOCK_VERSION` and the bits must be usable.
+pub fn validate_header(header: &Header) -> ConsensusResult<()> {
+    if !(MIN_BLOCK_VERSION..=MAX_BLOCK_VERSION).contains(&header.version) {
+        return Err(crate::errors::ConsensusError::InvalidBlockHeader {
+            msg: format!("Block version {} is outside {}..={}", header.version, MIN_BLOCK_VERSION, MAX_BLOCK_VERSION),
+        });
+    }
+    validate_bits(header.bits)
+}
+
+/// Validates that compact `bits` encode a usable target: the sign bit must be clear and the
+/// decoded target must be non-zero.
+pub fn validate_bits(bits: u32) -> ConsensusResult<()> {
+    if bits & COMPACT_SIGN_BIT != 0 {
+        return Err(crate::errors::ConsensusError::NegativeTarget { bits });
+    }
+    if Uint256::from_compact_target_bits(bits) == Uint256::default() {
+        return Err(crate::errors::ConsensusError::ZeroTarget { bits });
+    }
+    Ok(())
+}
+
+/// Formats the target encoded by compact `bits` as 64 big-endian hex digits, for logging.
+pub fn bits_to_target_hex(bits: u32) -> String {
+    Uint256::from_compact_target_bits(bits).as_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Parses 64 big-endian hex digits into a target and encodes it as compact bits, the inverse of
+/// [`bits_to_target_hex`] for canonically encoded bits. Returns `None` for malformed input.
+pub fn target_hex_to_bits(hex: &str) -> Option<u32> {
+    if hex.len() != 64 || !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut bytes = [0u8; 32];
+    for (byte, digits) in bytes.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+    Some(Uint256::from(bytes).to_compact_target_bits())
+}
+
+/// Validates that the block's transactions hold at most `MAX_SIGOPS_PER_BLOCK` signature operations.
+pub fn validate_block_sigops(block: &Block) -> ConsensusResult<()> {
+    let sigops: u64 = block.transactions.iter().map(|tx| tx.sigops() as u64).sum();
+    if sigops > MAX_SIGOPS_PER_BLOCK as u64 {
+        return Err(crate::errors::ConsensusError::TooManySigops { sigops, max_allowed: MAX_SIGOPS_PER_BLOCK as u64 });
+    }
+    Ok(())
+}
+
 /// Validates GhostDAG data for a block.
 pub fn validate_ghostdag_data(block: &Block) -> ConsensusResult<()> {
     // Genesis blocks don't have GhostDAG data
@@ -23,9 +88,7 @@ pub fn validate_ghostdag_data(block: &Block) -> ConsensusResult<()> {
         return Ok(());
     }
 
-    let ghostdag_data = block.ghostdag_data.as_ref().ok_or_else(|| {
-        crate::errors::ConsensusError::MissingGhostDagData
-    })?;
+    let ghostdag_data = block.ghostdag_data.as_ref().ok_or(crate::errors::ConsensusError::MissingGhostDagData { block: block.hash() })?;
 
     // Check that selected parent is in parents
     let parents: std::collections::HashSet<_> = block.header.parents_by_level.iter().flatten().collect();
@@ -39,25 +102,39 @@ pub fn validate_ghostdag_data(block: &Block) -> ConsensusResult<()> {
     Ok(())
 }
 
-/// Checks if a block satisfies the proof of work.
+/// Checks if a block satisfies the proof of work: its header's PoW hash must not exceed the
+/// target encoded by the header bits.
 pub fn check_proof_of_work(block: &Block) -> bool {
-    let hash = block.hash();
-    let target = hashing::target_from_bits(block.header.bits);
     // For genesis blocks with valid bits, always pass
     if block.is_genesis() && block.header.bits != 0 {
         return true;
     }
-    hashing::meets_target(&hash, &target)
+    hashing::meets_target(&block.header.pow_hash(), block.header.target().as_bytes())
+}
+
+/// Computes the compact bits required for the next block on top of `window`,
+/// never easier than the network's pow limit.
+pub fn required_bits_for_next_block(window: &DaaWindow, params: &Params) -> u32 {
+    if !window.is_sufficient() {
+        return params.genesis_bits;
+    }
+    daa::calculate_next_bits(&window.blocks, params.target_time_per_block, window.current_bits, &params.pow_limit)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::daa_score_timestamp::DaaScoreTimestamp;
+
+    fn create_window(count: u64, interval: u64, current_bits: u32) -> DaaWindow {
+        let blocks = (0..count).map(|i| DaaScoreTimestamp::new(i, 1_000_000 + i * interval)).collect();
+        DaaWindow::new(blocks, current_bits)
+    }
 
     #[test]
     fn test_validate_mining_rules() {
         let mut block = crate::block::Block::new(crate::header::Header::new(), vec![]);
-        block.header.bits = 0x7fffff; // Maximum difficulty (easiest) for testing
+        block.header.bits = 0x1f7fffff; // Very easy target for testing
         block.header.nonce = 1;
         // For testing, we'll skip PoW check for genesis blocks
         assert!(validate_mining_rules(&block).is_ok());
@@ -69,12 +146,171 @@ mod tests {
         assert!(validate_mining_rules(&block).is_err());
     }
 
+    #[test]
+    fn test_validate_bits() {
+        assert!(validate_bits(0x1d00ffff).is_ok());
+        assert!(validate_bits(0x1f7fffff).is_ok());
+
+        for bits in [0, 0x1d000000, 0x007fffff] {
+            assert_eq!(validate_bits(bits), Err(crate::errors::ConsensusError::ZeroTarget { bits }));
+        }
+        for bits in [0x1d800001, 0x04923456] {
+            assert_eq!(validate_bits(bits), Err(crate::errors::ConsensusError::NegativeTarget { bits }));
+        }
+    }
+
+    #[test]
+    fn test_target_hex_round_trip() {
+        assert_eq!(bits_to_target_hex(0x1d00ffff), "00000000ffff0000000000000000000000000000000000000000000000000000");
+        assert_eq!(bits_to_target_hex(0x207fffff), "7fffff0000000000000000000000000000000000000000000000000000000000");
+        assert_eq!(bits_to_target_hex(0x03123456), format!("{:064x}", 0x123456));
+        for bits in [0x1d00ffff, 0x1c7fff00, 0x1b0404cb, 0x207fffff, 0x1f7fffff, 0x03123456, 0x0] {
+            let hex = bits_to_target_hex(bits);
+            assert_eq!(hex.len(), 64);
+            assert_eq!(target_hex_to_bits(&hex), Some(bits), "bits {:#010x}", bits);
+            assert_eq!(target_hex_to_bits(&hex.to_uppercase()), Some(bits));
+        }
+
+        assert_eq!(target_hex_to_bits("ffff"), None);
+        assert_eq!(target_hex_to_bits(&"g".repeat(64)), None);
+        assert_eq!(target_hex_to_bits(&format!("+{}", "0".repeat(63))), None);
+        assert_eq!(target_hex_to_bits(&"é".repeat(32)), None);
+    }
+
+    #[test]
+    fn test_validate_header_version() {
+        let mut header = Header::new();
+        header.bits = 0x1d00ffff;
+        for version in MIN_BLOCK_VERSION..=MAX_BLOCK_VERSION {
+            header.version = version;
+            assert!(validate_header(&header).is_ok());
+        }
+        for version in [MIN_BLOCK_VERSION - 1, MAX_BLOCK_VERSION + 1, u16::MAX] {
+            header.version = version;
+            assert!(matches!(validate_header(&header), Err(crate::errors::ConsensusError::InvalidBlockHeader { .. })));
+        }
+
+        let mut block = crate::block::Block::new(header, vec![]);
+        assert!(matches!(validate_mining_rules(&block), Err(crate::errors::ConsensusError::InvalidBlockHeader { .. })));
+        block.header.version = MIN_BLOCK_VERSION;
+        assert!(validate_mining_rules(&block).is_ok());
+    }
+
+    #[test]
+    fn test_validate_mining_rules_rejects_zero_target() {
+        let mut block = crate::block::Block::new(crate::header::Header::new(), vec![]);
+        block.header.bits = 0x1d000000;
+        assert_eq!(validate_mining_rules(&block), Err(crate::errors::ConsensusError::ZeroTarget { bits: 0x1d000000 }));
+    }
+
+    #[test]
+    fn test_validate_block_sigops() {
+        use crate::tx::{opcodes::OP_CHECKSIG, Transaction, TxInput, TxOutput};
+
+        let tx_with_sigops = |sigops: usize| {
+            let input = TxInput { prev_tx_hash: crate::Hash::from_le_u64([1, 0, 0, 0]), index: 0, script_sig: vec![], sequence: 0 };
+            Transaction::new(0, vec![input], vec![TxOutput { value: 1, script_pubkey: vec![OP_CHECKSIG; sigops] }], 0)
+        };
+        let half = MAX_SIGOPS_PER_BLOCK as usize / 2;
+
+        let mut block = crate::block::Block::new(crate::header::Header::new(), vec![tx_with_sigops(half), tx_with_sigops(half)]);
+        assert_eq!(validate_block_sigops(&block), Ok(()));
+
+        block.transactions.push(tx_with_sigops(1));
+        let err = crate::errors::ConsensusError::TooManySigops {
+            sigops: MAX_SIGOPS_PER_BLOCK as u64 + 1,
+            max_allowed: MAX_SIGOPS_PER_BLOCK as u64,
+        };
+        assert_eq!(validate_block_sigops(&block), Err(err.clone()));
+        block.header.bits = 0x1f7fffff;
+        assert_eq!(validate_mining_rules(&block), Err(err));
+    }
+
     #[test]
     fn test_check_proof_of_work() {
         let mut block = crate::block::Block::new(crate::header::Header::new(), vec![]);
-        block.header.bits = 0x7fffff; // Maximum difficulty (easiest) for testing
-        block.header.nonce = 1;
-        // For testing, we'll assume PoW passes
+        block.header.bits = 0x7fffff;
+        // Genesis is exempt
         assert!(check_proof_of_work(&block));
+
+        // The target 0x7fffff << 232 accepts about half of all hashes: those below 2^255
+        block.header.parents_by_level = vec![vec![crate::Hash::from_le_u64([1, 0, 0, 0])]];
+        block.header.bits = 0x207fffff;
+        let below_half = |hash: crate::Hash| hash.as_bytes()[31] < 0x80;
+        let mut block_hash_disagrees = false;
+        for nonce in 0..64 {
+            block.header.nonce = nonce;
+            assert_eq!(check_proof_of_work(&block), below_half(block.header.pow_hash()));
+            block_hash_disagrees |= below_half(block.hash()) != below_half(block.header.pow_hash());
+        }
+        // The block hash alone would have decided differently for some nonce
+        assert!(block_hash_disagrees);
+    }
+
+    #[test]
+    fn test_required_bits_fresh_chain() {
+        let params = Params::default();
+        let window = DaaWindow::default();
+        assert_eq!(required_bits_for_next_block(&window, &params), params.genesis_bits);
+    }
+
+    #[test]
+    fn test_required_bits_fast_blocks() {
+        let params = Params::default();
+        let window = create_window(10, params.target_time_per_block / 100, params.genesis_bits);
+        let bits = required_bits_for_next_block(&window, &params);
+        // Blocks 100x too fast only tighten the target by the 4x retarget cap
+        assert_eq!(bits, 0x1c3fff00);
+        assert!(Uint256::from_compact_target_bits(bits) < Uint256::from_compact_target_bits(params.genesis_bits));
+    }
+
+    #[test]
+    fn test_required_bits_millisecond_block_times() {
+        let params = Params::default();
+        let current_bits = 0x1c7fff00;
+        let series = |intervals: &[u64]| {
+            let mut timestamp = params.genesis_timestamp;
+            let blocks = std::iter::once(0)
+                .chain(intervals.iter().copied())
+                .enumerate()
+                .map(|(daa_score, interval)| {
+                    timestamp += interval;
+                    DaaScoreTimestamp::new(daa_score as u64, timestamp)
+                })
+                .collect();
+            DaaWindow::new(blocks, current_bits)
+        };
+
+        // Jittery block times averaging exactly the 1000 ms target keep the difficulty
+        let on_target = series(&[900, 1100, 1050, 950, 800, 1200, 1000, 1000]);
+        assert_eq!(params.target_time_per_block, 1000);
+        assert_eq!(required_bits_for_next_block(&on_target, &params), current_bits);
+
+        // Blocks 25% slow ease the target by 25%: 0x7fff00 * 1.25 = 0x9ffec0, truncated to 0x9ffe00
+        let slow = series(&[1250; 8]);
+        assert_eq!(required_bits_for_next_block(&slow, &params), 0x1d009ffe);
+
+        // The same series mistakenly recorded in seconds only hits the 4x retarget cap
+        let in_seconds = series(&[1; 8]);
+        assert_eq!(required_bits_for_next_block(&in_seconds, &params), 0x1c1fffc0);
+    }
+
+    #[test]
+    fn test_required_bits_never_easier_than_pow_limit() {
+        for network in crate::network::NetworkId::ALL {
+            let params = Params::for_network(network);
+            for (interval, current_bits) in [(100, 0x1c100000), (10_000, 0x1c100000), (10_000, params.max_difficulty_bits())] {
+                let window = create_window(10, interval, current_bits);
+                let bits = required_bits_for_next_block(&window, &params);
+                assert!(Uint256::from_compact_target_bits(bits) <= params.pow_limit);
+            }
+        }
+    }
+
+    #[test]
+    fn test_required_bits_slow_blocks_clamped_to_genesis() {
+        let params = Params::default();
+        let window = create_window(10, params.target_time_per_block * 10, params.genesis_bits);
+        assert_eq!(required_bits_for_next_block(&window, &params), params.genesis_bits);
     }
 }
@@ -1,38 +1,218 @@
 //! MuHash for efficient UTXO set hashing.
+//!
+//! Implements the MuHash3072 multiset hash: each element is expanded to a 3072-bit number and the
+//! set hash is the product of those numbers modulo the prime `2^3072 - 1103717`. Removals multiply
+//! a separate denominator so only `finalize` has to compute a modular inverse.
 
-use crate::Hash;
+use crate::{hashing, tx::TxOutput, utxo::{utxo_collection::utxo_commitment, OutPoint}, Hash};
+
+/// Number of 64-bit limbs in a 3072-bit number.
+const LIMBS: usize = 48;
+
+/// Size of a serialized 3072-bit number in bytes.
+const NUM_BYTES: usize = LIMBS * 8;
+
+/// The modulus is `2^3072 - MODULUS_DIFF`, the largest 3072-bit safe prime.
+const MODULUS_DIFF: u64 = 1_103_717;
+
+/// A 3072-bit number in little-endian limbs, kept reduced below the modulus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Num3072([u64; LIMBS]);
+
+impl Num3072 {
+    const ONE: Self = {
+        let mut limbs = [0u64; LIMBS];
+        limbs[0] = 1;
+        Self(limbs)
+    };
+
+    /// Maps an element to a number by expanding its SHA256 digest with ChaCha20.
+    fn from_element(element: &Hash) -> Self {
+        let mut bytes = [0u8; NUM_BYTES];
+        chacha20_keystream(hashing::hash_data(element.as_bytes()).as_bytes(), &mut bytes);
+        let mut num = Self::from_le_bytes(&bytes);
+        if num.is_overflow() {
+            num.full_reduce();
+        }
+        num
+    }
+
+    fn from_le_bytes(bytes: &[u8; NUM_BYTES]) -> Self {
+        let mut limbs = [0u64; LIMBS];
+        for (limb, chunk) in limbs.iter_mut().zip(bytes.chunks_exact(8)) {
+            *limb = u64::from_le_bytes(chunk.try_into().unwrap());
+        }
+        Self(limbs)
+    }
+
+    fn to_le_bytes(self) -> [u8; NUM_BYTES] {
+        let mut bytes = [0u8; NUM_BYTES];
+        for (chunk, limb) in bytes.chunks_exact_mut(8).zip(self.0) {
+            chunk.copy_from_slice(&limb.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Checks whether the value is at least the modulus.
+    fn is_overflow(&self) -> bool {
+        self.0[0] > u64::MAX - MODULUS_DIFF && self.0[1..].iter().all(|&limb| limb == u64::MAX)
+    }
+
+    /// Subtracts the modulus, i.e. adds `MODULUS_DIFF` and drops the carry out of 2^3072.
+    fn full_reduce(&mut self) {
+        self.add_small(MODULUS_DIFF as u128);
+    }
+
+    /// Adds a small value, returning the carry out of the top limb.
+    fn add_small(&mut self, value: u128) -> u128 {
+        let mut carry = value;
+        for limb in self.0.iter_mut() {
+            if carry == 0 {
+                break;
+            }
+            let sum = *limb as u128 + carry;
+            *limb = sum as u64;
+            carry = sum >> 64;
+        }
+        carry
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        let mut wide = [0u64; 2 * LIMBS];
+        for (i, &a) in self.0.iter().enumerate() {
+            let mut carry = 0u128;
+            for (j, &b) in other.0.iter().enumerate() {
+                let t = wide[i + j] as u128 + a as u128 * b as u128 + carry;
+                wide[i + j] = t as u64;
+                carry = t >> 64;
+            }
+            wide[i + LIMBS] = carry as u64;
+        }
+
+        // Fold the high half back in, using 2^3072 ≡ MODULUS_DIFF
+        let mut result = Self([0u64; LIMBS]);
+        let mut carry = 0u128;
+        for i in 0..LIMBS {
+            let t = wide[i] as u128 + wide[i + LIMBS] as u128 * MODULUS_DIFF as u128 + carry;
+            result.0[i] = t as u64;
+            carry = t >> 64;
+        }
+        while carry != 0 {
+            carry = result.add_small(carry * MODULUS_DIFF as u128);
+        }
+        if result.is_overflow() {
+            result.full_reduce();
+        }
+        result
+    }
+
+    /// Computes the modular inverse via Fermat's little theorem, `self^(p - 2)`.
+    fn inverse(&self) -> Self {
+        let mut exponent = [u64::MAX; LIMBS];
+        exponent[0] = u64::MAX - MODULUS_DIFF - 1;
+
+        let mut result = Self::ONE;
+        for limb in exponent.iter().rev() {
+            for bit in (0..64).rev() {
+                result = result.mul(&result);
+                if (limb >> bit) & 1 == 1 {
+                    result = result.mul(self);
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Fills `out` with the ChaCha20 keystream for `key`, a zero nonce and a block counter starting at zero.
+fn chacha20_keystream(key: &[u8; 32], out: &mut [u8]) {
+    let mut state = [0u32; 16];
+    state[..4].copy_from_slice(&[0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574]);
+    for (word, chunk) in state[4..12].iter_mut().zip(key.chunks_exact(4)) {
+        *word = u32::from_le_bytes(chunk.try_into().unwrap());
+    }
+
+    for (counter, block) in out.chunks_mut(64).enumerate() {
+        state[12] = counter as u32;
+        let mut working = state;
+        for _ in 0..10 {
+            quarter_round(&mut working, 0, 4, 8, 12);
+            quarter_round(&mut working, 1, 5, 9, 13);
+            quarter_round(&mut working, 2, 6, 10, 14);
+            quarter_round(&mut working, 3, 7, 11, 15);
+            quarter_round(&mut working, 0, 5, 10, 15);
+            quarter_round(&mut working, 1, 6, 11, 12);
+            quarter_round(&mut working, 2, 7, 8, 13);
+            quarter_round(&mut working, 3, 4, 9, 14);
+        }
+        for (i, bytes) in block.chunks_mut(4).enumerate() {
+            let word = working[i].wrapping_add(state[i]).to_le_bytes();
+            bytes.copy_from_slice(&word[..bytes.len()]);
+        }
+    }
+}
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
 
 /// MuHash state for incremental hashing.
 #[derive(Debug, Clone)]
 pub struct MuHash {
-    state: Hash,
+    numerator: Num3072,
+    denominator: Num3072,
 }
 
 impl MuHash {
     /// Creates a new MuHash instance.
     pub fn new() -> Self {
-        Self { state: Hash::default() }
+        Self { numerator: Num3072::ONE, denominator: Num3072::ONE }
     }
 
     /// Adds an element to the hash.
     pub fn add(&mut self, element: &Hash) {
-        // Placeholder: XOR for simplicity
-        self.state = Hash::from_le_u64([
-            self.state.as_le_u64()[0] ^ element.as_le_u64()[0],
-            self.state.as_le_u64()[1] ^ element.as_le_u64()[1],
-            self.state.as_le_u64()[2] ^ element.as_le_u64()[2],
-            self.state.as_le_u64()[3] ^ element.as_le_u64()[3],
-        ]);
+        self.numerator = self.numerator.mul(&Num3072::from_element(element));
     }
 
     /// Removes an element from the hash.
     pub fn remove(&mut self, element: &Hash) {
-        self.add(element); // XOR is its own inverse
+        self.denominator = self.denominator.mul(&Num3072::from_element(element));
+    }
+
+    /// Builds the MuHash of a UTXO change set: the added outputs over the removed ones.
+    pub fn of_outputs(added: &[(OutPoint, TxOutput)], removed: &[(OutPoint, TxOutput)]) -> Self {
+        let mut muhash = Self::new();
+        for (outpoint, output) in added {
+            muhash.add(&utxo_commitment(outpoint, output));
+        }
+        for (outpoint, output) in removed {
+            muhash.remove(&utxo_commitment(outpoint, output));
+        }
+        muhash
+    }
+
+    /// Merges another MuHash into this one, as if all its additions and removals were applied here.
+    pub fn combine(&mut self, other: &MuHash) {
+        self.numerator = self.numerator.mul(&other.numerator);
+        self.denominator = self.denominator.mul(&other.denominator);
     }
 
     /// Gets the current hash.
     pub fn finalize(&self) -> Hash {
-        self.state
+        // The inverse is costly, so skip it while nothing has been removed
+        let accumulator = if self.denominator == Num3072::ONE {
+            self.numerator
+        } else {
+            self.numerator.mul(&self.denominator.inverse())
+        };
+        hashing::hash_data(&accumulator.to_le_bytes())
     }
 }
 
@@ -62,4 +242,64 @@ mod tests {
         assert_eq!(h1, h3);
         assert_ne!(h1, h2);
     }
+
+    #[test]
+    fn test_muhash_order_independence() {
+        let hashes: Vec<Hash> = (1..=4).map(|i| Hash::from_le_u64([i, 0, 0, 0])).collect();
+
+        let mut forward = MuHash::new();
+        hashes.iter().for_each(|hash| forward.add(hash));
+        forward.remove(&hashes[1]);
+
+        let mut backward = MuHash::new();
+        backward.remove(&hashes[1]);
+        hashes.iter().rev().for_each(|hash| backward.add(hash));
+
+        let mut expected = MuHash::new();
+        [hashes[3], hashes[0], hashes[2]].iter().for_each(|hash| expected.add(hash));
+
+        assert_eq!(forward.finalize(), backward.finalize());
+        assert_eq!(forward.finalize(), expected.finalize());
+    }
+
+    #[test]
+    fn test_muhash_duplicates_do_not_cancel() {
+        let hash = Hash::from_le_u64([7, 0, 0, 0]);
+        let mut muhash = MuHash::new();
+        muhash.add(&hash);
+        muhash.add(&hash);
+        assert_ne!(muhash.finalize(), MuHash::new().finalize());
+    }
+
+    #[test]
+    fn test_muhash_remove_never_added() {
+        let hash = Hash::from_le_u64([9, 0, 0, 0]);
+        let empty = MuHash::new().finalize();
+
+        let mut muhash = MuHash::new();
+        muhash.remove(&hash);
+        assert_ne!(muhash.finalize(), empty);
+        muhash.add(&hash);
+        assert_eq!(muhash.finalize(), empty);
+    }
+
+    #[test]
+    fn test_num3072_inverse() {
+        let num = Num3072::from_element(&Hash::from_le_u64([3, 0, 0, 0]));
+        assert_eq!(num.mul(&num.inverse()), Num3072::ONE);
+
+        // The largest reduced value, p - 1 ≡ -1, is its own inverse
+        let mut minus_one = Num3072([u64::MAX; LIMBS]);
+        minus_one.0[0] = u64::MAX - MODULUS_DIFF;
+        assert_eq!(minus_one.mul(&minus_one), Num3072::ONE);
+        assert_eq!(minus_one.inverse(), minus_one);
+    }
+
+    #[test]
+    fn test_chacha20_keystream() {
+        // RFC 8439 appendix A.1, test vector #1
+        let mut out = [0u8; 16];
+        chacha20_keystream(&[0u8; 32], &mut out);
+        assert_eq!(out, [0x76, 0xb8, 0xe0, 0xad, 0xa0, 0xf1, 0x3d, 0x90, 0x40, 0x5d, 0x6a, 0xe5, 0x53, 0x86, 0xbd, 0x28]);
+    }
 }
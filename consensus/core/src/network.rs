@@ -1,6 +1,8 @@
 //! Network-related primitives for consensus.
 
-use crate::Hash;
+use std::net::{AddrParseError, IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use crate::{hashing, Hash};
 
 /// Network identifier.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -12,6 +14,9 @@ pub enum NetworkId {
 }
 
 impl NetworkId {
+    /// Every known network.
+    pub const ALL: [NetworkId; 4] = [NetworkId::Mainnet, NetworkId::Testnet, NetworkId::Devnet, NetworkId::Simnet];
+
     /// Returns the magic bytes for the network.
     pub fn magic(&self) -> [u8; 4] {
         match self {
@@ -21,6 +26,36 @@ impl NetworkId {
             NetworkId::Simnet => [0xDA, 0xEC, 0xFB, 0x41],
         }
     }
+
+    /// Returns the network identified by the given magic bytes.
+    pub fn from_magic(magic: [u8; 4]) -> Option<Self> {
+        Self::ALL.into_iter().find(|network| network.magic() == magic)
+    }
+
+    /// Returns the human-readable prefix of the network's addresses.
+    pub fn address_prefix(&self) -> &'static str {
+        match self {
+            NetworkId::Mainnet => "jio",
+            NetworkId::Testnet => "jiotest",
+            NetworkId::Devnet => "jiodev",
+            NetworkId::Simnet => "jiosim",
+        }
+    }
+
+    /// Returns the network whose addresses use the given prefix.
+    pub fn from_address_prefix(prefix: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|network| network.address_prefix() == prefix)
+    }
+
+    /// Returns the port peers listen on by default.
+    pub fn default_p2p_port(&self) -> u16 {
+        match self {
+            NetworkId::Mainnet => 16111,
+            NetworkId::Testnet => 16211,
+            NetworkId::Devnet => 16611,
+            NetworkId::Simnet => 16511,
+        }
+    }
 }
 
 /// Peer address representation.
@@ -36,44 +71,104 @@ impl PeerAddress {
     }
 }
 
-/// Contextual network address (stub).
-#[derive(Debug, Clone, Default)]
+/// Parses `ip:port`, `[ipv6]:port`, a bare IP or a bracketed IPv6 address into an IP and an
+/// optional port.
+fn parse_ip_and_port(s: &str) -> Result<(IpAddr, Option<u16>), AddrParseError> {
+    if let Ok(socket_addr) = s.parse::<SocketAddr>() {
+        return Ok((socket_addr.ip(), Some(socket_addr.port())));
+    }
+    if let Some(ipv6) = s.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+        return Ok((IpAddr::V6(ipv6.parse::<Ipv6Addr>()?), None));
+    }
+    Ok((s.parse::<IpAddr>()?, None))
+}
+
+/// Network address whose port may be left to the context, e.g. the network's default port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ContextualNetAddress {
-    pub address: String,
+    pub ip: IpAddr,
+    pub port: Option<u16>,
 }
 
 impl ContextualNetAddress {
+    /// Creates an address, leaving the port to the context when `None`.
+    pub fn new(ip: IpAddr, port: Option<u16>) -> Self {
+        Self { ip, port }
+    }
+
+    /// The unspecified IPv4 address `0.0.0.0` without a port, i.e. listen on every interface.
     pub fn unspecified() -> Self {
-        Self { address: "0.0.0.0".to_string() }
+        Self::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), None)
+    }
+
+    /// Resolves the address, using `default_port` when none was given.
+    pub fn normalize(&self, default_port: u16) -> NetAddress {
+        NetAddress::new(self.ip, self.port.unwrap_or(default_port))
+    }
+}
+
+impl Default for ContextualNetAddress {
+    fn default() -> Self {
+        Self::unspecified()
     }
 }
 
 impl std::str::FromStr for ContextualNetAddress {
-    type Err = std::net::AddrParseError;
+    type Err = AddrParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // Stub: just store the string
-        Ok(Self { address: s.to_string() })
+        let (ip, port) = parse_ip_and_port(s)?;
+        Ok(Self::new(ip, port))
+    }
+}
+
+impl std::fmt::Display for ContextualNetAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.ip, self.port) {
+            (ip, Some(port)) => write!(f, "{}", SocketAddr::new(ip, port)),
+            (IpAddr::V6(ip), None) => write!(f, "[{}]", ip),
+            (ip, None) => write!(f, "{}", ip),
+        }
     }
 }
 
-/// Network address (stub).
-#[derive(Debug, Clone, Default)]
+/// Network address with a resolved port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct NetAddress {
-    pub address: String,
+    pub ip: IpAddr,
+    pub port: u16,
+}
+
+impl NetAddress {
+    /// Creates an address.
+    pub fn new(ip: IpAddr, port: u16) -> Self {
+        Self { ip, port }
+    }
 }
 
 impl std::str::FromStr for NetAddress {
-    type Err = std::net::AddrParseError;
+    type Err = AddrParseError;
 
+    /// Parses an address, defaulting the port to the default network's P2P port when omitted.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // Stub: just store the string
-        Ok(Self { address: s.to_string() })
+        Ok(s.parse::<ContextualNetAddress>()?.normalize(DEFAULT_NETWORK.default_p2p_port()))
+    }
+}
+
+impl std::fmt::Display for NetAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", SocketAddr::new(self.ip, self.port))
+    }
+}
+
+impl From<NetAddress> for SocketAddr {
+    fn from(address: NetAddress) -> Self {
+        SocketAddr::new(address.ip, address.port)
     }
 }
 
 /// Network message types.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum NetworkMessage {
     Ping,
     Pong,
@@ -84,6 +179,185 @@ pub enum NetworkMessage {
     Tx { transaction: Hash }, // Placeholder
 }
 
+/// Wire protocol errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolError {
+    /// The magic bytes don't belong to any known network.
+    UnknownMagic([u8; 4]),
+    /// The message was framed for a different network than expected.
+    MagicMismatch { expected: NetworkId, actual: NetworkId },
+    /// The command name doesn't map to a known message.
+    UnknownCommand(String),
+    /// The payload doesn't hash to the checksum in the header.
+    ChecksumMismatch { expected: [u8; 4], actual: [u8; 4] },
+    /// The message is truncated or its payload is malformed.
+    MalformedPayload(String),
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolError::UnknownMagic(magic) => write!(f, "Unknown network magic: {:02x?}", magic),
+            ProtocolError::MagicMismatch { expected, actual } => {
+                write!(f, "Network magic mismatch: expected {:?}, got {:?}", expected, actual)
+            }
+            ProtocolError::UnknownCommand(command) => write!(f, "Unknown command: {:?}", command),
+            ProtocolError::ChecksumMismatch { expected, actual } => {
+                write!(f, "Payload checksum mismatch: expected {:02x?}, got {:02x?}", expected, actual)
+            }
+            ProtocolError::MalformedPayload(msg) => write!(f, "Malformed payload: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<ProtocolError> for crate::errors::ConsensusError {
+    fn from(err: ProtocolError) -> Self {
+        crate::errors::ConsensusError::NetworkProtocol { msg: err.to_string() }
+    }
+}
+
+/// Size of the zero-padded ASCII command name in the message header.
+const COMMAND_SIZE: usize = 12;
+
+/// Size of the message header: magic, command, payload length and payload checksum.
+const MESSAGE_HEADER_SIZE: usize = 4 + COMMAND_SIZE + 4 + 4;
+
+/// The first four bytes of the payload's double SHA-256.
+fn payload_checksum(payload: &[u8]) -> [u8; 4] {
+    hashing::double_sha256(payload).as_bytes()[..4].try_into().unwrap()
+}
+
+impl NetworkMessage {
+    /// Returns the command name identifying the message type.
+    pub fn command(&self) -> &'static str {
+        match self {
+            NetworkMessage::Ping => "ping",
+            NetworkMessage::Pong => "pong",
+            NetworkMessage::GetBlocks { .. } => "getblocks",
+            NetworkMessage::Blocks { .. } => "blocks",
+            NetworkMessage::Inv { .. } => "inv",
+            NetworkMessage::GetData { .. } => "getdata",
+            NetworkMessage::Tx { .. } => "tx",
+        }
+    }
+
+    /// Splits `hashes` into `Inv` messages carrying at most `max_per_message` hashes each.
+    pub fn inv_chunks(hashes: Vec<Hash>, max_per_message: usize) -> Vec<NetworkMessage> {
+        hashes.chunks(max_per_message.max(1)).map(|chunk| NetworkMessage::Inv { hashes: chunk.to_vec() }).collect()
+    }
+
+    /// Encodes the message framed for the given network, behind a
+    /// `[magic(4)][command(12)][payload length(4)][checksum(4)]` header.
+    pub fn encode(&self, network: NetworkId) -> Vec<u8> {
+        let mut payload = Vec::new();
+        match self {
+            NetworkMessage::Ping | NetworkMessage::Pong => {}
+            NetworkMessage::GetBlocks { hashes }
+            | NetworkMessage::Blocks { blocks: hashes }
+            | NetworkMessage::Inv { hashes }
+            | NetworkMessage::GetData { hashes } => {
+                payload.extend_from_slice(&(hashes.len() as u64).to_le_bytes());
+                for hash in hashes {
+                    payload.extend_from_slice(hash.as_bytes());
+                }
+            }
+            NetworkMessage::Tx { transaction } => payload.extend_from_slice(transaction.as_bytes()),
+        }
+
+        let mut data = Vec::with_capacity(MESSAGE_HEADER_SIZE + payload.len());
+        let mut command = [0u8; COMMAND_SIZE];
+        command[..self.command().len()].copy_from_slice(self.command().as_bytes());
+        data.extend_from_slice(&network.magic());
+        data.extend_from_slice(&command);
+        data.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        data.extend_from_slice(&payload_checksum(&payload));
+        data.extend_from_slice(&payload);
+        data
+    }
+
+    /// Decodes a message, returning the network it was framed for along with it. The payload
+    /// must match the length and checksum in the header.
+    pub fn decode(bytes: &[u8]) -> Result<(NetworkId, NetworkMessage), ProtocolError> {
+        if bytes.len() < MESSAGE_HEADER_SIZE {
+            return Err(ProtocolError::MalformedPayload("message header is truncated".to_string()));
+        }
+        let magic: [u8; 4] = bytes[..4].try_into().unwrap();
+        let network = NetworkId::from_magic(magic).ok_or(ProtocolError::UnknownMagic(magic))?;
+        let command = parse_command(&bytes[4..4 + COMMAND_SIZE])?;
+        let payload_len = u32::from_le_bytes(bytes[16..20].try_into().unwrap()) as usize;
+        let expected: [u8; 4] = bytes[20..24].try_into().unwrap();
+        let payload = &bytes[MESSAGE_HEADER_SIZE..];
+        if payload.len() != payload_len {
+            return Err(ProtocolError::MalformedPayload(format!(
+                "payload length {} does not match header length {}",
+                payload.len(),
+                payload_len
+            )));
+        }
+        let actual = payload_checksum(payload);
+        if actual != expected {
+            return Err(ProtocolError::ChecksumMismatch { expected, actual });
+        }
+
+        let message = match command {
+            "ping" | "pong" if !payload.is_empty() => {
+                return Err(ProtocolError::MalformedPayload("unexpected payload".to_string()));
+            }
+            "ping" => NetworkMessage::Ping,
+            "pong" => NetworkMessage::Pong,
+            "getblocks" => NetworkMessage::GetBlocks { hashes: decode_hashes(payload)? },
+            "blocks" => NetworkMessage::Blocks { blocks: decode_hashes(payload)? },
+            "inv" => NetworkMessage::Inv { hashes: decode_hashes(payload)? },
+            "getdata" => NetworkMessage::GetData { hashes: decode_hashes(payload)? },
+            "tx" if payload.len() == 32 => NetworkMessage::Tx { transaction: Hash::from_slice(payload) },
+            "tx" => return Err(ProtocolError::MalformedPayload("transaction hash must be 32 bytes".to_string())),
+            command => return Err(ProtocolError::UnknownCommand(command.to_string())),
+        };
+        Ok((network, message))
+    }
+
+    /// Decodes a message, rejecting it unless it was framed for the `local` network.
+    pub fn decode_for(local: NetworkId, bytes: &[u8]) -> Result<NetworkMessage, ProtocolError> {
+        if bytes.len() >= 4 {
+            let magic: [u8; 4] = bytes[..4].try_into().unwrap();
+            if magic != local.magic() {
+                let actual = NetworkId::from_magic(magic).ok_or(ProtocolError::UnknownMagic(magic))?;
+                return Err(ProtocolError::MagicMismatch { expected: local, actual });
+            }
+        }
+        let (_, message) = Self::decode(bytes)?;
+        Ok(message)
+    }
+}
+
+/// Parses a zero-padded ASCII command name; padding may only trail the name.
+fn parse_command(bytes: &[u8]) -> Result<&str, ProtocolError> {
+    let len = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+    let (name, padding) = bytes.split_at(len);
+    if padding.iter().any(|&byte| byte != 0) || !name.iter().all(u8::is_ascii_graphic) {
+        return Err(ProtocolError::UnknownCommand(String::from_utf8_lossy(bytes).into_owned()));
+    }
+    Ok(std::str::from_utf8(name).unwrap())
+}
+
+/// Decodes a count-prefixed list of hashes.
+fn decode_hashes(payload: &[u8]) -> Result<Vec<Hash>, ProtocolError> {
+    if payload.len() < 8 {
+        return Err(ProtocolError::MalformedPayload("hash count is truncated".to_string()));
+    }
+    let count = u64::from_le_bytes(payload[..8].try_into().unwrap());
+    let hashes = &payload[8..];
+    if hashes.len() as u64 != count.saturating_mul(32) {
+        return Err(ProtocolError::MalformedPayload(format!("expected {} hashes", count)));
+    }
+    Ok(hashes.chunks_exact(32).map(Hash::from_slice).collect())
+}
+
+/// Maximum number of hashes carried by a single inventory message.
+pub const MAX_INV_PER_MESSAGE: usize = 50_000;
+
 /// Default network ID.
 pub const DEFAULT_NETWORK: NetworkId = NetworkId::Mainnet;
 
@@ -101,4 +375,145 @@ mod tests {
         let addr = PeerAddress::new("127.0.0.1".parse().unwrap(), 8333);
         assert_eq!(addr.port, 8333);
     }
+
+    #[test]
+    fn test_parse_ipv4_address() {
+        let address: ContextualNetAddress = "192.168.1.7:16111".parse().unwrap();
+        assert_eq!(address, ContextualNetAddress::new(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 7)), Some(16111)));
+        assert_eq!(address.to_string(), "192.168.1.7:16111");
+        assert_eq!("192.168.1.7:1".parse::<NetAddress>().unwrap(), NetAddress::new(address.ip, 1));
+    }
+
+    #[test]
+    fn test_parse_ipv6_address() {
+        let address: ContextualNetAddress = "[::1]:16211".parse().unwrap();
+        assert_eq!(address, ContextualNetAddress::new(IpAddr::V6(Ipv6Addr::LOCALHOST), Some(16211)));
+        assert_eq!(address.to_string(), "[::1]:16211");
+
+        for bare in ["::1", "[::1]"] {
+            assert_eq!(bare.parse::<ContextualNetAddress>().unwrap(), ContextualNetAddress::new(IpAddr::V6(Ipv6Addr::LOCALHOST), None));
+        }
+        assert_eq!("[::1]".parse::<ContextualNetAddress>().unwrap().to_string(), "[::1]");
+    }
+
+    #[test]
+    fn test_missing_port_defaults() {
+        let address: ContextualNetAddress = "10.0.0.1".parse().unwrap();
+        assert_eq!(address.port, None);
+        assert_eq!(address.normalize(NetworkId::Testnet.default_p2p_port()).port, 16211);
+        assert_eq!("10.0.0.1:5".parse::<ContextualNetAddress>().unwrap().normalize(16211).port, 5);
+
+        let address: NetAddress = "10.0.0.1".parse().unwrap();
+        assert_eq!(address.port, DEFAULT_NETWORK.default_p2p_port());
+        assert_eq!(ContextualNetAddress::default().to_string(), "0.0.0.0");
+    }
+
+    #[test]
+    fn test_parse_invalid_address() {
+        for invalid in ["notanip", "", "1.2.3", "1.2.3.4:port", "1.2.3.4:70000", "[1.2.3.4]", "::1:16111:"] {
+            assert!(invalid.parse::<ContextualNetAddress>().is_err(), "{:?} should not parse", invalid);
+            assert!(invalid.parse::<NetAddress>().is_err());
+        }
+    }
+
+    #[test]
+    fn test_decode_for_local_network() {
+        let message = NetworkMessage::Inv { hashes: vec![Hash::from_le_u64([1, 2, 3, 4])] };
+        let bytes = message.encode(NetworkId::Testnet);
+
+        assert_eq!(
+            NetworkMessage::decode_for(NetworkId::Mainnet, &bytes),
+            Err(ProtocolError::MagicMismatch { expected: NetworkId::Mainnet, actual: NetworkId::Testnet })
+        );
+        assert_eq!(NetworkMessage::decode_for(NetworkId::Testnet, &bytes), Ok(message));
+    }
+
+    #[test]
+    fn test_message_round_trip() {
+        let hashes: Vec<Hash> = (1..=3).map(|i| Hash::from_le_u64([i, 0, 0, 0])).collect();
+        let messages = vec![
+            NetworkMessage::Ping,
+            NetworkMessage::Pong,
+            NetworkMessage::GetBlocks { hashes: hashes.clone() },
+            NetworkMessage::Blocks { blocks: hashes.clone() },
+            NetworkMessage::Inv { hashes: hashes.clone() },
+            NetworkMessage::GetData { hashes: vec![] },
+            NetworkMessage::Tx { transaction: hashes[0] },
+        ];
+        for network in NetworkId::ALL {
+            for message in &messages {
+                let bytes = message.encode(network);
+                assert_eq!(&bytes[..4], &network.magic());
+                assert_eq!(parse_command(&bytes[4..16]), Ok(message.command()));
+                assert_eq!(NetworkMessage::decode(&bytes), Ok((network, message.clone())));
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed_frames() {
+        let mut bytes = NetworkMessage::Ping.encode(NetworkId::Mainnet);
+        bytes[..4].copy_from_slice(&[1, 2, 3, 4]);
+        assert_eq!(NetworkMessage::decode(&bytes), Err(ProtocolError::UnknownMagic([1, 2, 3, 4])));
+
+        let mut bytes = NetworkMessage::Ping.encode(NetworkId::Mainnet);
+        bytes[4..8].copy_from_slice(b"pang");
+        assert_eq!(NetworkMessage::decode(&bytes), Err(ProtocolError::UnknownCommand("pang".to_string())));
+        bytes[4..16].copy_from_slice(b"ping\0\0\0\0\0\0\0x");
+        assert!(matches!(NetworkMessage::decode(&bytes), Err(ProtocolError::UnknownCommand(_))));
+
+        let bytes = NetworkMessage::Inv { hashes: vec![Hash::default()] }.encode(NetworkId::Mainnet);
+        assert!(matches!(NetworkMessage::decode(&bytes[..bytes.len() - 1]), Err(ProtocolError::MalformedPayload(_))));
+        assert!(matches!(NetworkMessage::decode(&bytes[..MESSAGE_HEADER_SIZE - 1]), Err(ProtocolError::MalformedPayload(_))));
+    }
+
+    #[test]
+    fn test_decode_detects_corrupted_payload() {
+        let message = NetworkMessage::GetData { hashes: vec![Hash::from_le_u64([1, 2, 3, 4]), Hash::from_le_u64([5, 6, 7, 8])] };
+        let bytes = message.encode(NetworkId::Mainnet);
+        assert_eq!(&bytes[..4], &NetworkId::Mainnet.magic());
+        assert_eq!(&bytes[4..16], b"getdata\0\0\0\0\0");
+        assert_eq!(u32::from_le_bytes(bytes[16..20].try_into().unwrap()) as usize, bytes.len() - MESSAGE_HEADER_SIZE);
+
+        for index in MESSAGE_HEADER_SIZE..bytes.len() {
+            let mut corrupted = bytes.clone();
+            corrupted[index] ^= 0x01;
+            assert!(
+                matches!(NetworkMessage::decode(&corrupted), Err(ProtocolError::ChecksumMismatch { .. })),
+                "flipped byte {} went undetected",
+                index
+            );
+        }
+
+        let mut corrupted = bytes.clone();
+        corrupted[20] ^= 0xff;
+        assert_eq!(
+            NetworkMessage::decode(&corrupted),
+            Err(ProtocolError::ChecksumMismatch { expected: corrupted[20..24].try_into().unwrap(), actual: payload_checksum(&bytes[24..]) })
+        );
+    }
+
+    #[test]
+    fn test_inv_chunks() {
+        let hashes: Vec<Hash> = (0..120_000u64).map(|i| Hash::from_le_u64([i, 0, 0, 0])).collect();
+        let messages = NetworkMessage::inv_chunks(hashes.clone(), MAX_INV_PER_MESSAGE);
+        let sizes: Vec<usize> = messages
+            .iter()
+            .map(|message| match message {
+                NetworkMessage::Inv { hashes } => hashes.len(),
+                _ => panic!("expected an Inv message"),
+            })
+            .collect();
+        assert_eq!(sizes, vec![50_000, 50_000, 20_000]);
+
+        let rejoined: Vec<Hash> = messages
+            .into_iter()
+            .flat_map(|message| match message {
+                NetworkMessage::Inv { hashes } => hashes,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(rejoined, hashes);
+        assert!(NetworkMessage::inv_chunks(vec![], MAX_INV_PER_MESSAGE).is_empty());
+    }
 }
@@ -0,0 +1,221 @@
+//! Orphan block management.
+
+use std::collections::BTreeMap;
+
+use crate::{block::Block, constants::MAX_ORPHAN_BLOCKS, BlockHashMap, Hash, HashMapCustomHasher};
+
+/// An orphan block along with the parents it is waiting for.
+#[derive(Debug, Clone)]
+struct OrphanEntry {
+    block: Block,
+    missing_parents: Vec<Hash>,
+    /// Position in the recency order, higher is more recent.
+    last_used: u64,
+}
+
+/// Pool of blocks whose parents are not yet known, holding at most `capacity` orphans.
+/// Once full, the least recently added orphan is evicted first.
+#[derive(Debug)]
+pub struct OrphanPool {
+    orphans: BlockHashMap<OrphanEntry>,
+    /// Index from a missing parent to the orphans blocked on it.
+    waiting: BlockHashMap<Vec<Hash>>,
+    /// Orphans ordered from least to most recently used.
+    recency: BTreeMap<u64, Hash>,
+    next_use: u64,
+    capacity: usize,
+}
+
+impl OrphanPool {
+    /// Creates an empty orphan pool holding up to `MAX_ORPHAN_BLOCKS` orphans.
+    pub fn new() -> Self {
+        Self::with_capacity(MAX_ORPHAN_BLOCKS)
+    }
+
+    /// Creates an empty orphan pool holding up to `capacity` orphans.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self { orphans: BlockHashMap::new(), waiting: BlockHashMap::new(), recency: BTreeMap::new(), next_use: 0, capacity }
+    }
+
+    /// Adds an orphan block blocked on `missing_parents`, evicting the least recently used orphan
+    /// if the pool is full. Returns false if it was already present, in which case it is only
+    /// marked as recently used.
+    pub fn add(&mut self, block: Block, missing_parents: Vec<Hash>) -> bool {
+        let hash = block.hash();
+        let last_used = self.next_use;
+        self.next_use += 1;
+        if let Some(entry) = self.orphans.get_mut(&hash) {
+            self.recency.remove(&entry.last_used);
+            self.recency.insert(last_used, hash);
+            entry.last_used = last_used;
+            return false;
+        }
+
+        while self.orphans.len() >= self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else { break };
+            self.remove(&oldest);
+        }
+        if self.capacity == 0 {
+            return false;
+        }
+        for parent in &missing_parents {
+            self.waiting.entry(*parent).or_default().push(hash);
+        }
+        self.recency.insert(last_used, hash);
+        self.orphans.insert(hash, OrphanEntry { block, missing_parents, last_used });
+        true
+    }
+
+    /// Removes an orphan block from the pool.
+    pub fn remove(&mut self, hash: &Hash) -> Option<Block> {
+        let entry = self.orphans.remove(hash)?;
+        self.recency.remove(&entry.last_used);
+        for parent in &entry.missing_parents {
+            if let Some(waiting) = self.waiting.get_mut(parent) {
+                waiting.retain(|orphan| orphan != hash);
+                if waiting.is_empty() {
+                    self.waiting.remove(parent);
+                }
+            }
+        }
+        Some(entry.block)
+    }
+
+    /// Returns the orphans blocked on the given missing parent.
+    pub fn get_children_of(&self, parent: Hash) -> Vec<&Block> {
+        self.waiting.get(&parent).into_iter().flatten().filter_map(|hash| self.orphans.get(hash)).map(|entry| &entry.block).collect()
+    }
+
+    /// Marks `parent` as arrived and drains the orphans no longer missing any parent, in the
+    /// order they were added. Once a drained block is accepted, call this again with its hash
+    /// to release its own children.
+    pub fn resolve_parent(&mut self, parent: Hash) -> Vec<Block> {
+        let Some(children) = self.waiting.remove(&parent) else { return vec![] };
+        let mut connectable: Vec<Hash> = children
+            .into_iter()
+            .filter(|child| {
+                let Some(entry) = self.orphans.get_mut(child) else { return false };
+                entry.missing_parents.retain(|missing| *missing != parent);
+                entry.missing_parents.is_empty()
+            })
+            .collect();
+        connectable.sort_by_key(|hash| self.orphans[hash].last_used);
+        connectable.into_iter().filter_map(|hash| self.remove(&hash)).collect()
+    }
+
+    /// Checks if a block is held as an orphan.
+    pub fn contains(&self, hash: &Hash) -> bool {
+        self.orphans.contains_key(hash)
+    }
+
+    /// Returns the hashes of orphans blocked on the given missing parent.
+    pub fn waiting_on(&self, parent: Hash) -> Vec<Hash> {
+        self.waiting.get(&parent).cloned().unwrap_or_default()
+    }
+
+    /// Gets the number of orphans in the pool.
+    pub fn len(&self) -> usize {
+        self.orphans.len()
+    }
+
+    /// Checks if the pool is empty.
+    pub fn is_empty(&self) -> bool {
+        self.orphans.is_empty()
+    }
+}
+
+impl Default for OrphanPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::Header;
+
+    fn create_orphan(parent: Hash, nonce: u64) -> Block {
+        let mut header = Header::new();
+        header.parents_by_level = vec![vec![parent]];
+        header.nonce = nonce;
+        Block::new(header, vec![])
+    }
+
+    #[test]
+    fn test_waiting_on() {
+        let mut pool = OrphanPool::new();
+        let missing1 = Hash::from_le_u64([1, 0, 0, 0]);
+        let missing2 = Hash::from_le_u64([2, 0, 0, 0]);
+
+        let orphan1 = create_orphan(missing1, 1);
+        let orphan2 = create_orphan(missing1, 2);
+        let orphan3 = create_orphan(missing2, 3);
+        assert!(pool.add(orphan1.clone(), vec![missing1]));
+        assert!(pool.add(orphan2.clone(), vec![missing1]));
+        assert!(pool.add(orphan3.clone(), vec![missing2]));
+        assert!(!pool.add(orphan3.clone(), vec![missing2]));
+        assert_eq!(pool.len(), 3);
+
+        assert_eq!(pool.waiting_on(missing1), vec![orphan1.hash(), orphan2.hash()]);
+        assert_eq!(pool.waiting_on(missing2), vec![orphan3.hash()]);
+        assert!(pool.waiting_on(Hash::from_le_u64([3, 0, 0, 0])).is_empty());
+
+        assert_eq!(pool.remove(&orphan1.hash()), Some(orphan1));
+        assert_eq!(pool.waiting_on(missing1), vec![orphan2.hash()]);
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    fn test_eviction_order() {
+        let mut pool = OrphanPool::with_capacity(3);
+        let missing = Hash::from_le_u64([1, 0, 0, 0]);
+        let orphans: Vec<Block> = (0..5).map(|nonce| create_orphan(missing, nonce)).collect();
+
+        for orphan in &orphans[..3] {
+            assert!(pool.add(orphan.clone(), vec![missing]));
+        }
+        // Re-adding the oldest orphan makes it the most recently used
+        assert!(!pool.add(orphans[0].clone(), vec![missing]));
+
+        assert!(pool.add(orphans[3].clone(), vec![missing]));
+        assert_eq!(pool.len(), 3);
+        assert!(!pool.contains(&orphans[1].hash()));
+
+        assert!(pool.add(orphans[4].clone(), vec![missing]));
+        assert!(!pool.contains(&orphans[2].hash()));
+        assert_eq!(pool.waiting_on(missing), vec![orphans[0].hash(), orphans[3].hash(), orphans[4].hash()]);
+
+        let mut pool = OrphanPool::new();
+        for nonce in 0..MAX_ORPHAN_BLOCKS as u64 + 10 {
+            pool.add(create_orphan(missing, nonce), vec![missing]);
+        }
+        assert_eq!(pool.len(), MAX_ORPHAN_BLOCKS);
+        assert!(!pool.contains(&create_orphan(missing, 9).hash()));
+        assert!(pool.contains(&create_orphan(missing, 10).hash()));
+    }
+
+    #[test]
+    fn test_resolve_parent() {
+        let mut pool = OrphanPool::new();
+        let parent1 = Hash::from_le_u64([1, 0, 0, 0]);
+        let parent2 = Hash::from_le_u64([2, 0, 0, 0]);
+
+        let single = create_orphan(parent1, 1);
+        let double = create_orphan(parent1, 2);
+        pool.add(single.clone(), vec![parent1]);
+        pool.add(double.clone(), vec![parent1, parent2]);
+        let grandchild = create_orphan(single.hash(), 3);
+        pool.add(grandchild.clone(), vec![single.hash()]);
+        assert_eq!(pool.get_children_of(parent1), vec![&single, &double]);
+
+        assert_eq!(pool.resolve_parent(parent1), vec![single.clone()]);
+        assert!(pool.get_children_of(parent1).is_empty());
+        assert_eq!(pool.get_children_of(parent2), vec![&double]);
+        assert!(pool.resolve_parent(parent1).is_empty());
+
+        assert_eq!(pool.resolve_parent(single.hash()), vec![grandchild]);
+        assert_eq!(pool.resolve_parent(parent2), vec![double]);
+        assert!(pool.is_empty());
+    }
+}
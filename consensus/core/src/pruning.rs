@@ -81,8 +81,8 @@ mod tests {
     fn test_pruning_manager_no_duplicates() {
         let mut manager = PruningManager::new();
         let hash = Hash::from_le_u64([1, 0, 0, 0]);
-        manager.prune_block(hash.clone());
-        manager.prune_block(hash.clone());
+        manager.prune_block(hash);
+        manager.prune_block(hash);
         assert_eq!(manager.pruned_blocks.len(), 1);
     }
 }
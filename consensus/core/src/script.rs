@@ -0,0 +1,5 @@
+//! Script execution.
+
+pub mod interpreter;
+
+pub use interpreter::{execute, signature_message, ScriptError};
@@ -0,0 +1,237 @@
+//! A stack machine for the opcodes standard scripts are built from.
+//!
+//! The signature script runs first and may only push data; the script public key then runs on the
+//! resulting stack. A spend is valid when execution finishes with a true value on top of the stack.
+
+use std::fmt;
+
+use crate::{
+    constants::{MAX_SCRIPT_SIZE, MAX_STACK_SIZE},
+    hashing,
+    sign::verify_signature,
+    tx::{
+        opcodes::{parse_ops, Op},
+        Transaction,
+    },
+};
+
+/// Errors raised while executing a script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptError {
+    /// The input index is outside the transaction's inputs.
+    InvalidInputIndex(usize),
+    /// A script is longer than `MAX_SCRIPT_SIZE`.
+    ScriptTooLong(usize),
+    /// A script can't be parsed into operations.
+    Malformed(String),
+    /// The signature script contains an operation other than a data push.
+    NonPushOnlySignatureScript,
+    /// An operation needs more stack items than there are.
+    StackUnderflow,
+    /// The stack grew beyond `MAX_STACK_SIZE` items.
+    StackOverflow,
+    /// `OP_EQUALVERIFY` found two different items.
+    EqualVerifyFailed,
+    /// `OP_RETURN` was executed.
+    EarlyReturn,
+    /// An opcode the interpreter doesn't implement.
+    UnsupportedOpcode(u8),
+    /// Execution finished with an empty stack or a false value on top.
+    EvalFalse,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::InvalidInputIndex(index) => write!(f, "Input index {} is out of range", index),
+            ScriptError::ScriptTooLong(len) => write!(f, "Script of {} bytes exceeds the maximum of {}", len, MAX_SCRIPT_SIZE),
+            ScriptError::Malformed(msg) => write!(f, "Malformed script: {}", msg),
+            ScriptError::NonPushOnlySignatureScript => write!(f, "Signature script must only push data"),
+            ScriptError::StackUnderflow => write!(f, "Stack underflow"),
+            ScriptError::StackOverflow => write!(f, "Stack exceeds the maximum of {} items", MAX_STACK_SIZE),
+            ScriptError::EqualVerifyFailed => write!(f, "OP_EQUALVERIFY failed"),
+            ScriptError::EarlyReturn => write!(f, "OP_RETURN executed"),
+            ScriptError::UnsupportedOpcode(opcode) => write!(f, "Unsupported opcode 0x{:02x}", opcode),
+            ScriptError::EvalFalse => write!(f, "Script evaluated to false"),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// The message `OP_CHECKSIG` verifies signatures against: the transaction id, which leaves out
+/// signature scripts, followed by the little-endian input index.
+pub fn signature_message(tx: &Transaction, input_index: usize) -> Vec<u8> {
+    let mut message = tx.id().as_bytes().to_vec();
+    message.extend_from_slice(&(input_index as u32).to_le_bytes());
+    message
+}
+
+/// Executes `script_sig` followed by `script_pubkey` for the input at `input_index` of `tx`.
+pub fn execute(script_sig: &[u8], script_pubkey: &[u8], tx: &Transaction, input_index: usize) -> Result<(), ScriptError> {
+    if input_index >= tx.inputs.len() {
+        return Err(ScriptError::InvalidInputIndex(input_index));
+    }
+    let sig_ops = parse_script(script_sig)?;
+    let pubkey_ops = parse_script(script_pubkey)?;
+    if !sig_ops.iter().all(is_push) {
+        return Err(ScriptError::NonPushOnlySignatureScript);
+    }
+
+    let mut stack = Stack::default();
+    for op in sig_ops.into_iter().chain(pubkey_ops) {
+        step(&mut stack, op, tx, input_index)?;
+    }
+    match stack.pop() {
+        Ok(top) if is_true(&top) => Ok(()),
+        _ => Err(ScriptError::EvalFalse),
+    }
+}
+
+fn parse_script(script: &[u8]) -> Result<Vec<Op>, ScriptError> {
+    if script.len() > MAX_SCRIPT_SIZE {
+        return Err(ScriptError::ScriptTooLong(script.len()));
+    }
+    parse_ops(script).map_err(|err| ScriptError::Malformed(err.to_string()))
+}
+
+fn is_push(op: &Op) -> bool {
+    matches!(op, Op::PushBytes(_) | Op::PushData1(_) | Op::PushData2(_) | Op::PushData4(_))
+}
+
+/// Any non-zero byte makes a stack item true.
+fn is_true(item: &[u8]) -> bool {
+    item.iter().any(|&byte| byte != 0)
+}
+
+fn step(stack: &mut Stack, op: Op, tx: &Transaction, input_index: usize) -> Result<(), ScriptError> {
+    match op {
+        Op::PushBytes(data) | Op::PushData1(data) | Op::PushData2(data) | Op::PushData4(data) => stack.push(data),
+        Op::Dup => {
+            let top = stack.pop()?;
+            stack.push(top.clone())?;
+            stack.push(top)
+        }
+        Op::Hash160 => {
+            // Pubkey hashes are the 32-byte script hash, matching `ScriptPublicKey::pay_to_pubkey_hash`
+            let top = stack.pop()?;
+            stack.push(hashing::hash_script(&top).as_bytes().to_vec())
+        }
+        Op::Equal => {
+            let (a, b) = (stack.pop()?, stack.pop()?);
+            stack.push_bool(a == b)
+        }
+        Op::EqualVerify => {
+            let (a, b) = (stack.pop()?, stack.pop()?);
+            if a != b {
+                return Err(ScriptError::EqualVerifyFailed);
+            }
+            Ok(())
+        }
+        Op::CheckSig => {
+            let public_key = stack.pop()?;
+            let signature = stack.pop()?;
+            let valid = <[u8; 64]>::try_from(signature.as_slice())
+                .is_ok_and(|signature| verify_signature(&signature_message(tx, input_index), &signature, &public_key).is_ok());
+            stack.push_bool(valid)
+        }
+        Op::Return => Err(ScriptError::EarlyReturn),
+        Op::Other(opcode) => Err(ScriptError::UnsupportedOpcode(opcode)),
+    }
+}
+
+/// The execution stack, bounded by `MAX_STACK_SIZE` items.
+#[derive(Default)]
+struct Stack(Vec<Vec<u8>>);
+
+impl Stack {
+    fn push(&mut self, item: Vec<u8>) -> Result<(), ScriptError> {
+        if self.0.len() >= MAX_STACK_SIZE {
+            return Err(ScriptError::StackOverflow);
+        }
+        self.0.push(item);
+        Ok(())
+    }
+
+    fn push_bool(&mut self, value: bool) -> Result<(), ScriptError> {
+        self.push(if value { vec![1] } else { vec![] })
+    }
+
+    fn pop(&mut self) -> Result<Vec<u8>, ScriptError> {
+        self.0.pop().ok_or(ScriptError::StackUnderflow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        sign::sign_data,
+        tx::{opcodes::encode_ops, script_public_key::ScriptPublicKey, TxInput, TxOutput},
+        Hash,
+    };
+    use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+    fn public_key(private_key: &[u8; 32]) -> Vec<u8> {
+        let secret_key = SecretKey::from_slice(private_key).unwrap();
+        PublicKey::from_secret_key(&Secp256k1::signing_only(), &secret_key).serialize().to_vec()
+    }
+
+    fn spending_tx() -> Transaction {
+        let input = TxInput { prev_tx_hash: Hash::from_le_u64([1, 0, 0, 0]), index: 0, script_sig: vec![], sequence: 0 };
+        Transaction::new(0, vec![input], vec![TxOutput { value: 10, script_pubkey: vec![0x51] }], 0)
+    }
+
+    fn p2pkh_spend(tx: &Transaction, private_key: &[u8; 32]) -> Vec<u8> {
+        let signature = sign_data(&signature_message(tx, 0), private_key).unwrap();
+        encode_ops(&[Op::push(&signature), Op::push(&public_key(private_key))])
+    }
+
+    #[test]
+    fn test_p2pkh_spend() {
+        let tx = spending_tx();
+        let script_pubkey = ScriptPublicKey::pay_to_pubkey_hash(&hashing::hash_script(&public_key(&[1; 32]))).script;
+        assert_eq!(execute(&p2pkh_spend(&tx, &[1; 32]), &script_pubkey, &tx, 0), Ok(()));
+        assert_eq!(execute(&p2pkh_spend(&tx, &[1; 32]), &script_pubkey, &tx, 1), Err(ScriptError::InvalidInputIndex(1)));
+
+        // A key that doesn't hash to the locked pubkey hash
+        assert_eq!(execute(&p2pkh_spend(&tx, &[2; 32]), &script_pubkey, &tx, 0), Err(ScriptError::EqualVerifyFailed));
+    }
+
+    #[test]
+    fn test_p2pkh_invalid_signature() {
+        let tx = spending_tx();
+        let script_pubkey = ScriptPublicKey::pay_to_pubkey_hash(&hashing::hash_script(&public_key(&[1; 32]))).script;
+
+        // Signed by another key, then paired with the locked key
+        let signature = sign_data(&signature_message(&tx, 0), &[2; 32]).unwrap();
+        let script_sig = encode_ops(&[Op::push(&signature), Op::push(&public_key(&[1; 32]))]);
+        assert_eq!(execute(&script_sig, &script_pubkey, &tx, 0), Err(ScriptError::EvalFalse));
+
+        // A valid signature over a different transaction
+        let mut other = tx.clone();
+        other.outputs[0].value = 11;
+        assert_eq!(execute(&p2pkh_spend(&other, &[1; 32]), &script_pubkey, &tx, 0), Err(ScriptError::EvalFalse));
+        let err: crate::errors::ConsensusError = ScriptError::EvalFalse.into();
+        assert!(matches!(err, crate::errors::ConsensusError::ScriptValidation { .. }));
+    }
+
+    #[test]
+    fn test_execution_limits() {
+        let tx = spending_tx();
+        let too_long = vec![0x51; MAX_SCRIPT_SIZE + 1];
+        assert_eq!(execute(&[], &too_long, &tx, 0), Err(ScriptError::ScriptTooLong(MAX_SCRIPT_SIZE + 1)));
+
+        let pushes = encode_ops(&vec![Op::push(&[1]); MAX_STACK_SIZE]);
+        assert_eq!(execute(&pushes, &[], &tx, 0), Ok(()));
+        assert_eq!(execute(&pushes, &encode_ops(&[Op::Dup]), &tx, 0), Err(ScriptError::StackOverflow));
+
+        assert_eq!(execute(&[], &encode_ops(&[Op::Dup]), &tx, 0), Err(ScriptError::StackUnderflow));
+        assert_eq!(execute(&encode_ops(&[Op::Dup]), &[], &tx, 0), Err(ScriptError::NonPushOnlySignatureScript));
+        assert_eq!(execute(&[], &encode_ops(&[Op::Return]), &tx, 0), Err(ScriptError::EarlyReturn));
+        assert_eq!(execute(&[], &[0x51], &tx, 0), Err(ScriptError::UnsupportedOpcode(0x51)));
+        assert!(matches!(execute(&[0x20, 0x01], &[], &tx, 0), Err(ScriptError::Malformed(_))));
+        assert_eq!(execute(&encode_ops(&[Op::push(&[2]), Op::push(&[2])]), &encode_ops(&[Op::Equal]), &tx, 0), Ok(()));
+        assert_eq!(execute(&encode_ops(&[Op::push(&[0, 0])]), &[], &tx, 0), Err(ScriptError::EvalFalse));
+    }
+}
@@ -1,40 +1,98 @@
 //! Signature utilities.
+//!
+//! ECDSA over secp256k1: messages are hashed with SHA256, signatures are 64-byte compact
+//! `(r, s)` encodings and public keys are SEC1-encoded (33 or 65 bytes).
 
-use crate::errors::ConsensusResult;
+use std::fmt;
 
-/// Signs data with a private key (placeholder).
-pub fn sign_data(_data: &[u8], _private_key: &[u8]) -> Vec<u8> {
-    // Placeholder: return dummy signature
-    vec![0; 64]
+use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1, SecretKey};
+
+use crate::{errors::ConsensusResult, hashing};
+
+/// Errors raised when signing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignError {
+    /// The private key isn't a valid secp256k1 scalar.
+    InvalidPrivateKey,
 }
 
-/// Verifies a signature (placeholder).
-pub fn verify_signature(_data: &[u8], signature: &[u8], _public_key: &[u8]) -> ConsensusResult<()> {
-    if signature.len() != 64 {
-        return Err(crate::errors::ConsensusError::InvalidSignature);
+impl fmt::Display for SignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignError::InvalidPrivateKey => write!(f, "Invalid secp256k1 private key"),
+        }
     }
-    // Placeholder: always valid
-    Ok(())
+}
+
+impl std::error::Error for SignError {}
+
+fn message_digest(data: &[u8]) -> Message {
+    Message::from_digest(*hashing::hash_data(data).as_bytes())
+}
+
+/// Signs the SHA256 digest of `data`. Signatures are deterministic (RFC 6979).
+pub fn sign_data(data: &[u8], private_key: &[u8; 32]) -> Result<[u8; 64], SignError> {
+    let secret_key = SecretKey::from_slice(private_key).map_err(|_| SignError::InvalidPrivateKey)?;
+    let signature = Secp256k1::signing_only().sign_ecdsa(&message_digest(data), &secret_key);
+    Ok(signature.serialize_compact())
+}
+
+/// Verifies a signature over the SHA256 digest of `data`. Malformed signatures or keys and
+/// genuine mismatches all fail with `InvalidSignature`.
+pub fn verify_signature(data: &[u8], signature: &[u8; 64], public_key: &[u8]) -> ConsensusResult<()> {
+    let invalid = |_| crate::errors::ConsensusError::InvalidSignature;
+    let signature = Signature::from_compact(signature).map_err(invalid)?;
+    let public_key = PublicKey::from_slice(public_key).map_err(invalid)?;
+    Secp256k1::verification_only().verify_ecdsa(&message_digest(data), &signature, &public_key).map_err(invalid)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn public_key(private_key: &[u8; 32]) -> [u8; 33] {
+        let secret_key = SecretKey::from_slice(private_key).unwrap();
+        PublicKey::from_secret_key(&Secp256k1::signing_only(), &secret_key).serialize()
+    }
+
     #[test]
     fn test_sign_data() {
-        let sig = sign_data(b"test", &[0; 32]);
-        assert_eq!(sig.len(), 64);
+        let sig = sign_data(b"test", &[1; 32]).unwrap();
+        assert_eq!(sig, sign_data(b"test", &[1; 32]).unwrap());
+        assert_eq!(sign_data(b"test", &[0; 32]), Err(SignError::InvalidPrivateKey));
     }
 
     #[test]
     fn test_verify_signature_valid() {
-        let sig = sign_data(b"test", &[0; 32]);
-        assert!(verify_signature(b"test", &sig, &[0; 33]).is_ok());
+        let sig = sign_data(b"test", &[1; 32]).unwrap();
+        assert!(verify_signature(b"test", &sig, &public_key(&[1; 32])).is_ok());
     }
 
     #[test]
     fn test_verify_signature_invalid() {
-        assert!(verify_signature(b"test", &[0; 32], &[0; 33]).is_err());
+        let sig = sign_data(b"test", &[1; 32]).unwrap();
+        assert!(verify_signature(b"test", &[0; 64], &public_key(&[1; 32])).is_err());
+        assert!(verify_signature(b"test", &sig, &[0; 33]).is_err());
+    }
+
+    #[test]
+    fn test_verify_signature_wrong_key() {
+        let sig = sign_data(b"test", &[1; 32]).unwrap();
+        assert_eq!(
+            verify_signature(b"test", &sig, &public_key(&[2; 32])),
+            Err(crate::errors::ConsensusError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_verify_signature_tampered_data() {
+        let data = b"some signed payload".to_vec();
+        let sig = sign_data(&data, &[1; 32]).unwrap();
+        let mut tampered = data.clone();
+        tampered[3] ^= 1;
+        assert_eq!(
+            verify_signature(&tampered, &sig, &public_key(&[1; 32])),
+            Err(crate::errors::ConsensusError::InvalidSignature)
+        );
     }
 }
@@ -5,6 +5,53 @@ use crate::Hash;
 /// Subnet identifier.
 pub type SubnetId = u32;
 
+/// Size of a transaction subnetwork identifier in bytes.
+pub const SUBNETWORK_ID_SIZE: usize = 20;
+
+/// Identifier of the subnetwork a transaction belongs to. Built-in subnetworks decide which
+/// validation rules apply to the transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, serde::Serialize, serde::Deserialize)]
+pub struct SubnetworkId([u8; SUBNETWORK_ID_SIZE]);
+
+/// Subnetwork of regular value transfers; carries no gas or payload.
+pub const SUBNETWORK_ID_NATIVE: SubnetworkId = SubnetworkId::from_byte(0);
+
+/// Subnetwork of coinbase transactions.
+pub const SUBNETWORK_ID_COINBASE: SubnetworkId = SubnetworkId::from_byte(1);
+
+/// Subnetwork used to register new subnetworks.
+pub const SUBNETWORK_ID_REGISTRY: SubnetworkId = SubnetworkId::from_byte(2);
+
+impl SubnetworkId {
+    /// Creates a subnetwork id from its raw bytes.
+    pub const fn from_bytes(bytes: [u8; SUBNETWORK_ID_SIZE]) -> Self {
+        Self(bytes)
+    }
+
+    /// Creates a subnetwork id whose first byte is `byte` and the rest zero.
+    pub const fn from_byte(byte: u8) -> Self {
+        let mut bytes = [0u8; SUBNETWORK_ID_SIZE];
+        bytes[0] = byte;
+        Self(bytes)
+    }
+
+    /// Checks if this is the native subnetwork.
+    pub fn is_native(&self) -> bool {
+        *self == SUBNETWORK_ID_NATIVE
+    }
+
+    /// Checks if this is one of the built-in subnetworks (coinbase or registry).
+    pub fn is_builtin(&self) -> bool {
+        *self == SUBNETWORK_ID_COINBASE || *self == SUBNETWORK_ID_REGISTRY
+    }
+}
+
+impl AsRef<[u8]> for SubnetworkId {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
 /// Subnet information.
 #[derive(Debug, Clone)]
 pub struct Subnet {
@@ -40,4 +87,13 @@ mod tests {
         subnet.add_member(member);
         assert!(subnet.has_member(&member));
     }
+
+    #[test]
+    fn test_subnetwork_id() {
+        assert!(SubnetworkId::default().is_native());
+        assert!(!SUBNETWORK_ID_COINBASE.is_native());
+        assert!(SUBNETWORK_ID_COINBASE.is_builtin());
+        assert!(SUBNETWORK_ID_REGISTRY.is_builtin());
+        assert!(!SubnetworkId::from_byte(3).is_builtin());
+    }
 }
@@ -1,7 +1,11 @@
 //! Transaction data structures.
 
-use crate::{hashing, Hash, errors::ConsensusResult};
+use crate::{config::params::Params, hashing, Hash, errors::ConsensusResult};
+use crate::subnets::{SubnetworkId, SUBNETWORK_ID_COINBASE, SUBNETWORK_ID_NATIVE};
+use crate::utxo::{OutPoint, UtxoError, UtxoView};
 
+pub mod address;
+pub mod opcodes;
 pub mod script_public_key;
 
 /// Transaction input.
@@ -27,22 +31,44 @@ pub struct Transaction {
     pub inputs: Vec<TxInput>,
     pub outputs: Vec<TxOutput>,
     pub lock_time: u32,
+    #[serde(default)]
+    pub subnetwork_id: SubnetworkId,
+    #[serde(default)]
+    pub gas: u64,
+    #[serde(default)]
+    pub payload: Vec<u8>,
 }
 
 impl Transaction {
-    /// Creates a new transaction.
+    /// Creates a new transaction in the native subnetwork.
     pub fn new(version: u16, inputs: Vec<TxInput>, outputs: Vec<TxOutput>, lock_time: u32) -> Self {
-        Self { version, inputs, outputs, lock_time }
+        Self { version, inputs, outputs, lock_time, subnetwork_id: SUBNETWORK_ID_NATIVE, gas: 0, payload: vec![] }
     }
 
-    /// Computes the transaction hash.
+    /// Moves the transaction to another subnetwork with the given gas and payload.
+    pub fn with_subnetwork(mut self, subnetwork_id: SubnetworkId, gas: u64, payload: Vec<u8>) -> Self {
+        self.subnetwork_id = subnetwork_id;
+        self.gas = gas;
+        self.payload = payload;
+        self
+    }
+
+    /// Computes the transaction hash, committing to every field including signature scripts.
     pub fn hash(&self) -> Hash {
         let mut data = Vec::new();
+        self.write_fields(&mut data, true);
+        hashing::hash_transaction(&data)
+    }
+
+    /// Writes the hashed fields, optionally leaving out the inputs' signature scripts.
+    fn write_fields(&self, data: &mut Vec<u8>, include_signatures: bool) {
         data.extend_from_slice(&self.version.to_le_bytes());
         for input in &self.inputs {
             data.extend_from_slice(input.prev_tx_hash.as_bytes());
             data.extend_from_slice(&input.index.to_le_bytes());
-            data.extend_from_slice(&input.script_sig);
+            if include_signatures {
+                data.extend_from_slice(&input.script_sig);
+            }
             data.extend_from_slice(&input.sequence.to_le_bytes());
         }
         for output in &self.outputs {
@@ -50,12 +76,50 @@ impl Transaction {
             data.extend_from_slice(&output.script_pubkey);
         }
         data.extend_from_slice(&self.lock_time.to_le_bytes());
+        data.extend_from_slice(self.subnetwork_id.as_ref());
+        data.extend_from_slice(&self.gas.to_le_bytes());
+        data.extend_from_slice(&(self.payload.len() as u64).to_le_bytes());
+        data.extend_from_slice(&self.payload);
+    }
+
+    /// Encodes the transaction as CBOR.
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        ciborium::ser::into_writer(self, &mut buffer).expect("writing CBOR to a Vec cannot fail");
+        buffer
+    }
+
+    /// Decodes a CBOR transaction, accepting only the exact encoding `to_cbor` produces. Unknown
+    /// fields, missing fields, trailing bytes and non-canonical encodings are all rejected, so
+    /// peers can't attach data the transaction hash doesn't commit to.
+    pub fn from_cbor_strict(bytes: &[u8]) -> ConsensusResult<Self> {
+        let invalid = |msg: String| crate::errors::ConsensusError::TransactionValidation { msg };
+        let tx: Self = ciborium::de::from_reader(bytes).map_err(|err| invalid(format!("Malformed CBOR transaction: {}", err)))?;
+        if tx.to_cbor() != bytes {
+            return Err(invalid("Non-canonical CBOR transaction encoding".to_string()));
+        }
+        Ok(tx)
+    }
 
+    /// Returns the transaction id. Unlike [`Transaction::hash`] it leaves out signature scripts,
+    /// so re-signing or malleating a signature doesn't change the id outpoints refer to.
+    pub fn id(&self) -> Hash {
+        let mut data = Vec::new();
+        self.write_fields(&mut data, false);
         hashing::hash_transaction(&data)
     }
 
-    /// Validates the transaction.
+    /// Validates the transaction, limiting the payload to `MAX_TX_PAYLOAD_SIZE`.
     pub fn validate(&self) -> ConsensusResult<()> {
+        self.validate_with_max_payload(crate::constants::MAX_TX_PAYLOAD_SIZE)
+    }
+
+    /// Validates the transaction, limiting the payload to the network's `max_tx_payload_size`.
+    pub fn validate_with_params(&self, params: &Params) -> ConsensusResult<()> {
+        self.validate_with_max_payload(params.max_tx_payload_size)
+    }
+
+    fn validate_with_max_payload(&self, max_payload_size: usize) -> ConsensusResult<()> {
         if self.inputs.is_empty() {
             return Err(crate::errors::ConsensusError::TransactionValidation {
                 msg: "Transaction must have at least one input".to_string(),
@@ -78,23 +142,89 @@ impl Transaction {
             }
         }
 
+        if self.payload.len() > max_payload_size {
+            return Err(crate::errors::ConsensusError::TransactionValidation {
+                msg: format!("Payload of {} bytes exceeds the maximum of {}", self.payload.len(), max_payload_size),
+            });
+        }
+
         // Additional validations (e.g., script validation) can be added
         Ok(())
     }
 
     /// Checks if the transaction is a coinbase transaction.
     pub fn is_coinbase(&self) -> bool {
-        self.inputs.len() == 1 && self.inputs[0].prev_tx_hash == Hash::default()
+        self.inputs.len() == 1 && self.inputs[0].prev_tx_hash.is_null()
+    }
+
+    /// Calculates the fee paid by the transaction: the value of the outputs it spends
+    /// minus the value it creates. Coinbase transactions spend nothing and pay no fee,
+    /// so the view is not consulted for them.
+    pub fn calculate_fee(&self, view: &UtxoView) -> Result<u64, UtxoError> {
+        if self.is_coinbase() {
+            return Ok(0);
+        }
+
+        let mut input_value: u64 = 0;
+        for input in &self.inputs {
+            let outpoint = OutPoint { tx_hash: input.prev_tx_hash, index: input.index };
+            let output = view.get(&outpoint).ok_or(UtxoError::NotFound(TransactionOutpoint {
+                transaction_id: input.prev_tx_hash,
+                index: input.index,
+            }))?;
+            input_value = input_value
+                .checked_add(output.value)
+                .ok_or_else(|| UtxoError::InvalidOutput("Input value overflows".to_string()))?;
+        }
+        let output_value = self
+            .outputs
+            .iter()
+            .try_fold(0u64, |total, output| total.checked_add(output.value))
+            .ok_or_else(|| UtxoError::InvalidOutput("Output value overflows".to_string()))?;
+
+        input_value
+            .checked_sub(output_value)
+            .ok_or_else(|| UtxoError::InvalidOutput(format!("Outputs {} exceed inputs {}", output_value, input_value)))
+    }
+
+    /// Counts the signature operations in all input and output scripts.
+    pub fn sigops(&self) -> u32 {
+        let input_sigops = self.inputs.iter().map(|input| opcodes::count_sigops(&input.script_sig));
+        let output_sigops = self.outputs.iter().map(|output| opcodes::count_sigops(&output.script_pubkey));
+        input_sigops.chain(output_sigops).fold(0, u32::saturating_add)
     }
 
-    /// Calculates the mass of the transaction.
+    /// Estimates the serialized size in bytes: fixed-width fields plus a `u64` length before
+    /// every list, script and the payload.
+    pub fn estimated_serialized_size(&self) -> u64 {
+        // version, input and output counts, lock time, subnetwork id, gas and payload length
+        let header_size = 2 + 8 + 8 + 4 + crate::subnets::SUBNETWORK_ID_SIZE as u64 + 8 + 8 + self.payload.len() as u64;
+        // previous tx hash, index, script length, script and sequence
+        let inputs_size: u64 = self.inputs.iter().map(|input| 32 + 4 + 8 + input.script_sig.len() as u64 + 4).sum();
+        // value, script length and script
+        let outputs_size: u64 = self.outputs.iter().map(|output| 8 + 8 + output.script_pubkey.len() as u64).sum();
+        header_size + inputs_size + outputs_size
+    }
+
+    /// Calculates the mass of the transaction, see [`crate::mass::transaction_mass`].
     pub fn mass(&self) -> u64 {
-        // Simplified mass calculation: base mass + input mass + output mass
-        let base_mass = 100; // Fixed base
-        let input_mass = self.inputs.len() as u64 * 50;
-        let output_mass = self.outputs.len() as u64 * 30;
-        base_mass + input_mass + output_mass
+        crate::mass::transaction_mass(self)
+    }
+}
+
+/// Applies the rules of the transaction's subnetwork: coinbase subnetwork transactions must be
+/// well-formed coinbases, native transactions may not carry gas or a payload, and every other
+/// subnetwork allows them.
+pub fn validate_for_subnet(tx: &Transaction) -> ConsensusResult<()> {
+    if tx.subnetwork_id == SUBNETWORK_ID_COINBASE {
+        return crate::coinbase::validate_coinbase_structure(tx);
     }
+    if tx.subnetwork_id.is_native() && (tx.gas != 0 || !tx.payload.is_empty()) {
+        return Err(crate::errors::ConsensusError::TransactionValidation {
+            msg: "Native subnetwork transactions must not have gas or a payload".to_string(),
+        });
+    }
+    Ok(())
 }
 
 /// Mutable transaction.
@@ -166,6 +296,48 @@ mod tests {
         assert!(tx.validate().is_err());
     }
 
+    #[test]
+    fn test_transaction_validate_payload_size() {
+        let input = TxInput { prev_tx_hash: Hash::from_slice(b"funding"), index: 0, script_sig: vec![], sequence: 0 };
+        let tx = Transaction::new(1, vec![input], vec![TxOutput { value: 1, script_pubkey: vec![] }], 0);
+        let with_payload = |len| tx.clone().with_subnetwork(crate::subnets::SUBNETWORK_ID_REGISTRY, 0, vec![0; len]);
+
+        let max = crate::constants::MAX_TX_PAYLOAD_SIZE;
+        assert_eq!(with_payload(max).validate(), Ok(()));
+        assert_eq!(
+            with_payload(max + 1).validate(),
+            Err(crate::errors::ConsensusError::TransactionValidation {
+                msg: format!("Payload of {} bytes exceeds the maximum of {}", max + 1, max),
+            })
+        );
+
+        let params = Params { max_tx_payload_size: 16, ..Params::default() };
+        assert_eq!(with_payload(16).validate_with_params(&params), Ok(()));
+        assert!(with_payload(17).validate_with_params(&params).is_err());
+    }
+
+    #[test]
+    fn test_estimated_serialized_size() {
+        let tx = Transaction::new(1, vec![], vec![], 0);
+        assert_eq!(tx.estimated_serialized_size(), 58);
+
+        let input = TxInput { prev_tx_hash: Hash::default(), index: 0, script_sig: vec![0; 10], sequence: 0 };
+        let output = TxOutput { value: 1, script_pubkey: vec![0; 5] };
+        let tx = Transaction::new(1, vec![input], vec![output], 0).with_subnetwork(SubnetworkId::from_bytes([1; 20]), 0, vec![0; 3]);
+        assert_eq!(tx.estimated_serialized_size(), 58 + 3 + 58 + 21);
+    }
+
+    #[test]
+    fn test_transaction_sigops() {
+        let hash = Hash::from_le_u64([1, 2, 3, 4]);
+        let input = TxInput { prev_tx_hash: hash, index: 0, script_sig: vec![opcodes::OP_CHECKSIG; 2], sequence: 0 };
+        let outputs = vec![
+            TxOutput { value: 1, script_pubkey: script_public_key::ScriptPublicKey::pay_to_pubkey_hash(&hash).script },
+            TxOutput { value: 1, script_pubkey: vec![opcodes::OP_CHECKMULTISIG] },
+        ];
+        assert_eq!(Transaction::new(1, vec![input], outputs, 0).sigops(), 4);
+    }
+
     #[test]
     fn test_transaction_is_coinbase() {
         let input = TxInput {
@@ -177,4 +349,105 @@ mod tests {
         let tx = Transaction::new(1, vec![input], vec![], 0);
         assert!(tx.is_coinbase());
     }
+
+    #[test]
+    fn test_transaction_id_ignores_signatures() {
+        let input = TxInput { prev_tx_hash: Hash::default(), index: 0, script_sig: vec![], sequence: 0 };
+        let tx = Transaction::new(1, vec![input], vec![TxOutput { value: 1, script_pubkey: vec![] }], 0);
+        let mut signed = tx.clone();
+        signed.inputs[0].script_sig = vec![0x30, 0x44];
+        assert_eq!(tx.id(), signed.id());
+        assert_ne!(tx.hash(), signed.hash());
+    }
+
+    #[test]
+    fn test_transaction_hash_commits_to_payload() {
+        let tx = Transaction::new(1, vec![], vec![], 0);
+        let with_payload = tx.clone().with_subnetwork(crate::subnets::SUBNETWORK_ID_REGISTRY, 0, vec![1]);
+        assert_ne!(tx.hash(), with_payload.hash());
+        assert_ne!(with_payload.hash(), with_payload.clone().with_subnetwork(crate::subnets::SUBNETWORK_ID_REGISTRY, 0, vec![2]).hash());
+    }
+
+    #[test]
+    fn test_validate_for_subnet() {
+        let input = TxInput { prev_tx_hash: Hash::from_le_u64([1, 0, 0, 0]), index: 0, script_sig: vec![], sequence: 0 };
+        let output = TxOutput { value: 100, script_pubkey: vec![] };
+        let native = Transaction::new(1, vec![input], vec![output], 0);
+        assert!(validate_for_subnet(&native).is_ok());
+
+        // Native transactions can't carry a payload or gas
+        let with_payload = native.clone().with_subnetwork(SUBNETWORK_ID_NATIVE, 0, vec![1, 2, 3]);
+        assert!(validate_for_subnet(&with_payload).is_err());
+        let with_gas = native.clone().with_subnetwork(SUBNETWORK_ID_NATIVE, 10, vec![]);
+        assert!(validate_for_subnet(&with_gas).is_err());
+
+        // The coinbase subnetwork requires coinbase shape
+        assert!(validate_for_subnet(&crate::create_coinbase_transaction(50, vec![0x01])).is_ok());
+        let fake_coinbase = native.clone().with_subnetwork(SUBNETWORK_ID_COINBASE, 0, vec![]);
+        assert!(validate_for_subnet(&fake_coinbase).is_err());
+
+        // Other subnetworks allow payloads
+        let registry = native.with_subnetwork(crate::subnets::SUBNETWORK_ID_REGISTRY, 0, vec![1, 2, 3]);
+        assert!(validate_for_subnet(&registry).is_ok());
+    }
+
+    #[test]
+    fn test_from_cbor_strict() {
+        let input = TxInput { prev_tx_hash: Hash::from_le_u64([1, 0, 0, 0]), index: 0, script_sig: vec![1], sequence: 0 };
+        let tx = Transaction::new(1, vec![input], vec![TxOutput { value: 100, script_pubkey: vec![2] }], 0)
+            .with_subnetwork(crate::subnets::SUBNETWORK_ID_REGISTRY, 5, vec![3]);
+        let bytes = tx.to_cbor();
+        assert_eq!(Transaction::from_cbor_strict(&bytes), Ok(tx.clone()));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(matches!(
+            Transaction::from_cbor_strict(&trailing),
+            Err(crate::errors::ConsensusError::TransactionValidation { .. })
+        ));
+    }
+
+    #[test]
+    fn test_from_cbor_strict_rejects_unknown_field() {
+        #[derive(serde::Serialize)]
+        struct Smuggled<'a> {
+            #[serde(flatten)]
+            tx: &'a Transaction,
+            extra: Vec<u8>,
+        }
+
+        let tx = crate::create_coinbase_transaction(50, vec![0x01]);
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&Smuggled { tx: &tx, extra: vec![0xde, 0xad] }, &mut bytes).unwrap();
+
+        // The lenient decoder silently drops the extra key
+        assert_eq!(ciborium::de::from_reader::<Transaction, _>(&bytes[..]).unwrap(), tx);
+        assert!(matches!(
+            Transaction::from_cbor_strict(&bytes),
+            Err(crate::errors::ConsensusError::TransactionValidation { .. })
+        ));
+    }
+
+    #[test]
+    fn test_calculate_fee_coinbase() {
+        // The empty view would fail any lookup, so a coinbase must not consult it
+        let view = UtxoView::new_from_collection(&crate::UtxoCollection::new());
+        let coinbase = crate::create_coinbase_transaction(50, vec![0x01]);
+        assert_eq!(coinbase.calculate_fee(&view), Ok(0));
+    }
+
+    #[test]
+    fn test_calculate_fee() {
+        let collection = crate::UtxoCollection::new();
+        let outpoint = OutPoint { tx_hash: Hash::from_le_u64([1, 0, 0, 0]), index: 0 };
+        collection.insert(outpoint.clone(), TxOutput { value: 100, script_pubkey: vec![] }).unwrap();
+        let view = UtxoView::new_from_collection(&collection);
+
+        let input = TxInput { prev_tx_hash: outpoint.tx_hash, index: 0, script_sig: vec![], sequence: 0 };
+        let tx = Transaction::new(1, vec![input.clone()], vec![TxOutput { value: 90, script_pubkey: vec![] }], 0);
+        assert_eq!(tx.calculate_fee(&view), Ok(10));
+
+        let overspend = Transaction::new(1, vec![input], vec![TxOutput { value: 110, script_pubkey: vec![] }], 0);
+        assert!(matches!(overspend.calculate_fee(&view), Err(UtxoError::InvalidOutput(_))));
+    }
 }
@@ -0,0 +1,154 @@
+//! Bech32m addresses (BIP-350).
+//!
+//! An address is the network's prefix, the separator `1`, and the 5-bit encoding of a version
+//! byte followed by the script's key or script hash, closed by a six character checksum.
+
+use std::fmt;
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const SEPARATOR: char = '1';
+const CHECKSUM_LEN: usize = 6;
+const BECH32M_CONST: u32 = 0x2bc8_30a3;
+
+/// Address payload version of pay-to-pubkey-hash scripts.
+pub const VERSION_PUBKEY_HASH: u8 = 0;
+/// Address payload version of pay-to-pubkey scripts.
+pub const VERSION_PUBKEY: u8 = 1;
+/// Address payload version of pay-to-script-hash scripts.
+pub const VERSION_SCRIPT_HASH: u8 = 8;
+
+/// Errors raised when decoding an address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+    /// The separator is missing, or the prefix or checksum is empty.
+    MissingParts,
+    /// Upper and lower case characters are mixed.
+    MixedCase,
+    /// A character outside the bech32 alphabet.
+    InvalidChar(char),
+    /// The checksum doesn't match.
+    InvalidChecksum,
+    /// The prefix doesn't belong to any known network.
+    UnknownPrefix(String),
+    /// The data doesn't decode to whole bytes.
+    InvalidPadding,
+    /// The payload version or length doesn't match a supported script.
+    InvalidPayload { version: u8, len: usize },
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressError::MissingParts => write!(f, "Address must be a prefix, '1' and a checksummed payload"),
+            AddressError::MixedCase => write!(f, "Address mixes upper and lower case"),
+            AddressError::InvalidChar(c) => write!(f, "Invalid address character {:?}", c),
+            AddressError::InvalidChecksum => write!(f, "Invalid address checksum"),
+            AddressError::UnknownPrefix(prefix) => write!(f, "Unknown address prefix {:?}", prefix),
+            AddressError::InvalidPadding => write!(f, "Invalid address padding"),
+            AddressError::InvalidPayload { version, len } => {
+                write!(f, "Unsupported address payload: version {} with {} bytes", version, len)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+fn polymod(values: impl Iterator<Item = u8>) -> u32 {
+    const GENERATORS: [u32; 5] = [0x3b6a_57b2, 0x2650_8e6d, 0x1ea1_19fa, 0x3d42_33dd, 0x2a14_62b3];
+    values.fold(1, |checksum, value| {
+        let top = checksum >> 25;
+        let checksum = ((checksum & 0x01ff_ffff) << 5) ^ value as u32;
+        GENERATORS.iter().enumerate().filter(|(i, _)| (top >> i) & 1 == 1).fold(checksum, |acc, (_, generator)| acc ^ generator)
+    })
+}
+
+fn hrp_expand(hrp: &str) -> impl Iterator<Item = u8> + '_ {
+    hrp.bytes().map(|c| c >> 5).chain(std::iter::once(0)).chain(hrp.bytes().map(|c| c & 31))
+}
+
+/// Regroups `data` from `from`-bit to `to`-bit values. Without padding, leftover bits must be
+/// fewer than `from` and all zero.
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+    let (mut acc, mut bits) = (0u32, 0u32);
+    let mut out = Vec::with_capacity(data.len() * from as usize / to as usize + 1);
+    for &value in data {
+        acc = (acc << from) | value as u32;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            out.push(((acc >> bits) & ((1 << to) - 1)) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            out.push(((acc << (to - bits)) & ((1 << to) - 1)) as u8);
+        }
+    } else if bits >= from || (acc << (to - bits)) & ((1 << to) - 1) != 0 {
+        return None;
+    }
+    Some(out)
+}
+
+/// Encodes `payload` as a bech32m string with the human-readable part `hrp`.
+pub fn encode(hrp: &str, payload: &[u8]) -> String {
+    let data = convert_bits(payload, 8, 5, true).expect("padding always succeeds");
+    let checksum = polymod(hrp_expand(hrp).chain(data.iter().copied()).chain([0; CHECKSUM_LEN])) ^ BECH32M_CONST;
+
+    let mut address = String::with_capacity(hrp.len() + 1 + data.len() + CHECKSUM_LEN);
+    address.push_str(hrp);
+    address.push(SEPARATOR);
+    address.extend(data.iter().map(|&value| CHARSET[value as usize] as char));
+    address.extend((0..CHECKSUM_LEN).map(|i| CHARSET[((checksum >> (5 * (5 - i))) & 31) as usize] as char));
+    address
+}
+
+/// Decodes a bech32m string into its lowercase human-readable part and payload bytes.
+pub fn decode(address: &str) -> Result<(String, Vec<u8>), AddressError> {
+    if address.chars().any(|c| c.is_ascii_lowercase()) && address.chars().any(|c| c.is_ascii_uppercase()) {
+        return Err(AddressError::MixedCase);
+    }
+    let address = address.to_ascii_lowercase();
+    let (hrp, data) = address.rsplit_once(SEPARATOR).ok_or(AddressError::MissingParts)?;
+    if hrp.is_empty() || data.len() < CHECKSUM_LEN {
+        return Err(AddressError::MissingParts);
+    }
+    if let Some(c) = hrp.chars().find(|c| !(33..=126).contains(&(*c as u32))) {
+        return Err(AddressError::InvalidChar(c));
+    }
+
+    let values = data
+        .chars()
+        .map(|c| CHARSET.iter().position(|&symbol| symbol as char == c).map(|value| value as u8).ok_or(AddressError::InvalidChar(c)))
+        .collect::<Result<Vec<u8>, _>>()?;
+    if polymod(hrp_expand(hrp).chain(values.iter().copied())) != BECH32M_CONST {
+        return Err(AddressError::InvalidChecksum);
+    }
+    let payload = convert_bits(&values[..values.len() - CHECKSUM_LEN], 5, 8, false).ok_or(AddressError::InvalidPadding)?;
+    Ok((hrp.to_string(), payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bip350_vectors() {
+        assert_eq!(decode("a1lqfn3a"), Ok(("a".to_string(), vec![])));
+        assert_eq!(decode("A1LQFN3A"), Ok(("a".to_string(), vec![])));
+        assert_eq!(encode("a", &[]), "a1lqfn3a");
+        assert_eq!(decode("a1lqfn3q"), Err(AddressError::InvalidChecksum));
+        assert_eq!(decode("A1lqfn3a"), Err(AddressError::MixedCase));
+        assert_eq!(decode("lqfn3a"), Err(AddressError::MissingParts));
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        for len in 0..40u8 {
+            let payload: Vec<u8> = (0..len).map(|i| i.wrapping_mul(37)).collect();
+            let address = encode("jiotest", &payload);
+            assert_eq!(decode(&address), Ok(("jiotest".to_string(), payload)));
+        }
+        assert_eq!(decode("jio1bqqqqqq"), Err(AddressError::InvalidChar('b')));
+    }
+}
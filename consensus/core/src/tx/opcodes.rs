@@ -0,0 +1,212 @@
+//! Script opcodes.
+//!
+//! A structured view over the raw script bytes: data pushes carry their payload and the opcodes
+//! standard scripts are built from get their own variants. Anything else is kept as `Other`.
+
+use crate::errors::{ConsensusError, ConsensusResult};
+
+pub const OP_0: u8 = 0x00;
+pub const OP_PUSHBYTES_75: u8 = 0x4b;
+pub const OP_PUSHDATA1: u8 = 0x4c;
+pub const OP_PUSHDATA2: u8 = 0x4d;
+pub const OP_PUSHDATA4: u8 = 0x4e;
+pub const OP_RETURN: u8 = 0x6a;
+pub const OP_DUP: u8 = 0x76;
+pub const OP_EQUAL: u8 = 0x87;
+pub const OP_EQUALVERIFY: u8 = 0x88;
+pub const OP_HASH160: u8 = 0xa9;
+pub const OP_CHECKSIG: u8 = 0xac;
+pub const OP_CHECKSIGVERIFY: u8 = 0xad;
+pub const OP_CHECKMULTISIG: u8 = 0xae;
+pub const OP_CHECKMULTISIGVERIFY: u8 = 0xaf;
+
+/// A single script operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    /// `OP_0` or `OP_PUSHBYTES_N`, pushing up to 75 bytes given by the opcode itself.
+    PushBytes(Vec<u8>),
+    /// `OP_PUSHDATA1`, pushing data with a one-byte length prefix.
+    PushData1(Vec<u8>),
+    /// `OP_PUSHDATA2`, pushing data with a two-byte little-endian length prefix.
+    PushData2(Vec<u8>),
+    /// `OP_PUSHDATA4`, pushing data with a four-byte little-endian length prefix.
+    PushData4(Vec<u8>),
+    Dup,
+    Hash160,
+    Equal,
+    EqualVerify,
+    CheckSig,
+    Return,
+    /// Any opcode without a dedicated variant.
+    Other(u8),
+}
+
+impl Op {
+    /// Creates the smallest push operation for `data`.
+    pub fn push(data: &[u8]) -> Self {
+        match data.len() {
+            len if len <= OP_PUSHBYTES_75 as usize => Op::PushBytes(data.to_vec()),
+            len if len <= u8::MAX as usize => Op::PushData1(data.to_vec()),
+            len if len <= u16::MAX as usize => Op::PushData2(data.to_vec()),
+            _ => Op::PushData4(data.to_vec()),
+        }
+    }
+
+    /// Appends the encoded operation to `script`. Push payloads must fit their variant's length
+    /// prefix, as produced by [`Op::push`] or [`parse_ops`].
+    pub fn encode(&self, script: &mut Vec<u8>) {
+        match self {
+            Op::PushBytes(data) => {
+                debug_assert!(data.len() <= OP_PUSHBYTES_75 as usize);
+                script.push(data.len() as u8);
+                script.extend_from_slice(data);
+            }
+            Op::PushData1(data) => {
+                script.extend_from_slice(&[OP_PUSHDATA1, data.len() as u8]);
+                script.extend_from_slice(data);
+            }
+            Op::PushData2(data) => {
+                script.push(OP_PUSHDATA2);
+                script.extend_from_slice(&(data.len() as u16).to_le_bytes());
+                script.extend_from_slice(data);
+            }
+            Op::PushData4(data) => {
+                script.push(OP_PUSHDATA4);
+                script.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                script.extend_from_slice(data);
+            }
+            Op::Dup => script.push(OP_DUP),
+            Op::Hash160 => script.push(OP_HASH160),
+            Op::Equal => script.push(OP_EQUAL),
+            Op::EqualVerify => script.push(OP_EQUALVERIFY),
+            Op::CheckSig => script.push(OP_CHECKSIG),
+            Op::Return => script.push(OP_RETURN),
+            Op::Other(opcode) => script.push(*opcode),
+        }
+    }
+
+    /// Decodes the operation at the start of `script`, returning it with the remaining bytes.
+    pub fn decode(script: &[u8]) -> ConsensusResult<(Op, &[u8])> {
+        let (&opcode, rest) = script.split_first().ok_or_else(|| truncated("opcode"))?;
+        let op = match opcode {
+            OP_0..=OP_PUSHBYTES_75 => {
+                let (data, rest) = take(rest, opcode as usize)?;
+                return Ok((Op::PushBytes(data.to_vec()), rest));
+            }
+            OP_PUSHDATA1 => {
+                let (len, rest) = take(rest, 1)?;
+                let (data, rest) = take(rest, len[0] as usize)?;
+                return Ok((Op::PushData1(data.to_vec()), rest));
+            }
+            OP_PUSHDATA2 => {
+                let (len, rest) = take(rest, 2)?;
+                let (data, rest) = take(rest, u16::from_le_bytes([len[0], len[1]]) as usize)?;
+                return Ok((Op::PushData2(data.to_vec()), rest));
+            }
+            OP_PUSHDATA4 => {
+                let (len, rest) = take(rest, 4)?;
+                let (data, rest) = take(rest, u32::from_le_bytes(len.try_into().unwrap()) as usize)?;
+                return Ok((Op::PushData4(data.to_vec()), rest));
+            }
+            OP_DUP => Op::Dup,
+            OP_HASH160 => Op::Hash160,
+            OP_EQUAL => Op::Equal,
+            OP_EQUALVERIFY => Op::EqualVerify,
+            OP_CHECKSIG => Op::CheckSig,
+            OP_RETURN => Op::Return,
+            other => Op::Other(other),
+        };
+        Ok((op, rest))
+    }
+}
+
+fn truncated(what: &str) -> ConsensusError {
+    ConsensusError::ScriptValidation { msg: format!("Script ends inside {}", what) }
+}
+
+fn take(data: &[u8], len: usize) -> ConsensusResult<(&[u8], &[u8])> {
+    if data.len() < len {
+        return Err(truncated("a data push"));
+    }
+    Ok(data.split_at(len))
+}
+
+/// Parses a script into its operations, failing if a push runs past the end of the script.
+pub fn parse_ops(mut script: &[u8]) -> ConsensusResult<Vec<Op>> {
+    let mut ops = Vec::new();
+    while !script.is_empty() {
+        let (op, rest) = Op::decode(script)?;
+        ops.push(op);
+        script = rest;
+    }
+    Ok(ops)
+}
+
+/// Counts the signature operations in a script, one per `OP_CHECKSIG`, `OP_CHECKSIGVERIFY`,
+/// `OP_CHECKMULTISIG` or `OP_CHECKMULTISIGVERIFY`. Counting stops where a truncated push makes
+/// the rest of the script unparsable; data pushes are never counted.
+pub fn count_sigops(mut script: &[u8]) -> u32 {
+    let mut sigops = 0u32;
+    while let Ok((op, rest)) = Op::decode(script) {
+        if matches!(op, Op::CheckSig | Op::Other(OP_CHECKSIGVERIFY | OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY)) {
+            sigops += 1;
+        }
+        script = rest;
+    }
+    sigops
+}
+
+/// Encodes a sequence of operations into a script.
+pub fn encode_ops(ops: &[Op]) -> Vec<u8> {
+    let mut script = Vec::new();
+    ops.iter().for_each(|op| op.encode(&mut script));
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tx::script_public_key::ScriptPublicKey, Hash};
+
+    #[test]
+    fn test_p2pkh_ops_round_trip() {
+        let hash = Hash::from_le_u64([1, 2, 3, 4]);
+        let script = ScriptPublicKey::pay_to_pubkey_hash(&hash).script;
+        let ops = parse_ops(&script).unwrap();
+        assert_eq!(ops, vec![Op::Dup, Op::Hash160, Op::PushBytes(hash.as_bytes().to_vec()), Op::EqualVerify, Op::CheckSig]);
+        assert_eq!(encode_ops(&ops), script);
+    }
+
+    #[test]
+    fn test_push_data_round_trip() {
+        let ops = vec![Op::push(&[]), Op::push(&[7; 76]), Op::push(&[8; 256]), Op::Return, Op::Other(0x51)];
+        assert_eq!(ops[0], Op::PushBytes(vec![]));
+        assert!(matches!(ops[1], Op::PushData1(_)));
+        assert!(matches!(ops[2], Op::PushData2(_)));
+        assert_eq!(parse_ops(&encode_ops(&ops)).unwrap(), ops);
+
+        let mut long = Vec::new();
+        Op::PushData4(vec![9; 3]).encode(&mut long);
+        assert_eq!(long, vec![OP_PUSHDATA4, 3, 0, 0, 0, 9, 9, 9]);
+        assert_eq!(parse_ops(&long).unwrap(), vec![Op::PushData4(vec![9; 3])]);
+    }
+
+    #[test]
+    fn test_count_sigops() {
+        let hash = Hash::from_le_u64([1, 2, 3, 4]);
+        assert_eq!(count_sigops(&ScriptPublicKey::pay_to_pubkey_hash(&hash).script), 1);
+        assert_eq!(count_sigops(&[OP_CHECKSIG, OP_CHECKSIGVERIFY, OP_CHECKMULTISIG, OP_CHECKMULTISIGVERIFY, OP_DUP]), 4);
+
+        // Pushed bytes aren't opcodes, and a truncated push ends the count
+        assert_eq!(count_sigops(&encode_ops(&[Op::push(&[OP_CHECKSIG; 10]), Op::CheckSig])), 1);
+        assert_eq!(count_sigops(&[OP_CHECKSIG, 0x05, OP_CHECKSIG]), 1);
+        assert_eq!(count_sigops(&[]), 0);
+    }
+
+    #[test]
+    fn test_parse_ops_truncated() {
+        assert!(parse_ops(&[0x20, 0x01]).is_err());
+        assert!(parse_ops(&[OP_PUSHDATA2, 0x01]).is_err());
+        assert_eq!(parse_ops(&[]).unwrap(), vec![]);
+    }
+}
@@ -1,6 +1,8 @@
 //! Script public key for transaction outputs.
 
-use crate::{hashing, Hash, errors::ConsensusResult};
+use super::address::{self, AddressError, VERSION_PUBKEY, VERSION_PUBKEY_HASH, VERSION_SCRIPT_HASH};
+use super::opcodes::{encode_ops, parse_ops, Op, OP_RETURN};
+use crate::{hashing, network::NetworkId, Hash, errors::ConsensusResult};
 
 /// Script public key types.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -15,6 +17,12 @@ pub enum ScriptPublicKeyType {
     Unknown,
 }
 
+/// Checks whether a script can never be spent: `OP_RETURN` data carriers and scripts longer than
+/// `MAX_SCRIPT_SIZE`. Outputs locked by such scripts are kept out of the UTXO set.
+pub fn is_unspendable_script(script: &[u8]) -> bool {
+    script.first() == Some(&OP_RETURN) || script.len() > crate::constants::MAX_SCRIPT_SIZE
+}
+
 /// Script public key.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ScriptPublicKey {
@@ -30,10 +38,23 @@ impl ScriptPublicKey {
 
     /// Creates a pay-to-pubkey-hash script.
     pub fn pay_to_pubkey_hash(pubkey_hash: &Hash) -> Self {
-        let mut script = vec![0x76, 0xa9, 0x20]; // OP_DUP OP_HASH160 OP_PUSHBYTES_32
-        script.extend_from_slice(pubkey_hash.as_bytes());
-        script.extend_from_slice(&[0x88, 0xac]); // OP_EQUALVERIFY OP_CHECKSIG
-        Self::new(script, 0)
+        let ops = [Op::Dup, Op::Hash160, Op::push(pubkey_hash.as_bytes()), Op::EqualVerify, Op::CheckSig];
+        Self::new(encode_ops(&ops), 0)
+    }
+
+    /// Creates a pay-to-script-hash script.
+    pub fn pay_to_script_hash(script_hash: &[u8; 20]) -> Self {
+        Self::new(encode_ops(&[Op::Hash160, Op::push(script_hash), Op::Equal]), 0)
+    }
+
+    /// Creates a pay-to-pubkey script for a 33-byte compressed or 65-byte uncompressed key.
+    pub fn pay_to_pubkey(pubkey: &[u8]) -> Self {
+        Self::new(encode_ops(&[Op::push(pubkey), Op::CheckSig]), 0)
+    }
+
+    /// Parses the script into its operations.
+    pub fn ops(&self) -> ConsensusResult<Vec<Op>> {
+        parse_ops(&self.script)
     }
 
     /// Gets the script type.
@@ -73,6 +94,11 @@ impl ScriptPublicKey {
         (self.script.last() == Some(&0xac)) // OP_CHECKSIG
     }
 
+    /// Checks whether the script can never be spent, see [`is_unspendable_script`].
+    pub fn is_unspendable(&self) -> bool {
+        is_unspendable_script(&self.script)
+    }
+
     /// Extracts the pubkey hash from a P2PKH script.
     pub fn pubkey_hash(&self) -> Option<Hash> {
         if self.is_pay_to_pubkey_hash() {
@@ -82,6 +108,41 @@ impl ScriptPublicKey {
         }
     }
 
+    /// Extracts the script hash from a P2SH script.
+    pub fn script_hash_20(&self) -> Option<[u8; 20]> {
+        self.is_pay_to_script_hash().then(|| self.script[2..22].try_into().unwrap())
+    }
+
+    /// Renders the script as a bech32m address for `network`, or `None` for non-standard scripts.
+    pub fn address_string(&self, network: NetworkId) -> Option<String> {
+        let (version, data) = match self.script_type() {
+            ScriptPublicKeyType::PayToPubkeyHash => (VERSION_PUBKEY_HASH, &self.script[3..35]),
+            ScriptPublicKeyType::PayToScriptHash => (VERSION_SCRIPT_HASH, &self.script[2..22]),
+            // The length check alone doesn't pin the push opcode
+            ScriptPublicKeyType::PayToPubkey if self.script[0] as usize == self.script.len() - 2 => {
+                (VERSION_PUBKEY, &self.script[1..self.script.len() - 1])
+            }
+            _ => return None,
+        };
+        let mut payload = Vec::with_capacity(1 + data.len());
+        payload.push(version);
+        payload.extend_from_slice(data);
+        Some(address::encode(network.address_prefix(), &payload))
+    }
+
+    /// Parses a bech32m address of any known network back into its script.
+    pub fn from_address(addr: &str) -> Result<Self, AddressError> {
+        let (prefix, payload) = address::decode(addr)?;
+        NetworkId::from_address_prefix(&prefix).ok_or(AddressError::UnknownPrefix(prefix))?;
+        match payload.split_first() {
+            Some((&VERSION_PUBKEY_HASH, data)) if data.len() == 32 => Ok(Self::pay_to_pubkey_hash(&Hash::from_slice(data))),
+            Some((&VERSION_SCRIPT_HASH, data)) if data.len() == 20 => Ok(Self::pay_to_script_hash(data.try_into().unwrap())),
+            Some((&VERSION_PUBKEY, data)) if data.len() == 33 || data.len() == 65 => Ok(Self::pay_to_pubkey(data)),
+            Some((&version, data)) => Err(AddressError::InvalidPayload { version, len: data.len() }),
+            None => Err(AddressError::InvalidPayload { version: 0, len: 0 }),
+        }
+    }
+
     /// Validates the script (basic checks).
     pub fn validate(&self) -> ConsensusResult<()> {
         if self.script.is_empty() {
@@ -123,4 +184,62 @@ mod tests {
         let script = ScriptPublicKey::new(vec![], 0);
         assert!(script.validate().is_err());
     }
+
+    #[test]
+    fn test_is_unspendable() {
+        assert!(ScriptPublicKey::new(encode_ops(&[Op::Return, Op::push(b"data")]), 0).is_unspendable());
+        assert!(ScriptPublicKey::new(vec![0x51; crate::constants::MAX_SCRIPT_SIZE + 1], 0).is_unspendable());
+        assert!(!ScriptPublicKey::new(vec![0x51; crate::constants::MAX_SCRIPT_SIZE], 0).is_unspendable());
+        assert!(!ScriptPublicKey::pay_to_pubkey_hash(&Hash::default()).is_unspendable());
+        assert!(!ScriptPublicKey::new(vec![], 0).is_unspendable());
+    }
+
+    #[test]
+    fn test_script_hash_20() {
+        let script = ScriptPublicKey::pay_to_script_hash(&[7; 20]);
+        assert_eq!(script.script_type(), ScriptPublicKeyType::PayToScriptHash);
+        assert_eq!(script.script_hash_20(), Some([7; 20]));
+        assert_eq!(ScriptPublicKey::pay_to_pubkey_hash(&Hash::default()).script_hash_20(), None);
+    }
+
+    #[test]
+    fn test_address_round_trip() {
+        let mut pubkey = vec![0x02];
+        pubkey.extend_from_slice(&[9; 32]);
+        let scripts = [
+            ScriptPublicKey::pay_to_pubkey_hash(&Hash::from_le_u64([1, 2, 3, 4])),
+            ScriptPublicKey::pay_to_script_hash(&[7; 20]),
+            ScriptPublicKey::pay_to_pubkey(&pubkey),
+        ];
+        for network in NetworkId::ALL {
+            for script in &scripts {
+                let address = script.address_string(network).unwrap();
+                assert!(address.starts_with(&format!("{}1", network.address_prefix())));
+                assert_eq!(ScriptPublicKey::from_address(&address).as_ref(), Ok(script));
+                assert_eq!(ScriptPublicKey::from_address(&address.to_uppercase()).as_ref(), Ok(script));
+            }
+        }
+
+        let address = scripts[0].address_string(NetworkId::Mainnet).unwrap();
+        assert_ne!(address, scripts[0].address_string(NetworkId::Testnet).unwrap());
+        assert_eq!(ScriptPublicKey::new(vec![0x51], 0).address_string(NetworkId::Mainnet), None);
+    }
+
+    #[test]
+    fn test_from_address_errors() {
+        assert_eq!(
+            ScriptPublicKey::from_address(&address::encode("btc", &[VERSION_SCRIPT_HASH; 21])),
+            Err(AddressError::UnknownPrefix("btc".to_string()))
+        );
+        assert_eq!(
+            ScriptPublicKey::from_address(&address::encode("jio", &[VERSION_SCRIPT_HASH; 5])),
+            Err(AddressError::InvalidPayload { version: VERSION_SCRIPT_HASH, len: 4 })
+        );
+
+        let mut corrupted = ScriptPublicKey::pay_to_script_hash(&[7; 20]).address_string(NetworkId::Devnet).unwrap();
+        let last = if corrupted.ends_with('q') { 'p' } else { 'q' };
+        corrupted.pop();
+        corrupted.push(last);
+        assert_eq!(ScriptPublicKey::from_address(&corrupted), Err(AddressError::InvalidChecksum));
+    }
 }
@@ -8,4 +8,4 @@ pub use utxo_collection::{UtxoCollection, OutPoint, Utxo};
 pub use utxo_diff::UtxoDiff;
 pub use utxo_error::UtxoError;
 pub use utxo_inquirer::{UtxoInquirer, UtxoInquirerError};
-pub use utxo_view::UtxoView;
+pub use utxo_view::{TxValidationStatus, UtxoView};
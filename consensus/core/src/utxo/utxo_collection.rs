@@ -7,7 +7,7 @@ use crate::muhash::MuHash;
 use super::utxo_error::UtxoError;
 
 /// OutPoint representing a transaction output reference.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct OutPoint {
     pub tx_hash: crate::Hash,
     pub index: u32,
@@ -20,11 +20,38 @@ pub struct Utxo {
     pub output: TxOutput,
 }
 
+/// Hashes the canonical serialization of a UTXO (tx hash, index, value and length-prefixed
+/// script public key), which is the element committed to by the collection's MuHash.
+pub(crate) fn utxo_commitment(outpoint: &OutPoint, output: &TxOutput) -> crate::Hash {
+    let mut data = Vec::with_capacity(32 + 4 + 8 + 8 + output.script_pubkey.len());
+    data.extend_from_slice(outpoint.tx_hash.as_bytes());
+    data.extend_from_slice(&outpoint.index.to_le_bytes());
+    data.extend_from_slice(&output.value.to_le_bytes());
+    data.extend_from_slice(&(output.script_pubkey.len() as u64).to_le_bytes());
+    data.extend_from_slice(&output.script_pubkey);
+    crate::hashing::hash_data(&data)
+}
+
+/// A UTXO as stored by the serialized form of a `UtxoCollection`.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct StoredUtxo {
+    outpoint: OutPoint,
+    output: TxOutput,
+    coinbase_daa_score: Option<u64>,
+}
+
 /// Thread-safe UTXO collection.
+///
+/// Its serialized form holds only the UTXO entries, sorted by outpoint; the MuHash and total
+/// value are derived state and are rebuilt by [`UtxoCollection::deserialize_from`].
 #[derive(Debug, Clone)]
 pub struct UtxoCollection {
     pub(crate) utxos: Arc<RwLock<HashMap<OutPoint, TxOutput>>>,
     muhash: Arc<RwLock<MuHash>>,
+    /// Sum of all output values, kept in step with `utxos`
+    total_value: Arc<RwLock<u64>>,
+    /// DAA score of the block that created each unspent coinbase output
+    pub(crate) coinbase_daa_scores: Arc<RwLock<HashMap<OutPoint, u64>>>,
 }
 
 impl UtxoCollection {
@@ -33,6 +60,8 @@ impl UtxoCollection {
         Self {
             utxos: Arc::new(RwLock::new(HashMap::new())),
             muhash: Arc::new(RwLock::new(MuHash::new())),
+            total_value: Arc::new(RwLock::new(0)),
+            coinbase_daa_scores: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
@@ -45,9 +74,21 @@ impl UtxoCollection {
                 index: outpoint.index,
             }));
         }
-        utxos.insert(outpoint.clone(), output.clone());
+        let mut total_value = self.total_value.write().unwrap();
+        *total_value = total_value
+            .checked_add(output.value)
+            .ok_or_else(|| UtxoError::InvalidOutput("Total UTXO value overflows".to_string()))?;
         let mut muhash = self.muhash.write().unwrap();
-        muhash.add(&outpoint.tx_hash); // Simplified: hash tx_hash
+        muhash.add(&utxo_commitment(&outpoint, &output));
+        utxos.insert(outpoint, output);
+        Ok(())
+    }
+
+    /// Inserts a coinbase UTXO created by a block with `block_daa_score`, so spends of it can be
+    /// held back until it matures.
+    pub fn insert_coinbase(&self, outpoint: OutPoint, output: TxOutput, block_daa_score: u64) -> Result<(), UtxoError> {
+        self.insert(outpoint.clone(), output)?;
+        self.coinbase_daa_scores.write().unwrap().insert(outpoint, block_daa_score);
         Ok(())
     }
 
@@ -55,19 +96,48 @@ impl UtxoCollection {
     pub fn remove(&self, outpoint: &OutPoint) -> Result<Option<TxOutput>, UtxoError> {
         let mut utxos = self.utxos.write().unwrap();
         let output = utxos.remove(outpoint);
-        if output.is_some() {
+        if let Some(output) = &output {
             let mut muhash = self.muhash.write().unwrap();
-            muhash.remove(&outpoint.tx_hash);
+            muhash.remove(&utxo_commitment(outpoint, output));
+            *self.total_value.write().unwrap() -= output.value;
+            self.coinbase_daa_scores.write().unwrap().remove(outpoint);
         }
         Ok(output)
     }
 
+    /// Returns the DAA score of the block that created `outpoint`, if it is an unspent coinbase output.
+    pub fn coinbase_daa_score(&self, outpoint: &OutPoint) -> Option<u64> {
+        self.coinbase_daa_scores.read().unwrap().get(outpoint).copied()
+    }
+
     /// Gets a UTXO.
     pub fn get(&self, outpoint: &OutPoint) -> Option<TxOutput> {
         let utxos = self.utxos.read().unwrap();
         utxos.get(outpoint).cloned()
     }
 
+    /// Returns the UTXOs locked to `script_pubkey`, in no particular order. Their values sum to
+    /// the collection's `get_balance` for the same script.
+    pub fn utxos_for_script(&self, script_pubkey: &[u8]) -> Vec<(OutPoint, TxOutput)> {
+        let utxos = self.utxos.read().unwrap();
+        utxos
+            .iter()
+            .filter(|(_, output)| output.script_pubkey == script_pubkey)
+            .map(|(outpoint, output)| (outpoint.clone(), output.clone()))
+            .collect()
+    }
+
+    /// Iterates over the UTXOs locked to `script_pubkey`. Matches are copied out first, so no
+    /// lock is held while iterating.
+    pub fn iter_for_script(&self, script_pubkey: &[u8]) -> impl Iterator<Item = (OutPoint, TxOutput)> {
+        self.utxos_for_script(script_pubkey).into_iter()
+    }
+
+    /// Returns the sum of all output values in the collection.
+    pub fn total_value(&self) -> u64 {
+        *self.total_value.read().unwrap()
+    }
+
     /// Gets the length.
     pub fn len(&self) -> usize {
         let utxos = self.utxos.read().unwrap();
@@ -79,22 +149,130 @@ impl UtxoCollection {
         self.len() == 0
     }
 
-    /// Applies a diff.
+    /// Removes all UTXOs and resets the MuHash, e.g. before reindexing.
+    pub fn clear(&self) {
+        // Hold both locks so readers never observe a cleared map with a stale MuHash
+        let mut utxos = self.utxos.write().unwrap();
+        let mut muhash = self.muhash.write().unwrap();
+        utxos.clear();
+        *muhash = MuHash::new();
+        *self.total_value.write().unwrap() = 0;
+        self.coinbase_daa_scores.write().unwrap().clear();
+    }
+
+    /// Applies a diff. Removals of outputs the collection already holds go first, so a diff may
+    /// replace an outpoint's output; the rest follow the additions, so outputs both created and
+    /// spent by the diff cancel out. A diff removing an outpoint that is neither held nor added, or
+    /// carrying a different output than the one held, is rejected before anything changes.
     pub fn apply_diff(&self, diff: &super::utxo_diff::UtxoDiff) -> Result<(), UtxoError> {
+        self.check_removals(diff)?;
+        let (existing, created): (Vec<_>, Vec<_>) = diff.removed.iter().partition(|(outpoint, _)| self.get(outpoint).is_some());
+        for (outpoint, _) in existing {
+            self.remove(outpoint)?;
+        }
         for (outpoint, output) in &diff.added {
             self.insert(outpoint.clone(), output.clone())?;
         }
-        for outpoint in &diff.removed {
+        for (outpoint, _) in created {
             self.remove(outpoint)?;
         }
         Ok(())
     }
 
+    /// Checks that every output `diff` removes is the one the collection holds at that outpoint, or,
+    /// for an outpoint the collection doesn't hold, one the diff itself adds.
+    fn check_removals(&self, diff: &super::utxo_diff::UtxoDiff) -> Result<(), UtxoError> {
+        let utxos = self.utxos.read().unwrap();
+        for (outpoint, output) in &diff.removed {
+            let expected = match utxos.get(outpoint) {
+                Some(stored) => stored,
+                None => match diff.added.iter().find(|(added, _)| added == outpoint) {
+                    Some((_, added)) => added,
+                    None => return Err(UtxoError::NotFound(TransactionOutpoint { transaction_id: outpoint.tx_hash, index: outpoint.index })),
+                },
+            };
+            if expected != output {
+                return Err(UtxoError::DiffApplicationFailed(format!("removed output for {:?} does not match the unspent one", outpoint)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the diff turning `self` into `other`: outputs only `other` holds are added, outputs
+    /// only `self` holds are removed, and outputs that differ are both. Entries are sorted by
+    /// outpoint. Coinbase DAA scores aren't part of a diff and are not carried over.
+    pub fn diff_against(&self, other: &UtxoCollection) -> super::utxo_diff::UtxoDiff {
+        let mut diff = super::utxo_diff::UtxoDiff::new();
+        {
+            let ours = self.utxos.read().unwrap();
+            let theirs = other.utxos.read().unwrap();
+            for (outpoint, output) in ours.iter().filter(|(outpoint, output)| theirs.get(*outpoint) != Some(*output)) {
+                diff.remove(outpoint.clone(), output.clone());
+            }
+            for (outpoint, output) in theirs.iter().filter(|(outpoint, output)| ours.get(*outpoint) != Some(*output)) {
+                diff.add(outpoint.clone(), output.clone());
+            }
+        }
+        for entries in [&mut diff.added, &mut diff.removed] {
+            entries.sort_unstable_by_key(|(outpoint, _)| (outpoint.tx_hash, outpoint.index));
+        }
+        diff
+    }
+
     /// Gets the MuHash.
     pub fn muhash(&self) -> crate::Hash {
         let muhash = self.muhash.read().unwrap();
         muhash.finalize()
     }
+
+    /// Computes the MuHash the collection would have after applying `diff`, without mutating it.
+    /// Rejects the same diffs as [`Self::apply_diff`].
+    pub fn commitment_after(&self, diff: &super::utxo_diff::UtxoDiff) -> Result<crate::Hash, UtxoError> {
+        self.check_removals(diff)?;
+        let mut muhash = self.muhash.read().unwrap().clone();
+        muhash.combine(&MuHash::of_outputs(&diff.added, &diff.removed));
+        Ok(muhash.finalize())
+    }
+}
+
+impl serde::Serialize for UtxoCollection {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let utxos = self.utxos.read().unwrap();
+        let coinbase_daa_scores = self.coinbase_daa_scores.read().unwrap();
+        let mut entries: Vec<StoredUtxo> = utxos
+            .iter()
+            .map(|(outpoint, output)| StoredUtxo {
+                outpoint: outpoint.clone(),
+                output: output.clone(),
+                coinbase_daa_score: coinbase_daa_scores.get(outpoint).copied(),
+            })
+            .collect();
+        entries.sort_unstable_by_key(|entry| (entry.outpoint.tx_hash, entry.outpoint.index));
+        serializer.collect_seq(entries)
+    }
+}
+
+impl UtxoCollection {
+    /// Loads a collection serialized by its `Serialize` impl, re-inserting every entry so the
+    /// MuHash and total value are recomputed rather than trusted. Duplicate outpoints are rejected.
+    pub fn deserialize_from<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries = <Vec<StoredUtxo> as serde::Deserialize>::deserialize(deserializer)?;
+        let collection = Self::new();
+        for StoredUtxo { outpoint, output, coinbase_daa_score } in entries {
+            let inserted = match coinbase_daa_score {
+                Some(daa_score) => collection.insert_coinbase(outpoint, output, daa_score),
+                None => collection.insert(outpoint, output),
+            };
+            inserted.map_err(serde::de::Error::custom)?;
+        }
+        Ok(collection)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for UtxoCollection {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Self::deserialize_from(deserializer)
+    }
 }
 
 impl Default for UtxoCollection {
@@ -125,6 +303,47 @@ mod tests {
         assert_eq!(collection.len(), 0);
     }
 
+    #[test]
+    fn test_utxos_for_script() {
+        use crate::utxo::utxo_inquirer::UtxoInquirer;
+
+        let collection = UtxoCollection::new();
+        for (index, (value, script)) in [(100, vec![0x01]), (200, vec![0x02]), (300, vec![0x01]), (400, vec![])].into_iter().enumerate() {
+            let outpoint = OutPoint { tx_hash: Hash::default(), index: index as u32 };
+            collection.insert(outpoint, TxOutput { value, script_pubkey: script }).unwrap();
+        }
+
+        let mut indices: Vec<u32> = collection.utxos_for_script(&[0x01]).iter().map(|(outpoint, _)| outpoint.index).collect();
+        indices.sort_unstable();
+        assert_eq!(indices, vec![0, 2]);
+        assert_eq!(collection.iter_for_script(&[0x01]).map(|(_, output)| output.value).sum::<u64>(), collection.get_balance(&[0x01]));
+        assert_eq!(collection.iter_for_script(&[0x02]).count(), 1);
+        assert!(collection.utxos_for_script(&[0x03]).is_empty());
+    }
+
+    #[test]
+    fn test_total_value() {
+        let collection = UtxoCollection::new();
+        let outpoints: Vec<OutPoint> = (0..5).map(|index| OutPoint { tx_hash: Hash::default(), index }).collect();
+        for (i, outpoint) in outpoints.iter().enumerate() {
+            collection.insert(outpoint.clone(), TxOutput { value: 10 * (i as u64 + 1), script_pubkey: vec![] }).unwrap();
+        }
+        collection.remove(&outpoints[1]).unwrap();
+        collection.remove(&outpoints[1]).unwrap();
+        let scanned: u64 = collection.utxos.read().unwrap().values().map(|output| output.value).sum();
+        assert_eq!(collection.total_value(), scanned);
+        assert_eq!(scanned, 130);
+
+        // An insert that would overflow the total is rejected and leaves the collection untouched
+        let overflow = OutPoint { tx_hash: Hash::default(), index: 9 };
+        assert!(matches!(collection.insert(overflow.clone(), TxOutput { value: u64::MAX, script_pubkey: vec![] }), Err(UtxoError::InvalidOutput(_))));
+        assert_eq!(collection.get(&overflow), None);
+        assert_eq!(collection.total_value(), 130);
+
+        collection.clear();
+        assert_eq!(collection.total_value(), 0);
+    }
+
     #[test]
     fn test_get() {
         let collection = UtxoCollection::new();
@@ -139,4 +358,181 @@ mod tests {
         collection.insert(outpoint.clone(), output.clone()).unwrap();
         assert_eq!(collection.get(&outpoint), Some(output));
     }
+
+    #[test]
+    fn test_clear() {
+        let collection = UtxoCollection::new();
+        for i in 0..3 {
+            let outpoint = OutPoint {
+                tx_hash: Hash::from_le_u64([i, 0, 0, 0]),
+                index: 0,
+            };
+            let output = TxOutput {
+                value: 100,
+                script_pubkey: vec![],
+            };
+            collection.insert(outpoint, output).unwrap();
+        }
+        assert_ne!(collection.muhash(), UtxoCollection::new().muhash());
+
+        collection.clear();
+        assert_eq!(collection.len(), 0);
+        assert_eq!(collection.muhash(), UtxoCollection::new().muhash());
+    }
+
+    #[test]
+    fn test_muhash_commits_to_full_outputs() {
+        let tx_hash = Hash::from_le_u64([1, 0, 0, 0]);
+        let first = (OutPoint { tx_hash, index: 0 }, TxOutput { value: 100, script_pubkey: vec![0x51] });
+        let second = (OutPoint { tx_hash, index: 1 }, TxOutput { value: 100, script_pubkey: vec![0x51] });
+
+        let only_first = UtxoCollection::new();
+        only_first.insert(first.0.clone(), first.1.clone()).unwrap();
+        let only_second = UtxoCollection::new();
+        only_second.insert(second.0.clone(), second.1.clone()).unwrap();
+        let both = UtxoCollection::new();
+        both.insert(first.0.clone(), first.1.clone()).unwrap();
+        both.insert(second.0.clone(), second.1.clone()).unwrap();
+
+        let empty = UtxoCollection::new().muhash();
+        assert_ne!(only_first.muhash(), empty);
+        assert_ne!(only_first.muhash(), only_second.muhash());
+        assert_ne!(both.muhash(), only_first.muhash());
+        assert_ne!(both.muhash(), only_second.muhash());
+
+        // The value and script are committed too
+        let other_value = UtxoCollection::new();
+        other_value.insert(first.0.clone(), TxOutput { value: 101, script_pubkey: vec![0x51] }).unwrap();
+        assert_ne!(other_value.muhash(), only_first.muhash());
+
+        both.remove(&second.0).unwrap();
+        assert_eq!(both.muhash(), only_first.muhash());
+    }
+
+    #[test]
+    fn test_commitment_after() {
+        let collection = UtxoCollection::new();
+        let existing: Vec<OutPoint> = (0..3).map(|i| OutPoint { tx_hash: Hash::from_le_u64([i, 0, 0, 0]), index: 0 }).collect();
+        for (i, outpoint) in existing.iter().enumerate() {
+            collection.insert(outpoint.clone(), TxOutput { value: 100 + i as u64, script_pubkey: vec![] }).unwrap();
+        }
+
+        let mut diff = super::super::UtxoDiff::new();
+        let created = OutPoint { tx_hash: Hash::from_le_u64([9, 0, 0, 0]), index: 1 };
+        let transient = OutPoint { tx_hash: Hash::from_le_u64([9, 0, 0, 0]), index: 2 };
+        diff.add(created, TxOutput { value: 7, script_pubkey: vec![0x51] });
+        diff.add(transient.clone(), TxOutput { value: 8, script_pubkey: vec![] });
+        diff.remove(existing[1].clone(), TxOutput { value: 101, script_pubkey: vec![] });
+        diff.remove(transient, TxOutput { value: 8, script_pubkey: vec![] });
+
+        let before = collection.muhash();
+        let expected = collection.commitment_after(&diff).unwrap();
+        assert_eq!(collection.muhash(), before);
+
+        collection.apply_diff(&diff).unwrap();
+        assert_eq!(collection.muhash(), expected);
+    }
+
+    #[test]
+    fn test_diff_removing_unknown_output_rejected() {
+        let collection = UtxoCollection::new();
+        let held = OutPoint { tx_hash: Hash::from_le_u64([1, 0, 0, 0]), index: 0 };
+        let output = TxOutput { value: 100, script_pubkey: vec![0x51] };
+        collection.insert(held.clone(), output.clone()).unwrap();
+        let before = (collection.muhash(), collection.total_value());
+
+        let mut missing = super::super::UtxoDiff::new();
+        let absent = OutPoint { tx_hash: Hash::from_le_u64([2, 0, 0, 0]), index: 0 };
+        missing.remove(absent.clone(), output.clone());
+        let not_found = UtxoError::NotFound(TransactionOutpoint { transaction_id: absent.tx_hash, index: 0 });
+        assert_eq!(collection.commitment_after(&missing), Err(not_found.clone()));
+        assert_eq!(collection.apply_diff(&missing), Err(not_found));
+
+        let mut mismatched = super::super::UtxoDiff::new();
+        mismatched.remove(held.clone(), TxOutput { value: 1, script_pubkey: vec![0x51] });
+        assert!(matches!(collection.commitment_after(&mismatched), Err(UtxoError::DiffApplicationFailed(_))));
+        assert!(matches!(collection.apply_diff(&mismatched), Err(UtxoError::DiffApplicationFailed(_))));
+
+        // A created-and-spent output must match the one the diff adds
+        let mut transient = super::super::UtxoDiff::new();
+        let created = OutPoint { tx_hash: Hash::from_le_u64([3, 0, 0, 0]), index: 0 };
+        transient.add(created.clone(), output.clone());
+        transient.remove(created, TxOutput { value: 99, script_pubkey: vec![0x51] });
+        assert!(matches!(collection.commitment_after(&transient), Err(UtxoError::DiffApplicationFailed(_))));
+        assert!(matches!(collection.apply_diff(&transient), Err(UtxoError::DiffApplicationFailed(_))));
+
+        // Nothing was applied
+        assert_eq!((collection.muhash(), collection.total_value()), before);
+        assert_eq!(collection.get(&held), Some(output));
+    }
+
+    #[test]
+    fn test_diff_against() {
+        let entry = |i: u64, value: u64| (OutPoint { tx_hash: Hash::from_le_u64([i, 0, 0, 0]), index: 0 }, TxOutput { value, script_pubkey: vec![i as u8] });
+        let ours = UtxoCollection::new();
+        let theirs = UtxoCollection::new();
+        for i in 0..6 {
+            let (outpoint, output) = entry(i, 100);
+            ours.insert(outpoint, output).unwrap();
+        }
+        for (i, value) in [(0, 100), (1, 100), (2, 250), (6, 100), (7, 300)] {
+            let (outpoint, output) = entry(i, value);
+            theirs.insert(outpoint, output).unwrap();
+        }
+
+        let diff = ours.diff_against(&theirs);
+        assert_eq!(diff.added, vec![entry(2, 250), entry(6, 100), entry(7, 300)]);
+        assert_eq!(diff.removed, vec![entry(2, 100), entry(3, 100), entry(4, 100), entry(5, 100)]);
+        assert_eq!(ours.commitment_after(&diff), Ok(theirs.muhash()));
+
+        ours.apply_diff(&diff).unwrap();
+        let sorted = |collection: &UtxoCollection| {
+            let mut entries: Vec<_> = collection.utxos.read().unwrap().clone().into_iter().collect();
+            entries.sort_unstable_by_key(|(outpoint, _)| (outpoint.tx_hash, outpoint.index));
+            entries
+        };
+        assert_eq!(sorted(&ours), sorted(&theirs));
+        assert_eq!(ours.muhash(), theirs.muhash());
+        assert_eq!(ours.total_value(), theirs.total_value());
+        assert_eq!(ours.diff_against(&theirs), super::super::UtxoDiff::new());
+    }
+
+    #[test]
+    fn test_serde_round_trip_rebuilds_derived_state() {
+        let collection = UtxoCollection::new();
+        for i in 0..5u64 {
+            let outpoint = OutPoint { tx_hash: Hash::from_le_u64([i, 0, 0, 0]), index: i as u32 };
+            collection.insert(outpoint, TxOutput { value: 100 * (i + 1), script_pubkey: vec![i as u8] }).unwrap();
+        }
+        let coinbase = OutPoint { tx_hash: Hash::from_le_u64([7, 0, 0, 0]), index: 0 };
+        collection.insert_coinbase(coinbase.clone(), TxOutput { value: 50, script_pubkey: vec![] }, 42).unwrap();
+
+        let json = serde_json::to_string(&collection).unwrap();
+        assert!(!json.contains("muhash") && !json.contains("total_value"));
+        let loaded: UtxoCollection = serde_json::from_str(&json).unwrap();
+
+        let mut recomputed = MuHash::new();
+        for (outpoint, output) in collection.utxos.read().unwrap().iter() {
+            recomputed.add(&utxo_commitment(outpoint, output));
+        }
+        assert_eq!(loaded.muhash(), collection.muhash());
+        assert_eq!(loaded.muhash(), recomputed.finalize());
+        assert_eq!(loaded.total_value(), collection.total_value());
+        assert_eq!(loaded.len(), 6);
+        assert_eq!(loaded.coinbase_daa_score(&coinbase), Some(42));
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+
+        // The MuHash keeps tracking changes after loading
+        loaded.remove(&coinbase).unwrap();
+        collection.remove(&coinbase).unwrap();
+        assert_eq!(loaded.muhash(), collection.muhash());
+
+        // A duplicated entry is rejected rather than counted twice
+        let single = UtxoCollection::new();
+        single.insert(coinbase, TxOutput { value: 1, script_pubkey: vec![] }).unwrap();
+        let entries = serde_json::to_string(&single).unwrap();
+        let entry = entries.trim_start_matches('[').trim_end_matches(']');
+        assert_eq!(serde_json::from_str::<UtxoCollection>(&format!("[{}]", entry)).unwrap().total_value(), 1);
+        assert!(serde_json::from_str::<UtxoCollection>(&format!("[{},{}]", entry, entry)).is_err());
+    }
 }
@@ -1,14 +1,16 @@
 //! UTXO diff for incremental changes.
 
-use crate::tx::{Transaction, TxOutput};
+use crate::tx::{script_public_key::is_unspendable_script, Transaction, TransactionOutpoint, TxOutput};
 use super::utxo_collection::OutPoint;
 use super::utxo_error::UtxoError;
+use super::utxo_view::UtxoView;
 
 /// Incremental UTXO changes.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct UtxoDiff {
     pub added: Vec<(OutPoint, TxOutput)>,
-    pub removed: Vec<OutPoint>,
+    /// Spent outputs, kept alongside their outpoints so the diff can be reversed
+    pub removed: Vec<(OutPoint, TxOutput)>,
 }
 
 impl UtxoDiff {
@@ -22,9 +24,9 @@ impl UtxoDiff {
         self.added.push((outpoint, output));
     }
 
-    /// Removes a UTXO.
-    pub fn remove(&mut self, outpoint: OutPoint) {
-        self.removed.push(outpoint);
+    /// Removes a UTXO, recording the output it held.
+    pub fn remove(&mut self, outpoint: OutPoint, output: TxOutput) {
+        self.removed.push((outpoint, output));
     }
 
     /// Applies the diff to a collection.
@@ -32,38 +34,133 @@ impl UtxoDiff {
         collection.apply_diff(self)
     }
 
-    /// Reverses the diff.
+    /// Reverses the diff: applying it after the original restores the previous UTXO set.
     pub fn reverse(&self) -> UtxoDiff {
-        let mut reversed = UtxoDiff::new();
-        // Note: Reverse is incomplete without collection access
-        for (outpoint, _) in &self.added {
-            reversed.remove(outpoint.clone());
+        UtxoDiff { added: self.removed.clone(), removed: self.added.clone() }
+    }
+
+    /// Composes two diffs into one equivalent to applying `self` and then `other`. Outputs created
+    /// by `self` and spent by `other` cancel out, as for a transaction spending an earlier one in
+    /// the same block. Spending an outpoint twice or creating one that already exists in the
+    /// composed diff, including one `self` spent, is a contradiction and fails.
+    pub fn compose(&self, other: &UtxoDiff) -> Result<UtxoDiff, UtxoError> {
+        let mut composed = self.clone();
+        for (outpoint, output) in &other.removed {
+            if let Some(position) = composed.added.iter().position(|(added, _)| added == outpoint) {
+                composed.added.swap_remove(position);
+            } else if composed.removed.iter().any(|(removed, _)| removed == outpoint) {
+                return Err(UtxoError::AlreadySpent(TransactionOutpoint { transaction_id: outpoint.tx_hash, index: outpoint.index }));
+            } else {
+                composed.removed.push((outpoint.clone(), output.clone()));
+            }
+        }
+        for (outpoint, output) in &other.added {
+            if composed.added.iter().any(|(added, _)| added == outpoint) || self.removed.iter().any(|(removed, _)| removed == outpoint) {
+                return Err(UtxoError::DiffApplicationFailed(format!("Outpoint {:?} is added twice", outpoint)));
+            }
+            composed.added.push((outpoint.clone(), output.clone()));
+        }
+        Ok(composed)
+    }
+
+    /// Serializes the diff into a compact little-endian binary form, suitable for syncing:
+    /// each list is prefixed with its length and scripts are length-prefixed.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for entries in [&self.added, &self.removed] {
+            data.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+            for (outpoint, output) in entries {
+                write_outpoint(&mut data, outpoint);
+                data.extend_from_slice(&output.value.to_le_bytes());
+                data.extend_from_slice(&(output.script_pubkey.len() as u64).to_le_bytes());
+                data.extend_from_slice(&output.script_pubkey);
+            }
         }
-        // For removed, we can't add back without knowing the output
-        reversed
+        data
     }
 
-    /// Creates a diff from a transaction.
-    pub fn from_transaction(tx: &Transaction) -> Self {
+    /// Deserializes a diff produced by `to_bytes`, rejecting truncated or trailing data.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, UtxoError> {
+        let mut reader = Reader { data };
+        let mut diff = UtxoDiff::new();
+        for _ in 0..reader.read_u64()? {
+            let (outpoint, output) = reader.read_entry()?;
+            diff.add(outpoint, output);
+        }
+        for _ in 0..reader.read_u64()? {
+            let (outpoint, output) = reader.read_entry()?;
+            diff.remove(outpoint, output);
+        }
+        if !reader.data.is_empty() {
+            return Err(UtxoError::MalformedDiff(format!("{} trailing bytes", reader.data.len())));
+        }
+        Ok(diff)
+    }
+
+    /// Creates a diff from a transaction, looking up the outputs its inputs spend in `view`.
+    /// Coinbase transactions spend nothing, and unspendable outputs are not added.
+    pub fn from_transaction(tx: &Transaction, view: &UtxoView) -> Result<Self, UtxoError> {
         let mut diff = UtxoDiff::new();
         // Spend inputs
-        for input in &tx.inputs {
+        for input in tx.inputs.iter().filter(|_| !tx.is_coinbase()) {
             let outpoint = OutPoint {
                 tx_hash: input.prev_tx_hash,
                 index: input.index,
             };
-            diff.remove(outpoint);
+            let output = view.get(&outpoint).cloned().ok_or(UtxoError::NotFound(TransactionOutpoint {
+                transaction_id: outpoint.tx_hash,
+                index: outpoint.index,
+            }))?;
+            diff.remove(outpoint, output);
         }
         // Add outputs
-        let tx_hash = tx.hash();
+        let tx_hash = tx.id();
         for (index, output) in tx.outputs.iter().enumerate() {
+            // Unspendable outputs never enter the UTXO set, but still take up their index
+            if is_unspendable_script(&output.script_pubkey) {
+                continue;
+            }
             let outpoint = OutPoint {
                 tx_hash,
                 index: index as u32,
             };
             diff.add(outpoint, output.clone());
         }
-        diff
+        Ok(diff)
+    }
+}
+
+fn write_outpoint(data: &mut Vec<u8>, outpoint: &OutPoint) {
+    data.extend_from_slice(outpoint.tx_hash.as_bytes());
+    data.extend_from_slice(&outpoint.index.to_le_bytes());
+}
+
+/// Cursor over the remaining bytes of a serialized diff.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], UtxoError> {
+        if self.data.len() < len {
+            return Err(UtxoError::MalformedDiff(format!("needed {} bytes, {} left", len, self.data.len())));
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn read_u64(&mut self) -> Result<u64, UtxoError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn read_entry(&mut self) -> Result<(OutPoint, TxOutput), UtxoError> {
+        let tx_hash = crate::Hash::from_slice(self.take(32)?);
+        let index = u32::from_le_bytes(self.take(4)?.try_into().unwrap());
+        let value = self.read_u64()?;
+        let script_len = self.read_u64()? as usize;
+        let script_pubkey = self.take(script_len)?.to_vec();
+        Ok((OutPoint { tx_hash, index }, TxOutput { value, script_pubkey }))
     }
 }
 
@@ -73,22 +170,71 @@ mod tests {
     use crate::tx::{TxInput, Transaction};
     use crate::Hash;
 
+    fn funded_collection(funding: Hash) -> crate::UtxoCollection {
+        let collection = crate::UtxoCollection::new();
+        collection.insert(OutPoint { tx_hash: funding, index: 0 }, TxOutput { value: 3, script_pubkey: vec![0x51] }).unwrap();
+        collection
+    }
+
     #[test]
     fn test_from_transaction() {
-        let input = TxInput {
-            prev_tx_hash: Hash::default(),
-            index: 0,
-            script_sig: vec![],
-            sequence: 0,
-        };
-        let output = TxOutput {
-            value: 100,
-            script_pubkey: vec![],
-        };
-        let tx = Transaction::new(1, vec![input], vec![output.clone()], 0);
-        let diff = UtxoDiff::from_transaction(&tx);
-        assert_eq!(diff.removed.len(), 1);
+        let funding = Hash::from_slice(b"funding");
+        let view = UtxoView::new_from_collection(&funded_collection(funding));
+        let tx = create_tx(&[(funding, 0)], 1);
+        let diff = UtxoDiff::from_transaction(&tx, &view).unwrap();
+        assert_eq!(diff.removed, vec![(OutPoint { tx_hash: funding, index: 0 }, TxOutput { value: 3, script_pubkey: vec![0x51] })]);
         assert_eq!(diff.added.len(), 1);
+
+        let coinbase = crate::create_coinbase_transaction(50, vec![]);
+        assert!(UtxoDiff::from_transaction(&coinbase, &view).unwrap().removed.is_empty());
+        assert!(matches!(UtxoDiff::from_transaction(&create_tx(&[(funding, 1)], 1), &view), Err(UtxoError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_from_transaction_outpoints_survive_resigning() {
+        let funding = Hash::from_slice(b"funding");
+        let view = UtxoView::new_from_collection(&funded_collection(funding));
+        let tx = create_tx(&[(funding, 0)], 1);
+        let mut resigned = tx.clone();
+        resigned.inputs[0].script_sig = vec![0x40; 65];
+        assert_ne!(tx.hash(), resigned.hash());
+
+        let diff = UtxoDiff::from_transaction(&tx, &view).unwrap();
+        assert_eq!(diff.added[0].0, OutPoint { tx_hash: tx.id(), index: 0 });
+        assert_eq!(UtxoDiff::from_transaction(&resigned, &view).unwrap().added, diff.added);
+    }
+
+    #[test]
+    fn test_from_transaction_skips_unspendable() {
+        let funding = Hash::from_slice(b"funding");
+        let view = UtxoView::new_from_collection(&funded_collection(funding));
+        let mut tx = create_tx(&[(funding, 0)], 1);
+        tx.outputs.insert(0, TxOutput { value: 0, script_pubkey: vec![crate::tx::opcodes::OP_RETURN, 0x01, 0xff] });
+
+        let diff = UtxoDiff::from_transaction(&tx, &view).unwrap();
+        assert_eq!(diff.added, vec![(OutPoint { tx_hash: tx.id(), index: 1 }, tx.outputs[1].clone())]);
+    }
+
+    #[test]
+    fn test_reverse_restores_collection() {
+        let funding = Hash::from_slice(b"funding");
+        use crate::utxo::utxo_inquirer::UtxoInquirer;
+
+        let collection = funded_collection(funding);
+        let sorted_snapshot = |collection: &crate::UtxoCollection| {
+            let mut snapshot = collection.snapshot();
+            snapshot.sort_by_key(|(outpoint, _)| (outpoint.tx_hash, outpoint.index));
+            snapshot
+        };
+        let (before, muhash) = (sorted_snapshot(&collection), collection.muhash());
+
+        let diff = UtxoDiff::from_transaction(&create_tx(&[(funding, 0)], 2), &UtxoView::new_from_collection(&collection)).unwrap();
+        collection.apply_diff(&diff).unwrap();
+        assert_ne!(collection.muhash(), muhash);
+
+        collection.apply_diff(&diff.reverse()).unwrap();
+        assert_eq!(sorted_snapshot(&collection), before);
+        assert_eq!(collection.muhash(), muhash);
     }
 
     #[test]
@@ -107,4 +253,82 @@ mod tests {
         assert!(diff.apply_to(&collection).is_ok());
         assert_eq!(collection.get(&outpoint), Some(output));
     }
+
+    fn create_diff() -> UtxoDiff {
+        let mut diff = UtxoDiff::new();
+        for i in 0..3u32 {
+            let outpoint = OutPoint { tx_hash: Hash::from_le_u64([i as u64 + 1, 0, 0, 0]), index: i };
+            diff.add(outpoint, TxOutput { value: 100 * i as u64, script_pubkey: vec![0xac; i as usize] });
+        }
+        for i in 0..2u32 {
+            let outpoint = OutPoint { tx_hash: Hash::from_le_u64([0, i as u64 + 1, 0, 0]), index: i + 7 };
+            diff.remove(outpoint, TxOutput { value: i as u64 + 5, script_pubkey: vec![0x51] });
+        }
+        diff
+    }
+
+    #[test]
+    fn test_diff_binary_round_trip() {
+        let diff = create_diff();
+        let bytes = diff.to_bytes();
+        assert_eq!(UtxoDiff::from_bytes(&bytes), Ok(diff));
+        assert!(matches!(UtxoDiff::from_bytes(&bytes[..bytes.len() - 1]), Err(UtxoError::MalformedDiff(_))));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(matches!(UtxoDiff::from_bytes(&trailing), Err(UtxoError::MalformedDiff(_))));
+    }
+
+    #[test]
+    fn test_diff_serde_round_trip() {
+        let diff = create_diff();
+        let json = serde_json::to_string(&diff).unwrap();
+        assert_eq!(serde_json::from_str::<UtxoDiff>(&json).unwrap(), diff);
+    }
+
+    fn create_tx(spends: &[(Hash, u32)], outputs: u64) -> Transaction {
+        let inputs = spends.iter().map(|&(prev_tx_hash, index)| TxInput { prev_tx_hash, index, script_sig: vec![], sequence: 0 }).collect();
+        let outputs = (1..=outputs).map(|value| TxOutput { value, script_pubkey: vec![] }).collect();
+        Transaction::new(1, inputs, outputs, 0)
+    }
+
+    #[test]
+    fn test_compose_cancels_intra_block_spend() {
+        let funding = Hash::from_slice(b"funding");
+        let parent = create_tx(&[(funding, 0)], 2);
+        let child = create_tx(&[(parent.id(), 1)], 1);
+
+        let collection = funded_collection(funding);
+        let mut view = UtxoView::new_from_collection(&collection);
+        let parent_diff = UtxoDiff::from_transaction(&parent, &view).unwrap();
+        view.apply_diff(&parent_diff);
+        let composed = parent_diff.compose(&UtxoDiff::from_transaction(&child, &view).unwrap()).unwrap();
+        assert_eq!(composed.removed.iter().map(|(outpoint, _)| outpoint.clone()).collect::<Vec<_>>(), vec![OutPoint { tx_hash: funding, index: 0 }]);
+        let added: Vec<OutPoint> = composed.added.iter().map(|(outpoint, _)| outpoint.clone()).collect();
+        assert_eq!(added.len(), 2);
+        assert!(added.contains(&OutPoint { tx_hash: parent.id(), index: 0 }));
+        assert!(added.contains(&OutPoint { tx_hash: child.id(), index: 0 }));
+
+        // The composed diff applies cleanly on top of the funding output
+        composed.apply_to(&collection).unwrap();
+        assert_eq!(collection.len(), 2);
+        assert_eq!(collection.get(&OutPoint { tx_hash: parent.id(), index: 1 }), None);
+    }
+
+    #[test]
+    fn test_compose_contradictions() {
+        let funding = Hash::from_slice(b"funding");
+        let view = UtxoView::new_from_collection(&funded_collection(funding));
+        let tx = UtxoDiff::from_transaction(&create_tx(&[(funding, 0)], 1), &view).unwrap();
+        assert!(matches!(tx.compose(&tx), Err(UtxoError::AlreadySpent(_))));
+
+        let mut created = UtxoDiff::new();
+        created.add(OutPoint { tx_hash: funding, index: 5 }, TxOutput { value: 1, script_pubkey: vec![] });
+        assert!(matches!(created.compose(&created), Err(UtxoError::DiffApplicationFailed(_))));
+
+        let mut spent = UtxoDiff::new();
+        spent.remove(OutPoint { tx_hash: funding, index: 5 }, TxOutput { value: 1, script_pubkey: vec![] });
+        assert!(matches!(spent.compose(&created), Err(UtxoError::DiffApplicationFailed(_))));
+        assert_eq!(created.compose(&spent), Ok(UtxoDiff::new()));
+    }
 }
@@ -13,6 +13,10 @@ pub enum UtxoError {
     InvalidOutput(String),
     /// Diff application failed.
     DiffApplicationFailed(String),
+    /// Serialized diff could not be decoded.
+    MalformedDiff(String),
+    /// Coinbase output spent before `COINBASE_MATURITY` DAA score has passed.
+    ImmatureCoinbaseSpend(TransactionOutpoint),
 }
 
 impl std::fmt::Display for UtxoError {
@@ -22,6 +26,8 @@ impl std::fmt::Display for UtxoError {
             UtxoError::AlreadySpent(outpoint) => write!(f, "UTXO already spent: {:?}", outpoint),
             UtxoError::InvalidOutput(msg) => write!(f, "Invalid output: {}", msg),
             UtxoError::DiffApplicationFailed(msg) => write!(f, "Diff application failed: {}", msg),
+            UtxoError::MalformedDiff(msg) => write!(f, "Malformed diff: {}", msg),
+            UtxoError::ImmatureCoinbaseSpend(outpoint) => write!(f, "Immature coinbase output spent: {:?}", outpoint),
         }
     }
 }
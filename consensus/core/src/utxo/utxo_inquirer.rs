@@ -16,6 +16,14 @@ pub trait UtxoInquirer {
 
     /// Gets the balance for a script pubkey.
     fn get_balance(&self, script_pubkey: &[u8]) -> u64;
+
+    /// Returns a point-in-time copy of all UTXOs.
+    fn snapshot(&self) -> Vec<(OutPoint, TxOutput)>;
+
+    /// Iterates over all UTXOs, by default over a snapshot so no lock is held while iterating.
+    fn iter_utxos(&self) -> Box<dyn Iterator<Item = (OutPoint, TxOutput)> + '_> {
+        Box::new(self.snapshot().into_iter())
+    }
 }
 
 impl UtxoInquirer for UtxoCollection {
@@ -30,6 +38,14 @@ impl UtxoInquirer for UtxoCollection {
             .map(|output| output.value)
             .sum()
     }
+
+    fn snapshot(&self) -> Vec<(OutPoint, TxOutput)> {
+        // Copy under a single read lock acquisition
+        let utxos = self.utxos.read().unwrap();
+        let mut snapshot = Vec::with_capacity(utxos.len());
+        snapshot.extend(utxos.iter().map(|(outpoint, output)| (outpoint.clone(), output.clone())));
+        snapshot
+    }
 }
 
 /// Error type for inquirer.
@@ -79,4 +95,23 @@ mod tests {
         collection.insert(outpoint2, output2).unwrap();
         assert_eq!(collection.get_balance(&script), 300);
     }
+
+    #[test]
+    fn test_iter_utxos() {
+        let collection = UtxoCollection::new();
+        for i in 0..5 {
+            let outpoint = OutPoint {
+                tx_hash: Hash::from_le_u64([i, 0, 0, 0]),
+                index: 0,
+            };
+            let output = TxOutput {
+                value: 100 * (i + 1),
+                script_pubkey: vec![],
+            };
+            collection.insert(outpoint, output).unwrap();
+        }
+
+        assert_eq!(collection.iter_utxos().count(), 5);
+        assert_eq!(collection.iter_utxos().map(|(_, output)| output.value).sum::<u64>(), 1500);
+    }
 }
@@ -1,35 +1,77 @@
 //! UTXO view for immutable snapshots.
 
+use crate::api::args::TransactionValidationBatchArgs;
+use crate::errors::{tx::TxResult, ConsensusError};
 use crate::tx::Transaction;
 use super::utxo_collection::{UtxoCollection, OutPoint};
 use super::utxo_diff::UtxoDiff;
 use super::utxo_error::UtxoError;
 
+/// Outcome of validating a transaction in a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxValidationStatus {
+    /// All inputs were found in the view.
+    Valid,
+    /// None of the inputs were found and none of their parent transactions were accepted or
+    /// pruned, so the parents may simply not have arrived yet.
+    Orphan,
+}
+
 /// Immutable UTXO view.
 #[derive(Debug, Clone)]
 pub struct UtxoView {
     utxos: std::collections::HashMap<OutPoint, crate::tx::TxOutput>,
+    coinbase_daa_scores: std::collections::HashMap<OutPoint, u64>,
+    /// Ids of the transactions accepted into, or pruned from, the history behind this view. Ids
+    /// stay known after all their outputs are spent.
+    known_tx_ids: std::collections::HashSet<crate::Hash>,
 }
 
 impl UtxoView {
     /// Creates a view from a collection.
     pub fn new_from_collection(collection: &UtxoCollection) -> Self {
         let utxos = collection.utxos.read().unwrap().clone();
-        Self { utxos }
+        let coinbase_daa_scores = collection.coinbase_daa_scores.read().unwrap().clone();
+        let known_tx_ids = utxos.keys().map(|outpoint| outpoint.tx_hash).collect();
+        Self { utxos, coinbase_daa_scores, known_tx_ids }
+    }
+
+    /// Records transactions as known, e.g. pruned ones whose outputs are all spent and so can't
+    /// be recovered from the collection.
+    pub fn insert_known_tx_ids(&mut self, tx_ids: impl IntoIterator<Item = crate::Hash>) {
+        self.known_tx_ids.extend(tx_ids);
+    }
+
+    /// Checks whether the transaction with `tx_id` was accepted or pruned.
+    pub fn is_known_tx(&self, tx_id: &crate::Hash) -> bool {
+        self.known_tx_ids.contains(tx_id)
+    }
+
+    /// Gets a UTXO.
+    pub fn get(&self, outpoint: &OutPoint) -> Option<&crate::tx::TxOutput> {
+        self.utxos.get(outpoint)
     }
 
     /// Applies a diff to the view.
     pub fn apply_diff(&mut self, diff: &UtxoDiff) {
         for (outpoint, output) in &diff.added {
+            self.known_tx_ids.insert(outpoint.tx_hash);
             self.utxos.insert(outpoint.clone(), output.clone());
         }
-        for outpoint in &diff.removed {
+        for (outpoint, _) in &diff.removed {
             self.utxos.remove(outpoint);
+            self.coinbase_daa_scores.remove(outpoint);
         }
     }
 
-    /// Validates a transaction against the view.
-    pub fn validate_tx(&self, tx: &Transaction) -> Result<(), UtxoError> {
+    /// Calculates the fee `tx` pays against this view, see [`Transaction::calculate_fee`].
+    pub fn calculate_fee(&self, tx: &Transaction) -> Result<u64, UtxoError> {
+        tx.calculate_fee(self)
+    }
+
+    /// Validates a transaction against the view, as seen from a block with `pov_daa_score`.
+    /// Coinbase outputs may only be spent `COINBASE_MATURITY` DAA score after their block.
+    pub fn validate_tx(&self, tx: &Transaction, pov_daa_score: u64) -> Result<(), UtxoError> {
         let mut seen = std::collections::HashSet::new();
         for input in &tx.inputs {
             let outpoint = OutPoint {
@@ -48,9 +90,50 @@ impl UtxoView {
                     index: outpoint.index,
                 }));
             }
+            if let Some(&block_daa_score) = self.coinbase_daa_scores.get(&outpoint) {
+                if pov_daa_score < block_daa_score.saturating_add(crate::constants::COINBASE_MATURITY) {
+                    return Err(UtxoError::ImmatureCoinbaseSpend(crate::tx::TransactionOutpoint {
+                        transaction_id: outpoint.tx_hash,
+                        index: outpoint.index,
+                    }));
+                }
+            }
         }
         Ok(())
     }
+
+    /// Validates a batch of transactions against the view. A transaction whose inputs are all
+    /// missing, and whose parents are all unknown to the view, is classified as an orphan rather
+    /// than invalid; it is rejected with `OrphanTransaction` unless `args.allow_orphans` is set.
+    /// Coinbase transactions spend nothing and are always valid here.
+    pub fn validate_batch(
+        &self,
+        txs: &[Transaction],
+        pov_daa_score: u64,
+        args: &TransactionValidationBatchArgs,
+    ) -> Vec<TxResult<TxValidationStatus>> {
+        txs.iter()
+            .map(|tx| {
+                if tx.is_coinbase() {
+                    return Ok(TxValidationStatus::Valid);
+                }
+                if !self.is_orphan(tx) {
+                    return self.validate_tx(tx, pov_daa_score).map(|()| TxValidationStatus::Valid).map_err(ConsensusError::from);
+                }
+                if args.allow_orphans {
+                    Ok(TxValidationStatus::Orphan)
+                } else {
+                    Err(ConsensusError::OrphanTransaction { tx_id: tx.id() })
+                }
+            })
+            .collect()
+    }
+
+    /// Checks whether every input's parent is unknown to the view. A known parent means the
+    /// missing output was spent, which makes the transaction invalid rather than orphaned.
+    fn is_orphan(&self, tx: &Transaction) -> bool {
+        !tx.inputs.is_empty() && tx.inputs.iter().all(|input| !self.is_known_tx(&input.prev_tx_hash))
+    }
 }
 
 #[cfg(test)]
@@ -96,7 +179,7 @@ mod tests {
             sequence: 0,
         };
         let tx = Transaction::new(1, vec![input], vec![], 0);
-        assert!(view.validate_tx(&tx).is_ok());
+        assert!(view.validate_tx(&tx, 0).is_ok());
     }
 
     #[test]
@@ -111,6 +194,108 @@ mod tests {
             sequence: 0,
         };
         let tx = Transaction::new(1, vec![input], vec![], 0);
-        assert!(view.validate_tx(&tx).is_err());
+        assert!(view.validate_tx(&tx, 0).is_err());
+    }
+
+    fn create_spending_tx(prev_tx_hash: Hash, index: u32) -> Transaction {
+        let input = TxInput { prev_tx_hash, index, script_sig: vec![], sequence: 0 };
+        Transaction::new(1, vec![input], vec![], 0)
+    }
+
+    #[test]
+    fn test_validate_batch_orphan() {
+        let collection = UtxoCollection::new();
+        let known = OutPoint { tx_hash: Hash::from_le_u64([1, 0, 0, 0]), index: 0 };
+        collection.insert(known.clone(), crate::tx::TxOutput { value: 100, script_pubkey: vec![] }).unwrap();
+        let view = UtxoView::new_from_collection(&collection);
+
+        let valid = create_spending_tx(known.tx_hash, 0);
+        let orphan = create_spending_tx(Hash::from_le_u64([2, 0, 0, 0]), 0);
+        let spent = create_spending_tx(known.tx_hash, 1);
+        let txs = vec![valid, orphan.clone(), spent];
+
+        let args = TransactionValidationBatchArgs { allow_orphans: true, ..Default::default() };
+        let results = view.validate_batch(&txs, 0, &args);
+        assert_eq!(results[0], Ok(TxValidationStatus::Valid));
+        assert_eq!(results[1], Ok(TxValidationStatus::Orphan));
+        assert!(results[2].is_err());
+
+        let results = view.validate_batch(&txs, 0, &TransactionValidationBatchArgs::default());
+        assert_eq!(results[0], Ok(TxValidationStatus::Valid));
+        assert_eq!(results[1], Err(ConsensusError::OrphanTransaction { tx_id: orphan.id() }));
+        assert!(results[2].is_err());
+    }
+
+    #[test]
+    fn test_validate_batch_spent_parent_and_coinbase() {
+        let collection = UtxoCollection::new();
+        let funding = OutPoint { tx_hash: Hash::from_le_u64([1, 0, 0, 0]), index: 0 };
+        collection.insert(funding.clone(), crate::tx::TxOutput { value: 100, script_pubkey: vec![] }).unwrap();
+        let mut view = UtxoView::new_from_collection(&collection);
+
+        // Spending the parent's only output leaves it without outputs in the view
+        let spend = create_spending_tx(funding.tx_hash, 0);
+        view.apply_diff(&UtxoDiff::from_transaction(&spend, &view).unwrap());
+        assert!(view.is_known_tx(&funding.tx_hash));
+
+        let pruned = Hash::from_le_u64([3, 0, 0, 0]);
+        view.insert_known_tx_ids([pruned]);
+
+        let double_spend = create_spending_tx(funding.tx_hash, 0);
+        let spends_pruned = create_spending_tx(pruned, 0);
+        let coinbase = crate::create_coinbase_transaction(50, vec![]);
+        let args = TransactionValidationBatchArgs { allow_orphans: true, ..Default::default() };
+        let results = view.validate_batch(&[double_spend, spends_pruned, coinbase.clone()], 0, &args);
+        let not_found = |transaction_id| {
+            Err(ConsensusError::from(UtxoError::NotFound(crate::tx::TransactionOutpoint { transaction_id, index: 0 })))
+        };
+        assert_eq!(results[0], not_found(funding.tx_hash));
+        assert_eq!(results[1], not_found(pruned));
+        assert_eq!(results[2], Ok(TxValidationStatus::Valid));
+
+        let results = view.validate_batch(&[coinbase], 0, &TransactionValidationBatchArgs::default());
+        assert_eq!(results[0], Ok(TxValidationStatus::Valid));
+    }
+
+    #[test]
+    fn test_calculate_fee() {
+        let collection = UtxoCollection::new();
+        let funding = OutPoint { tx_hash: Hash::from_le_u64([1, 0, 0, 0]), index: 0 };
+        collection.insert(funding.clone(), crate::tx::TxOutput { value: 100, script_pubkey: vec![] }).unwrap();
+        let view = UtxoView::new_from_collection(&collection);
+
+        let paying = |value| {
+            let mut tx = create_spending_tx(funding.tx_hash, 0);
+            tx.outputs.push(crate::tx::TxOutput { value, script_pubkey: vec![] });
+            tx
+        };
+        assert_eq!(view.calculate_fee(&paying(90)), Ok(10));
+        assert_eq!(view.calculate_fee(&paying(100)), Ok(0));
+        assert!(matches!(view.calculate_fee(&paying(101)), Err(UtxoError::InvalidOutput(_))));
+        assert_eq!(view.calculate_fee(&crate::create_coinbase_transaction(50, vec![])), Ok(0));
+    }
+
+    #[test]
+    fn test_validate_tx_coinbase_maturity() {
+        use crate::constants::COINBASE_MATURITY;
+
+        let collection = UtxoCollection::new();
+        let coinbase = OutPoint { tx_hash: Hash::from_le_u64([1, 0, 0, 0]), index: 0 };
+        let regular = OutPoint { tx_hash: Hash::from_le_u64([2, 0, 0, 0]), index: 0 };
+        collection.insert_coinbase(coinbase.clone(), crate::tx::TxOutput { value: 50, script_pubkey: vec![] }, 1000).unwrap();
+        collection.insert(regular.clone(), crate::tx::TxOutput { value: 50, script_pubkey: vec![] }).unwrap();
+        assert_eq!(collection.coinbase_daa_score(&coinbase), Some(1000));
+        let view = UtxoView::new_from_collection(&collection);
+
+        let spend = create_spending_tx(coinbase.tx_hash, 0);
+        assert_eq!(
+            view.validate_tx(&spend, 1000 + COINBASE_MATURITY - 1),
+            Err(UtxoError::ImmatureCoinbaseSpend(crate::tx::TransactionOutpoint { transaction_id: coinbase.tx_hash, index: 0 }))
+        );
+        assert_eq!(view.validate_tx(&spend, 1000 + COINBASE_MATURITY), Ok(()));
+        assert_eq!(view.validate_tx(&create_spending_tx(regular.tx_hash, 0), 0), Ok(()));
+
+        collection.remove(&coinbase).unwrap();
+        assert_eq!(collection.coinbase_daa_score(&coinbase), None);
     }
 }
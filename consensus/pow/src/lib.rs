@@ -8,14 +8,12 @@ pub mod xoshiro;
 
 use std::cmp::max;
 
-use crate::matrix::Matrix;
-use jio_consensus_core::{hashing, header::Header, BlockLevel};
+use jio_consensus_core::{header::Header, BlockLevel};
 use jio_hashes::PowHash;
 use jio_math::Uint256;
 
 /// State is an intermediate data structure with pre-computed values to speed up mining.
 pub struct State {
-    pub(crate) matrix: Matrix,
     pub(crate) target: Uint256,
     // PRE_POW_HASH || TIME || 32 zero byte padding; without NONCE
     pub(crate) hasher: PowHash,
@@ -24,14 +22,13 @@ pub struct State {
 impl State {
     #[inline]
     pub fn new(header: &Header) -> Self {
-        let target = Uint256::from_compact_target_bits(header.bits);
-        // Zero out the time and nonce.
-        let pre_pow_hash = hashing::header::hash_override_nonce_time(header, 0, 0);
-        // PRE_POW_HASH || TIME || 32 zero byte padding || NONCE
-        let hasher = PowHash::new(pre_pow_hash, header.timestamp);
-        let matrix = Matrix::generate(pre_pow_hash);
-
-        Self { matrix, target, hasher }
+        let target = header.target();
+        // The pre-pow hash has the time and nonce zeroed out.
+        let (pre_pow_hash, timestamp) = header.mining_prefix();
+        // PRE_POW_HASH || TIME || 32 zero byte padding || NONCE, followed by the HeavyHash matrix
+        let hasher = PowHash::new(pre_pow_hash, timestamp);
+
+        Self { target, hasher }
     }
 
     #[inline]
@@ -40,8 +37,9 @@ impl State {
     pub fn calculate_pow(&self, nonce: u64) -> Uint256 {
         // Hasher already contains PRE_POW_HASH || TIME || 32 zero byte padding; so only the NONCE is missing
         let hash = self.hasher.clone().finalize_with_nonce(nonce);
-        let hash = self.matrix.heavy_hash(hash);
-        Uint256::from_le_bytes(hash.as_bytes())
+        let mut bytes = *hash.as_bytes();
+        bytes.reverse();
+        Uint256::from(bytes)
     }
 
     #[inline]
@@ -53,6 +51,23 @@ impl State {
     }
 }
 
+/// Outcome of checking a pow value against a target, with the difficulty it actually achieved.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowResult {
+    /// Whether the pow value is at most the target.
+    pub passed: bool,
+    /// `max_target / pow_value`, the difficulty the solution would have met on its own.
+    pub achieved_difficulty: f64,
+}
+
+/// Checks `pow_value` against `target` and reports the difficulty it achieved relative to
+/// `max_target`, the network's `Params::pow_limit`, which pools use to price shares. Comparing it
+/// with `max_target / target` gives the margin by which the solution beat or missed the target.
+/// A zero pow value achieves infinite difficulty.
+pub fn pow_margin(pow_value: Uint256, target: Uint256, max_target: Uint256) -> PowResult {
+    PowResult { passed: pow_value <= target, achieved_difficulty: max_target.to_f64() / pow_value.to_f64() }
+}
+
 pub fn calc_block_level(header: &Header, max_block_level: BlockLevel) -> BlockLevel {
     let (block_level, _) = calc_block_level_check_pow(header, max_block_level);
     block_level
@@ -73,3 +88,54 @@ pub fn calc_level_from_pow(pow: Uint256, max_block_level: BlockLevel) -> BlockLe
     let signed_block_level = max_block_level as i64 - pow.bits() as i64;
     max(signed_block_level, 0) as BlockLevel
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uint256_from_u128(value: u128) -> Uint256 {
+        let mut bytes = [0u8; 32];
+        bytes[16..].copy_from_slice(&value.to_be_bytes());
+        Uint256::from(bytes)
+    }
+
+    #[test]
+    fn test_pow_margin() {
+        let max_target = jio_consensus_core::config::params::Params::default().pow_limit;
+        let target = uint256_from_u128(1 << 100);
+        let target_difficulty = max_target.to_f64() / target.to_f64();
+
+        let beating = pow_margin(uint256_from_u128(1 << 96), target, max_target);
+        assert!(beating.passed);
+        assert!(beating.achieved_difficulty / target_difficulty > 1.0);
+        assert!((beating.achieved_difficulty / target_difficulty - 16.0).abs() < 1e-9);
+
+        let missing = pow_margin(uint256_from_u128(1 << 101), target, max_target);
+        assert!(!missing.passed);
+        assert!(missing.achieved_difficulty / target_difficulty < 1.0);
+
+        assert!(pow_margin(target, target, max_target).passed);
+        // A solution exactly at the pow limit has difficulty 1
+        assert_eq!(pow_margin(max_target, target, max_target).achieved_difficulty, 1.0);
+    }
+
+    #[test]
+    fn test_state_agrees_with_header() {
+        let mut header = Header::new();
+        header.parents_by_level = vec![vec![jio_consensus_core::Hash::from_le_u64([1, 0, 0, 0])]];
+        header.bits = 0x207fffff;
+        let state = State::new(&header);
+        assert_eq!(state.target, header.target());
+        for nonce in 0..16 {
+            header.nonce = nonce;
+            let block = jio_consensus_core::block::Block::new(header.clone(), vec![]);
+            let (passed, pow) = state.check_pow(nonce);
+            assert_eq!(pow, {
+                let mut bytes = *header.pow_hash().as_bytes();
+                bytes.reverse();
+                Uint256::from(bytes)
+            });
+            assert_eq!(passed, jio_consensus_core::check_proof_of_work(&block));
+        }
+    }
+}
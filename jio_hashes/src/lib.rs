@@ -3,6 +3,12 @@
 use std::fmt;
 use std::hash::Hasher;
 
+mod matrix;
+mod xoshiro;
+
+pub use matrix::Matrix;
+pub use xoshiro::Xoshiro256;
+
 /// Trait for extending hashers with additional methods.
 pub trait HasherExtensions {
     /// Writes the len as u64 little endian bytes
@@ -23,7 +29,8 @@ pub trait HasherExtensions {
     /// Writes the u64 as a little endian u8 array
     fn write_u64(&mut self, element: u64) -> &mut Self;
 
-    /// Writes blue work as big endian bytes w/o the leading zeros
+    /// Writes blue work as big endian bytes w/o the leading zeros.
+    /// Zero work is written as an empty byte array (emulates bigint.bytes() in the jiopad golang ref)
     fn write_blue_work(&mut self, work: u64) -> &mut Self;
 
     /// Writes the number of bytes followed by the bytes themselves
@@ -70,7 +77,7 @@ impl HasherExtensions for BlockHash {
 
     #[inline(always)]
     fn write_blue_work(&mut self, work: u64) -> &mut Self {
-        let be_bytes = work.to_le_bytes();
+        let be_bytes = work.to_be_bytes();
         let start = be_bytes.iter().copied().position(|byte| byte != 0).unwrap_or(be_bytes.len());
         self.write_var_bytes(&be_bytes[start..])
     }
@@ -91,11 +98,72 @@ impl HasherExtensions for BlockHash {
     }
 }
 
+/// Length of a hash in bytes.
+pub const HASH_SIZE: usize = 32;
+
+/// Error returned when a hash is built from a byte source that isn't exactly `HASH_SIZE` bytes long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashLenError {
+    /// The source ended after `len` bytes.
+    TooShort { len: usize },
+    /// The source had bytes left after the first `HASH_SIZE`.
+    TooLong,
+}
+
+impl fmt::Display for HashLenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashLenError::TooShort { len } => write!(f, "expected {} hash bytes, got {}", HASH_SIZE, len),
+            HashLenError::TooLong => write!(f, "expected {} hash bytes, got more", HASH_SIZE),
+        }
+    }
+}
+
+impl std::error::Error for HashLenError {}
+
+/// Error returned when parsing a hash from a hex string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashParseError {
+    /// The string (without any `0x` prefix) isn't exactly `2 * HASH_SIZE` characters long.
+    InvalidLength { len: usize },
+    /// The string contains a character that isn't a hex digit.
+    InvalidHexChar { c: char },
+}
+
+impl fmt::Display for HashParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashParseError::InvalidLength { len } => write!(f, "expected {} hex characters, got {}", 2 * HASH_SIZE, len),
+            HashParseError::InvalidHexChar { c } => write!(f, "invalid hex character {:?}", c),
+        }
+    }
+}
+
+impl std::error::Error for HashParseError {}
+
 /// A 256-bit hash.
+///
+/// The default value is the all-zero null hash ([`ZERO_HASH`]). No real hash is expected to be
+/// zero, so it serves as the sentinel for "no hash": the previous outpoint of coinbase inputs and
+/// the selected parent of genesis.
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, serde::Serialize, serde::Deserialize)]
 pub struct Hash([u8; 32]);
 
+/// The all-zero null hash, equal to `Hash::default()`.
+pub const ZERO_HASH: Hash = Hash([0u8; HASH_SIZE]);
+
 impl Hash {
+    /// Checks whether every byte of the hash is zero.
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0u8; HASH_SIZE]
+    }
+
+    /// Checks whether this is the null hash, an alias of [`Hash::is_zero`] that reads better
+    /// where the zero hash stands for a missing reference.
+    pub fn is_null(&self) -> bool {
+        self.is_zero()
+    }
+
     /// Create a hash from little-endian u64 array.
     pub fn from_le_u64(data: [u64; 4]) -> Self {
         let mut bytes = [0u8; 32];
@@ -113,6 +181,37 @@ impl Hash {
         Self(bytes)
     }
 
+    /// Create a hash from an iterator yielding exactly `HASH_SIZE` bytes. At most one byte
+    /// past the hash is consumed to detect a longer source.
+    pub fn from_bytes_exact<I: IntoIterator<Item = u8>>(iter: I) -> Result<Self, HashLenError> {
+        let mut iter = iter.into_iter();
+        let mut bytes = [0u8; HASH_SIZE];
+        for (len, byte) in bytes.iter_mut().enumerate() {
+            *byte = iter.next().ok_or(HashLenError::TooShort { len })?;
+        }
+        if iter.next().is_some() {
+            return Err(HashLenError::TooLong);
+        }
+        Ok(Self(bytes))
+    }
+
+    /// Parse a hash from the hex format produced by `Display`, with an optional `0x` prefix.
+    pub fn from_hex(s: &str) -> Result<Self, HashParseError> {
+        let digits = s.strip_prefix("0x").unwrap_or(s);
+        if let Some(c) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+            return Err(HashParseError::InvalidHexChar { c });
+        }
+        if digits.len() != 2 * HASH_SIZE {
+            return Err(HashParseError::InvalidLength { len: digits.len() });
+        }
+        // `Display` prints the bytes in reverse order
+        let mut bytes = [0u8; HASH_SIZE];
+        for (byte, pair) in bytes.iter_mut().rev().zip(digits.as_bytes().chunks_exact(2)) {
+            *byte = u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap();
+        }
+        Ok(Self(bytes))
+    }
+
     /// Get the hash as bytes.
     pub fn as_bytes(&self) -> &[u8; 32] {
         &self.0
@@ -137,6 +236,14 @@ impl fmt::Display for Hash {
     }
 }
 
+impl std::str::FromStr for Hash {
+    type Err = HashParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(s)
+    }
+}
+
 impl fmt::Debug for Hash {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Hash({})", self)
@@ -187,9 +294,13 @@ impl Default for BlockHash {
 }
 
 /// PoW hasher for HeavyHash algorithm.
+///
+/// The nonce is appended to `PRE_POW_HASH || TIME || 32 zero byte padding` and the SHA3 digest
+/// is run through the HeavyHash matrix generated from the pre-pow hash.
 #[derive(Clone)]
 pub struct PowHash {
     hasher: sha3::Sha3_256,
+    matrix: Matrix,
 }
 
 impl PowHash {
@@ -198,17 +309,93 @@ impl PowHash {
         use sha3::Digest;
         let mut hasher = sha3::Sha3_256::default();
         hasher.update(pre_pow_hash.as_bytes());
-        hasher.update(&timestamp.to_le_bytes());
+        hasher.update(timestamp.to_le_bytes());
         // Add 32 zero bytes padding
-        hasher.update(&[0u8; 32]);
-        Self { hasher }
+        hasher.update([0u8; 32]);
+        Self { hasher, matrix: Matrix::generate(pre_pow_hash) }
     }
 
     /// Finalizes the hash with a nonce.
     pub fn finalize_with_nonce(mut self, nonce: u64) -> Hash {
         use sha3::Digest;
-        self.hasher.update(&nonce.to_le_bytes());
-        let result = self.hasher.finalize();
-        Hash::from_slice(&result)
+        self.hasher.update(nonce.to_le_bytes());
+        let inner = Hash::from_slice(&self.hasher.finalize());
+        self.matrix.heavy_hash(inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_blue_work() {
+        let tests: Vec<(u64, Vec<u8>)> =
+            vec![(0, vec![0, 0, 0, 0, 0, 0, 0, 0]), (123456, vec![3, 0, 0, 0, 0, 0, 0, 0, 1, 226, 64])];
+
+        for (work, expected) in tests {
+            let mut hasher = BlockHash::new();
+            hasher.write_blue_work(work);
+
+            let mut hasher2 = BlockHash::new();
+            hasher2.update(&expected);
+            assert_eq!(hasher.finalize(), hasher2.finalize());
+        }
+    }
+
+    #[test]
+    fn test_default_is_null_hash() {
+        assert_eq!(Hash::default().as_bytes(), &[0u8; 32]);
+        assert_eq!(Hash::default(), ZERO_HASH);
+        assert!(Hash::default().is_zero());
+        assert!(Hash::default().is_null());
+        assert!(!Hash::from_le_u64([0, 0, 0, 1 << 63]).is_null());
+    }
+
+    #[test]
+    fn test_from_bytes_exact() {
+        let hash = Hash::from_bytes_exact(0..32u8).unwrap();
+        assert_eq!(hash.as_bytes().to_vec(), (0..32u8).collect::<Vec<_>>());
+        assert_eq!(Hash::from_bytes_exact(0..31u8), Err(HashLenError::TooShort { len: 31 }));
+        assert_eq!(Hash::from_bytes_exact(std::iter::empty()), Err(HashLenError::TooShort { len: 0 }));
+        assert_eq!(Hash::from_bytes_exact(0..33u8), Err(HashLenError::TooLong));
+    }
+
+    #[test]
+    fn test_hash_hex_round_trip() {
+        let hash = Hash::from_le_u64([1, 2, 3, 0xdead_beef]);
+        assert_eq!(hash.to_string().parse::<Hash>(), Ok(hash));
+        assert_eq!(Hash::from_hex(&format!("0x{}", hash)), Ok(hash));
+        assert_eq!(Hash::from_hex(&hash.to_string().to_uppercase()), Ok(hash));
+    }
+
+    #[test]
+    fn test_hash_from_hex_invalid() {
+        let hex = Hash::from_le_u64([1, 2, 3, 4]).to_string();
+        assert_eq!(Hash::from_hex(&hex[1..]), Err(HashParseError::InvalidLength { len: 63 }));
+        assert_eq!(Hash::from_hex(&format!("{}00", hex)), Err(HashParseError::InvalidLength { len: 66 }));
+        assert_eq!(Hash::from_hex(""), Err(HashParseError::InvalidLength { len: 0 }));
+        assert_eq!(Hash::from_hex(&format!("{}g", &hex[1..])), Err(HashParseError::InvalidHexChar { c: 'g' }));
+        assert_eq!(Hash::from_hex(&format!("0x0x{}", &hex[4..])), Err(HashParseError::InvalidHexChar { c: 'x' }));
+    }
+
+    #[test]
+    fn test_pow_hash_heavy_hash() {
+        use sha3::Digest;
+        let pre_pow_hash = Hash::from_le_u64([1, 2, 3, 4]);
+        let (timestamp, nonce) = (1_700_000_000_000u64, 42u64);
+
+        let mut plain = sha3::Sha3_256::default();
+        plain.update(pre_pow_hash.as_bytes());
+        plain.update(timestamp.to_le_bytes());
+        plain.update([0u8; 32]);
+        plain.update(nonce.to_le_bytes());
+        let plain = Hash::from_slice(&plain.finalize());
+
+        let pow = PowHash::new(pre_pow_hash, timestamp).finalize_with_nonce(nonce);
+        assert_ne!(pow, plain);
+        assert_eq!(pow, Matrix::generate(pre_pow_hash).heavy_hash(plain));
+        assert_eq!(pow, PowHash::new(pre_pow_hash, timestamp).finalize_with_nonce(nonce));
+        assert_ne!(pow, PowHash::new(pre_pow_hash, timestamp).finalize_with_nonce(nonce + 1));
     }
 }
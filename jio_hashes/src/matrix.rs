@@ -0,0 +1,125 @@
+//! Matrix for HeavyHash algorithm.
+
+use crate::{xoshiro::Xoshiro256, Hash};
+
+/// Dimension of the HeavyHash matrix: one row and column per nibble of a hash.
+const SIZE: usize = 64;
+
+/// 64x64 matrix of 4-bit entries used by HeavyHash.
+#[derive(Clone)]
+pub struct Matrix {
+    data: [[u16; SIZE]; SIZE],
+}
+
+impl Matrix {
+    /// Generate matrix from pre_pow_hash. The matrix is drawn from a xoshiro generator seeded with
+    /// the hash and redrawn until it is full-rank over GF(2).
+    pub fn generate(pre_pow_hash: Hash) -> Self {
+        let mut generator = Xoshiro256::from_hash(&pre_pow_hash);
+        loop {
+            let matrix = Self::rand_matrix(&mut generator);
+            if matrix.gf2_rank() == SIZE {
+                return matrix;
+            }
+        }
+    }
+
+    fn rand_matrix(generator: &mut Xoshiro256) -> Self {
+        let mut data = [[0u16; SIZE]; SIZE];
+        for row in data.iter_mut() {
+            for chunk in row.chunks_exact_mut(16) {
+                let value = generator.next_u64();
+                for (shift, entry) in chunk.iter_mut().enumerate() {
+                    *entry = ((value >> (4 * shift)) & 0x0F) as u16;
+                }
+            }
+        }
+        Self { data }
+    }
+
+    /// Rank over GF(2) of the matrix reduced modulo 2. Full rank here implies an odd, hence
+    /// non-zero, determinant over the integers as well.
+    fn gf2_rank(&self) -> usize {
+        let mut rows: Vec<u64> = self
+            .data
+            .iter()
+            .map(|row| row.iter().enumerate().fold(0u64, |bits, (j, &entry)| bits | (((entry & 1) as u64) << j)))
+            .collect();
+
+        let mut rank = 0;
+        for column in 0..SIZE {
+            let mask = 1u64 << column;
+            let Some(pivot) = (rank..SIZE).find(|&i| rows[i] & mask != 0) else {
+                continue;
+            };
+            rows.swap(rank, pivot);
+            let pivot_row = rows[rank];
+            for (i, row) in rows.iter_mut().enumerate() {
+                if i != rank && *row & mask != 0 {
+                    *row ^= pivot_row;
+                }
+            }
+            rank += 1;
+        }
+        rank
+    }
+
+    /// Apply heavy hash to input hash: multiply the matrix by the input's 64 nibbles and SHA3 the
+    /// product. The matrix is invertible, so distinct inputs always yield distinct products.
+    pub fn heavy_hash(&self, input: Hash) -> Hash {
+        use sha3::Digest;
+
+        let mut vector = [0u16; SIZE];
+        for (i, &byte) in input.as_bytes().iter().enumerate() {
+            vector[2 * i] = (byte >> 4) as u16;
+            vector[2 * i + 1] = (byte & 0x0F) as u16;
+        }
+
+        // Each product is at most 64 * 15 * 15 < 2^14, so it fits in a u16
+        let mut hasher = sha3::Sha3_256::default();
+        for row in self.data.iter() {
+            let sum: u16 = row.iter().zip(vector.iter()).map(|(&m, &v)| m * v).sum();
+            hasher.update(sum.to_le_bytes());
+        }
+        Hash::from_slice(&hasher.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_full_rank() {
+        for i in 0..16 {
+            let matrix = Matrix::generate(Hash::from_le_u64([i, i * 3, 7, 11]));
+            assert_eq!(matrix.gf2_rank(), SIZE);
+            assert!(matrix.data.iter().flatten().all(|&entry| entry < 16));
+        }
+        // The zero hash would seed a generator stuck at zero without remapping
+        assert_eq!(Matrix::generate(Hash::default()).gf2_rank(), SIZE);
+    }
+
+    #[test]
+    fn test_gf2_rank_detects_dependent_rows() {
+        let mut matrix = Matrix::generate(Hash::from_le_u64([1, 2, 3, 4]));
+        matrix.data[5] = matrix.data[9];
+        assert!(matrix.gf2_rank() < SIZE);
+    }
+
+    #[test]
+    fn test_heavy_hash_nibble_sensitivity() {
+        let matrix = Matrix::generate(Hash::from_le_u64([5, 6, 7, 8]));
+        let input = Hash::from_le_u64([0x0123_4567_89ab_cdef, 42, u64::MAX, 0]);
+        let expected = matrix.heavy_hash(input);
+        assert_eq!(expected, matrix.heavy_hash(input));
+
+        for nibble in 0..SIZE {
+            for flip in [0x1u8, 0x8, 0xF] {
+                let mut bytes = *input.as_bytes();
+                bytes[nibble / 2] ^= if nibble % 2 == 0 { flip << 4 } else { flip };
+                assert_ne!(matrix.heavy_hash(Hash::from_slice(&bytes)), expected, "nibble {nibble} flip {flip:#x}");
+            }
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! Xoshiro random number generator for HeavyHash.
+
+use crate::Hash;
+
+/// Seed standing in for the zero hash in [`Xoshiro256::from_hash`].
+const ZERO_HASH_SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// Xoshiro256** random number generator.
+pub struct Xoshiro256 {
+    state: [u64; 4],
+}
+
+impl Xoshiro256 {
+    /// Create new generator with seed.
+    pub fn new(seed: u64) -> Self {
+        let mut state = [0u64; 4];
+        state[0] = seed;
+        state[1] = seed.wrapping_mul(0x9E3779B97F4A7C15);
+        state[2] = seed.wrapping_mul(0xB5297A4D3C2DB1EF);
+        state[3] = seed.wrapping_mul(0x68BC384E9F5B8D3F);
+        Self { state }
+    }
+
+    /// Create new generator whose state is the hash read as four little endian u64 words. The
+    /// all-zero state is a fixed point that only ever yields zeros, so the zero hash is remapped
+    /// to the state `Xoshiro256::new(ZERO_HASH_SEED)` starts from.
+    pub fn from_hash(hash: &Hash) -> Self {
+        let mut state = [0u64; 4];
+        for (word, chunk) in state.iter_mut().zip(hash.as_bytes().chunks_exact(8)) {
+            *word = u64::from_le_bytes(chunk.try_into().unwrap());
+        }
+        if state == [0; 4] {
+            return Self::new(ZERO_HASH_SEED);
+        }
+        Self { state }
+    }
+
+    /// Generate next random u64.
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.state[1] << 17;
+
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(45);
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_zero_hash() {
+        let mut generator = Xoshiro256::from_hash(&Hash::default());
+        assert!((0..4).map(|_| generator.next_u64()).any(|value| value != 0));
+
+        let mut expected = Xoshiro256::new(ZERO_HASH_SEED);
+        let mut generator = Xoshiro256::from_hash(&Hash::default());
+        assert!((0..16).all(|_| generator.next_u64() == expected.next_u64()));
+    }
+}
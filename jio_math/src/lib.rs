@@ -1,14 +1,19 @@
 //! Jio math library.
 
+use std::cmp::Ordering;
 use std::fmt;
+use std::ops::{Add, AddAssign, Sub};
 
 pub mod uint256;
 
 /// A 192-bit unsigned integer.
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, serde::Serialize, serde::Deserialize)]
 pub struct Uint192([u8; 24]);
 
 impl Uint192 {
+    /// The largest representable value.
+    pub const MAX: Self = Self([0xFF; 24]);
+
     /// Create from u64.
     pub const fn from_u64(val: u64) -> Self {
         let bytes = [
@@ -25,6 +30,51 @@ impl Uint192 {
         Self(bytes)
     }
 
+    /// Create from little-endian bytes.
+    pub const fn from_le_bytes(bytes: [u8; 24]) -> Self {
+        Self(bytes)
+    }
+
+    /// Create from big-endian bytes.
+    pub fn from_be_bytes(mut bytes: [u8; 24]) -> Self {
+        bytes.reverse();
+        Self(bytes)
+    }
+
+    /// Checks whether the value is zero.
+    pub fn is_zero(&self) -> bool {
+        self.0.iter().all(|&byte| byte == 0)
+    }
+
+    /// Adds `other`, returning `None` on overflow.
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        let mut bytes = [0u8; 24];
+        let mut carry = 0u16;
+        for (out, (&a, &b)) in bytes.iter_mut().zip(self.0.iter().zip(other.0.iter())) {
+            let sum = a as u16 + b as u16 + carry;
+            *out = sum as u8;
+            carry = sum >> 8;
+        }
+        (carry == 0).then_some(Self(bytes))
+    }
+
+    /// Adds `other`, clamping to [`Uint192::MAX`] on overflow.
+    pub fn saturating_add(self, other: Self) -> Self {
+        self.checked_add(other).unwrap_or(Self::MAX)
+    }
+
+    /// Subtracts `other`, returning `None` on underflow.
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        let mut bytes = [0u8; 24];
+        let mut borrow = 0i16;
+        for (out, (&a, &b)) in bytes.iter_mut().zip(self.0.iter().zip(other.0.iter())) {
+            let diff = a as i16 - b as i16 - borrow;
+            *out = diff as u8;
+            borrow = (diff < 0) as i16;
+        }
+        (borrow == 0).then_some(Self(bytes))
+    }
+
     /// Get as bytes.
     pub fn as_bytes(&self) -> &[u8; 24] {
         &self.0
@@ -34,6 +84,107 @@ impl Uint192 {
     pub fn to_le_bytes(&self) -> [u8; 24] {
         self.0
     }
+
+    /// Expected work for a block mined at `target`, i.e. `2^256 / (target + 1)`.
+    /// Saturates to [`Uint192::MAX`] when the work does not fit in 192 bits.
+    pub fn work_from_target(target: &Uint256) -> Self {
+        // Little-endian u64 limbs with a fifth limb for the carry of `target + 1`
+        let mut divisor = [0u64; 5];
+        for (limb, chunk) in divisor.iter_mut().zip(target.as_bytes().rchunks_exact(8)) {
+            *limb = u64::from_be_bytes(chunk.try_into().unwrap());
+        }
+        for limb in divisor.iter_mut() {
+            let (sum, carry) = limb.overflowing_add(1);
+            *limb = sum;
+            if !carry {
+                break;
+            }
+        }
+
+        // Shift-subtract long division of 2^256 by the divisor
+        let mut quotient = [0u64; 5];
+        let mut remainder = [0u64; 5];
+        for bit in (0..=256).rev() {
+            shl1(&mut remainder);
+            if bit == 256 {
+                remainder[0] |= 1;
+            }
+            if !lt(&remainder, &divisor) {
+                sub_assign(&mut remainder, &divisor);
+                quotient[bit / 64] |= 1 << (bit % 64);
+            }
+        }
+
+        if quotient[3] != 0 || quotient[4] != 0 {
+            return Self::MAX;
+        }
+        let mut bytes = [0u8; 24];
+        for (chunk, limb) in bytes.chunks_exact_mut(8).zip(quotient) {
+            chunk.copy_from_slice(&limb.to_le_bytes());
+        }
+        Self(bytes)
+    }
+
+    /// Expected work for a block with the given compact target bits.
+    pub fn work_from_bits(bits: u32) -> Self {
+        Self::work_from_target(&Uint256::from_compact_target_bits(bits))
+    }
+}
+
+impl Add for Uint192 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        self.checked_add(other).expect("attempt to add with overflow")
+    }
+}
+
+impl AddAssign for Uint192 {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Uint192 {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.checked_sub(other).expect("attempt to subtract with overflow")
+    }
+}
+
+fn shl1(limbs: &mut [u64; 5]) {
+    for i in (1..limbs.len()).rev() {
+        limbs[i] = (limbs[i] << 1) | (limbs[i - 1] >> 63);
+    }
+    limbs[0] <<= 1;
+}
+
+fn lt(a: &[u64; 5], b: &[u64; 5]) -> bool {
+    a.iter().rev().lt(b.iter().rev())
+}
+
+fn sub_assign(a: &mut [u64; 5], b: &[u64; 5]) {
+    let mut borrow = false;
+    for (x, &y) in a.iter_mut().zip(b) {
+        let (diff, b1) = x.overflowing_sub(y);
+        let (diff, b2) = diff.overflowing_sub(borrow as u64);
+        *x = diff;
+        borrow = b1 || b2;
+    }
+}
+
+impl Ord for Uint192 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Bytes are little-endian, so compare from the most significant end
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+impl PartialOrd for Uint192 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 impl fmt::Display for Uint192 {
@@ -52,3 +203,89 @@ impl fmt::Debug for Uint192 {
 }
 
 pub use uint256::Uint256;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uint192_ordering() {
+        assert!(Uint192::from_u64(256) > Uint192::from_u64(255));
+        assert!(Uint192::from_u64(1) < Uint192::from_u64(u64::MAX));
+        assert_eq!(Uint192::from_u64(42).cmp(&Uint192::from_u64(42)), Ordering::Equal);
+    }
+
+    fn target_pow2_minus_one(exp: usize) -> Uint256 {
+        let mut bytes = [0u8; 32];
+        for byte in bytes.iter_mut().rev().take(exp / 8) {
+            *byte = 0xFF;
+        }
+        Uint256::from(bytes)
+    }
+
+    #[test]
+    fn test_work_from_easy_target() {
+        // 0x1f7fffff decodes to just under 2^247, so on average 2^9 hashes are needed
+        assert_eq!(Uint192::work_from_bits(0x1f7fffff), Uint192::from_u64(512));
+        assert_eq!(Uint192::work_from_bits(0x1d00ffff), Uint192::from_u64(0x1_0001_0001));
+    }
+
+    #[test]
+    fn test_work_from_hard_target() {
+        let work = Uint192::work_from_target(&target_pow2_minus_one(128));
+        let mut expected = [0u8; 24];
+        expected[16] = 1;
+        assert_eq!(work, Uint192(expected));
+        assert!(work > Uint192::from_u64(u64::MAX));
+    }
+
+    #[test]
+    fn test_work_from_target_saturates() {
+        assert_eq!(Uint192::work_from_target(&target_pow2_minus_one(64)), Uint192::MAX);
+        assert_eq!(Uint192::work_from_target(&Uint256::default()), Uint192::MAX);
+    }
+
+    #[test]
+    fn test_uint192_add_sub() {
+        let a = Uint192::from_u64(u64::MAX);
+        let mut expected = [0u8; 24];
+        expected[..8].copy_from_slice(&(u64::MAX - 1).to_le_bytes());
+        expected[8] = 1;
+        assert_eq!(a + a, Uint192(expected));
+        assert_eq!(a + a - a, a);
+
+        let mut sum = Uint192::from_u64(1);
+        sum += Uint192::from_u64(2);
+        assert_eq!(sum, Uint192::from_u64(3));
+        assert_eq!(Uint192::from_u64(1).checked_sub(Uint192::from_u64(2)), None);
+    }
+
+    #[test]
+    fn test_uint192_overflow_boundary() {
+        let one = Uint192::from_u64(1);
+        let below_max = Uint192::MAX - one;
+        assert_eq!(below_max.checked_add(one), Some(Uint192::MAX));
+        assert_eq!(Uint192::MAX.checked_add(one), None);
+        assert_eq!(Uint192::MAX.saturating_add(Uint192::MAX), Uint192::MAX);
+        assert_eq!(below_max.saturating_add(one), Uint192::MAX);
+        assert!((Uint192::MAX - Uint192::MAX).is_zero());
+    }
+
+    #[test]
+    #[should_panic(expected = "attempt to add with overflow")]
+    fn test_uint192_add_overflow_panics() {
+        let _ = Uint192::MAX + Uint192::from_u64(1);
+    }
+
+    #[test]
+    fn test_uint192_from_be_bytes() {
+        let mut be = [0u8; 24];
+        be[23] = 0x2a;
+        be[0] = 0x80;
+        let value = Uint192::from_be_bytes(be);
+        assert_eq!(value.as_bytes()[0], 0x2a);
+        assert_eq!(value.as_bytes()[23], 0x80);
+        assert!(!value.is_zero());
+        assert!(Uint192::from_be_bytes([0; 24]).is_zero());
+    }
+}
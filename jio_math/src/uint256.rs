@@ -7,42 +7,73 @@ use serde::{Serialize, Deserialize};
 pub struct Uint256([u8; 32]);
 
 impl Uint256 {
-    /// Create from compact target bits (Bitcoin-style).
+    /// The largest representable value.
+    pub const MAX: Self = Self([0xFF; 32]);
+
+    /// Create from compact target bits (Bitcoin-style). Targets that overflow 256 bits decode to zero.
     pub fn from_compact_target_bits(bits: u32) -> Self {
         let mut bytes = [0u8; 32];
         let exponent = (bits >> 24) as usize;
         let mantissa = bits & 0x00FF_FFFF;
         if exponent <= 3 {
-            let shift = 3 - exponent;
-            let mantissa_shifted = (mantissa as u32) << (8 * shift);
-            let mantissa_bytes = mantissa_shifted.to_be_bytes();
-            bytes[32 - shift..32].copy_from_slice(&mantissa_bytes[4 - shift..]);
+            let value = mantissa >> (8 * (3 - exponent));
+            bytes[28..].copy_from_slice(&value.to_be_bytes());
         } else {
-            let shift = exponent - 3;
-            if shift < 29 {
-                let mantissa_bytes = (mantissa as u32).to_be_bytes();
-                let start = 32 - 4 - shift;
-                let end = 32 - shift;
-                bytes[start..end].copy_from_slice(&mantissa_bytes);
+            // The mantissa's least significant byte lands `exponent - 3` bytes above the bottom
+            for (i, &byte) in mantissa.to_be_bytes()[1..].iter().enumerate() {
+                match (29 + i).checked_sub(exponent - 3) {
+                    Some(index) => bytes[index] = byte,
+                    None if byte != 0 => return Self::default(),
+                    None => {}
+                }
             }
         }
         Self(bytes)
     }
 
+    /// Encode as canonical compact target bits, the inverse of `from_compact_target_bits`.
+    ///
+    /// The mantissa keeps the three most significant bytes. When its top bit would be set it is
+    /// shifted down a byte and the exponent grows, so the encoding never reads as negative.
+    pub fn to_compact_target_bits(&self) -> u32 {
+        let Some(first) = self.0.iter().position(|&byte| byte != 0) else {
+            return 0;
+        };
+        let mut size = (32 - first) as u32;
+        let mut mantissa = (first..first + 3).fold(0u32, |acc, i| acc << 8 | self.0.get(i).copied().unwrap_or(0) as u32);
+        if mantissa & 0x0080_0000 != 0 {
+            mantissa >>= 8;
+            size += 1;
+        }
+        size << 24 | mantissa
+    }
+
+    /// Get as bytes, most significant first (the layout produced by compact decoding).
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Approximates the value as an `f64`, losing precision beyond 53 significant bits.
+    pub fn to_f64(&self) -> f64 {
+        self.0.iter().fold(0.0, |acc, &byte| acc * 256.0 + byte as f64)
+    }
+
     /// Get the number of bits in the integer.
     pub fn bits(&self) -> u32 {
         let mut bits = 256;
         for &byte in self.0.iter().rev() {
             if byte != 0 {
                 bits -= self.0.iter().rev().position(|&b| b != 0).unwrap() as u32 * 8;
-                bits += (byte as u32).leading_zeros() as u32;
+                bits += (byte as u32).leading_zeros();
                 break;
             }
         }
         256 - bits
     }
 
-    /// Compare with another Uint256.
+    /// Compare with another Uint256. Agrees with the derived `Ord`; kept as an inherent method
+    /// for callers that don't import the trait.
+    #[allow(clippy::should_implement_trait)]
     pub fn cmp(&self, other: &Self) -> Ordering {
         self.0.cmp(&other.0)
     }